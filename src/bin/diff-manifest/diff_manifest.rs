@@ -0,0 +1,413 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use cargo_edit::{
+    find, shell_note, CargoResult, Context, DepKind, Dependency, Manifest, Source, UnstableFlag,
+    UnstableFlags, UnstableOption,
+};
+use clap::Args;
+use serde_derive::Serialize;
+
+/// Compare the dependencies and features of two manifests
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct DiffManifestArgs {
+    /// Manifest to compare against
+    #[clap(value_name = "BASE", parse(from_os_str), conflicts_with = "rev")]
+    base: Option<PathBuf>,
+
+    /// Compare against the manifest at a git revision, like a tag
+    #[clap(
+        long = "ref",
+        value_name = "REV",
+        env = "CARGO_DIFF_MANIFEST_REF",
+        hide_env = true
+    )]
+    rev: Option<String>,
+
+    /// Path to the manifest to compare
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        env = "CARGO_DIFF_MANIFEST_MANIFEST_PATH",
+        hide_env = true
+    )]
+    manifest_path: Option<PathBuf>,
+
+    /// Format to report differences in
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        arg_enum,
+        default_value = "human",
+        env = "CARGO_DIFF_MANIFEST_OUTPUT_FORMAT",
+        hide_env = true
+    )]
+    output_format: OutputFormat,
+
+    /// Unstable (nightly-only) flags
+    #[clap(short = 'Z', value_name = "FLAG", global = true)]
+    unstable_features: Vec<UnstableFlag>,
+}
+
+impl DiffManifestArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+/// Options for `-Z`, see `-Z help`
+const UNSTABLE_OPTIONS: &[UnstableOption] = &[];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum OutputFormat {
+    Human,
+    Json,
+}
+
+/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
+/// messages.
+fn exec(args: DiffManifestArgs) -> CargoResult<()> {
+    UnstableFlags::new(&args.unstable_features, UNSTABLE_OPTIONS)?;
+    let manifest_path = dunce::canonicalize(find(args.manifest_path.as_deref())?)?;
+    let crate_root = manifest_path.parent().expect("at least a parent");
+    let new = read_manifest(&manifest_path)?;
+
+    let (old, old_root) = match (&args.base, &args.rev) {
+        (Some(base), None) => {
+            let base = dunce::canonicalize(base)
+                .with_context(|| format!("Unable to find {}", base.display()))?;
+            let old = read_manifest(&base)?;
+            (old, base.parent().expect("at least a parent").to_owned())
+        }
+        (None, Some(rev)) => (
+            read_manifest_at(&manifest_path, rev)?,
+            crate_root.to_owned(),
+        ),
+        (None, None) => anyhow::bail!("Either a base manifest or `--ref` is required"),
+        (Some(_), Some(_)) => unreachable!("clap should prevent this"),
+    };
+
+    let diff = ManifestDiff::new(&old, &old_root, &new, crate_root)?;
+    match args.output_format {
+        OutputFormat::Human => diff.print(),
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&diff)?;
+            println!("{}", json);
+            Ok(())
+        }
+    }
+}
+
+fn read_manifest(path: &Path) -> CargoResult<Manifest> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    data.parse()
+        .with_context(|| format!("Unable to parse {}", path.display()))
+}
+
+/// Read the manifest as committed at `rev`
+fn read_manifest_at(manifest_path: &Path, rev: &str) -> CargoResult<Manifest> {
+    let repo = git2::Repository::discover(manifest_path)
+        .with_context(|| format!("{} is not in a git repository", manifest_path.display()))?;
+    let workdir = repo
+        .workdir()
+        .with_context(|| "Bare repositories are not supported")?;
+    let workdir = dunce::canonicalize(workdir)?;
+    let relpath = manifest_path
+        .strip_prefix(&workdir)
+        .with_context(|| format!("{} is outside of the repository", manifest_path.display()))?;
+
+    let tree = repo
+        .revparse_single(rev)
+        .and_then(|object| object.peel_to_tree())
+        .with_context(|| format!("Unknown git revision `{}`", rev))?;
+    let blob = tree
+        .get_path(relpath)
+        .and_then(|entry| entry.to_object(&repo))
+        .and_then(|object| object.peel_to_blob())
+        .with_context(|| format!("{} does not exist at `{}`", relpath.display(), rev))?;
+    let data = std::str::from_utf8(blob.content())
+        .with_context(|| format!("{} is not UTF-8 at `{}`", relpath.display(), rev))?;
+    data.parse()
+        .with_context(|| format!("Unable to parse {} at `{}`", relpath.display(), rev))
+}
+
+/// Differences between two manifests
+#[derive(Debug, Serialize)]
+struct ManifestDiff {
+    dependencies: Vec<DependencyChange>,
+    features: Vec<FeatureChange>,
+}
+
+impl ManifestDiff {
+    fn new(old: &Manifest, old_root: &Path, new: &Manifest, new_root: &Path) -> CargoResult<Self> {
+        let dependencies = diff_maps(dependencies(old, old_root)?, dependencies(new, new_root)?)
+            .into_iter()
+            .map(|((kind, target, name), change)| DependencyChange {
+                table: table_name(kind, target.as_deref()),
+                name,
+                change,
+            })
+            .collect();
+        let features = diff_maps(features(old), features(new))
+            .into_iter()
+            .map(|(name, change)| FeatureChange { name, change })
+            .collect();
+        Ok(Self {
+            dependencies,
+            features,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.dependencies.is_empty() && self.features.is_empty()
+    }
+
+    fn print(&self) -> CargoResult<()> {
+        if self.is_empty() {
+            return shell_note("manifests have the same dependencies and features");
+        }
+
+        let mut table = None;
+        for dependency in &self.dependencies {
+            if table != Some(&dependency.table) {
+                println!("{}:", dependency.table);
+                table = Some(&dependency.table);
+            }
+            println!("  {}", dependency.change.display(&dependency.name, " "));
+        }
+        if !self.features.is_empty() {
+            println!("features:");
+        }
+        for feature in &self.features {
+            println!("  {}", feature.change.display(&feature.name, " = "));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DependencyChange {
+    table: String,
+    name: String,
+    #[serde(flatten)]
+    change: Change<DependencySummary>,
+}
+
+#[derive(Debug, Serialize)]
+struct FeatureChange {
+    name: String,
+    #[serde(flatten)]
+    change: Change<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "change", rename_all = "kebab-case")]
+enum Change<T> {
+    Added { new: T },
+    Removed { old: T },
+    Changed { old: T, new: T },
+}
+
+impl<T: Describe> Change<T> {
+    fn display(&self, name: &str, separator: &str) -> String {
+        match self {
+            Self::Added { new } => format!("+ {}{}{}", name, separator, new.describe()),
+            Self::Removed { old } => format!("- {}{}{}", name, separator, old.describe()),
+            Self::Changed { old, new } => format!(
+                "~ {}{}{} -> {}",
+                name,
+                separator,
+                old.describe(),
+                new.describe()
+            ),
+        }
+    }
+}
+
+trait Describe {
+    fn describe(&self) -> String;
+}
+
+impl Describe for Vec<String> {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+/// The parts of a dependency that matter to its users, ignoring formatting
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct DependencySummary {
+    version: Option<String>,
+    source: Option<String>,
+    package: Option<String>,
+    features: Vec<String>,
+    default_features: bool,
+    optional: bool,
+}
+
+impl DependencySummary {
+    fn new(dependency: &Dependency, crate_root: &Path) -> Self {
+        let source = match dependency.source() {
+            Some(Source::Registry(_)) | None => dependency
+                .registry()
+                .map(|registry| format!("registry+{}", registry)),
+            Some(Source::Path(src)) => {
+                let relpath =
+                    pathdiff::diff_paths(&src.path, crate_root).unwrap_or_else(|| src.path.clone());
+                Some(format!(
+                    "path+{}",
+                    relpath.to_string_lossy().replace('\\', "/")
+                ))
+            }
+            Some(Source::Git(src)) => Some(format!("git+{}", src)),
+            Some(source) => Some(source.to_string()),
+        };
+        Self {
+            version: dependency.version().map(|v| v.to_owned()),
+            source,
+            package: dependency.rename().map(|_| dependency.name.clone()),
+            features: dependency.features.clone().unwrap_or_default(),
+            default_features: dependency.default_features().unwrap_or(true),
+            optional: dependency.optional().unwrap_or(false),
+        }
+    }
+}
+
+impl Describe for DependencySummary {
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        parts.extend(self.version.clone());
+        parts.extend(self.source.clone());
+        if let Some(package) = &self.package {
+            parts.push(format!("package={}", package));
+        }
+        if !self.features.is_empty() {
+            parts.push(format!("features={}", self.features.join(",")));
+        }
+        if !self.default_features {
+            parts.push("no-default-features".to_owned());
+        }
+        if self.optional {
+            parts.push("optional".to_owned());
+        }
+        if parts.is_empty() {
+            parts.push("*".to_owned());
+        }
+        parts.join(" ")
+    }
+}
+
+type DependencyKey = (DepKind, Option<String>, String);
+
+fn dependencies(
+    manifest: &Manifest,
+    crate_root: &Path,
+) -> CargoResult<BTreeMap<DependencyKey, DependencySummary>> {
+    let mut dependencies = BTreeMap::new();
+    for (table, item) in manifest.get_sections() {
+        let table_like = item.as_table_like().expect("sections are tables");
+        for (key, item) in table_like.iter() {
+            let dependency = Dependency::from_toml(crate_root, key, item).with_context(|| {
+                format!(
+                    "Invalid dependency {} in {}",
+                    key,
+                    table_name(table.kind(), table.target())
+                )
+            })?;
+            dependencies.insert(
+                (
+                    table.kind(),
+                    table.target().map(|t| t.to_owned()),
+                    key.to_owned(),
+                ),
+                DependencySummary::new(&dependency, crate_root),
+            );
+        }
+    }
+    Ok(dependencies)
+}
+
+fn features(manifest: &Manifest) -> BTreeMap<String, Vec<String>> {
+    manifest
+        .data
+        .get("features")
+        .and_then(|f| f.as_table_like())
+        .into_iter()
+        .flat_map(|f| f.iter())
+        .map(|(name, values)| {
+            let values = values
+                .as_array()
+                .into_iter()
+                .flat_map(|a| a.iter())
+                .filter_map(|v| v.as_str())
+                .map(|v| v.to_owned())
+                .collect();
+            (name.to_owned(), values)
+        })
+        .collect()
+}
+
+fn table_name(kind: DepKind, target: Option<&str>) -> String {
+    let kind_table = cargo_edit::DepTable::from(kind).kind_table().to_owned();
+    match target {
+        Some(target) => format!("target.'{}'.{}", target, kind_table),
+        None => kind_table,
+    }
+}
+
+fn diff_maps<K: Ord + Clone, V: PartialEq>(
+    mut old: BTreeMap<K, V>,
+    mut new: BTreeMap<K, V>,
+) -> Vec<(K, Change<V>)> {
+    let keys = old
+        .keys()
+        .chain(new.keys())
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    keys.into_iter()
+        .filter_map(|key| {
+            let change = match (old.remove(&key), new.remove(&key)) {
+                (Some(old), Some(new)) if old == new => return None,
+                (Some(old), Some(new)) => Change::Changed { old, new },
+                (Some(old), None) => Change::Removed { old },
+                (None, Some(new)) => Change::Added { new },
+                (None, None) => unreachable!("key came from one of the maps"),
+            };
+            Some((key, change))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn diff_maps_reports_each_change() {
+        let old = BTreeMap::from([("a", 1), ("b", 2), ("c", 3)]);
+        let new = BTreeMap::from([("b", 2), ("c", 4), ("d", 5)]);
+        let actual = diff_maps(old, new)
+            .into_iter()
+            .map(|(key, change)| {
+                let change = match change {
+                    Change::Added { new } => format!("+{}", new),
+                    Change::Removed { old } => format!("-{}", old),
+                    Change::Changed { old, new } => format!("{}->{}", old, new),
+                };
+                (key, change)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actual,
+            vec![
+                ("a", "-1".to_owned()),
+                ("c", "3->4".to_owned()),
+                ("d", "+5".to_owned())
+            ]
+        );
+    }
+}
@@ -0,0 +1,362 @@
+use std::path::PathBuf;
+
+use cargo_edit::{
+    filter_members, resolve_manifests, set_dep_version, shell_status, shell_warn, CargoFlags,
+    CargoResult, Context, DepKind, DepTable, Dependency, LocalManifest, ManifestTransaction,
+    RegistrySource,
+};
+use clap::Args;
+use serde_derive::Deserialize;
+
+use crate::cli::DryRunArg;
+
+/// Apply a file of dependency edits to the workspace
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct ApplyArgs {
+    /// TOML file listing the edits, as `[[edit]]` tables
+    #[clap(value_name = "FILE", parse(from_os_str))]
+    file: PathBuf,
+
+    /// Path to the manifest to apply the edits to
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        env = "CARGO_EDIT_APPLY_MANIFEST_PATH",
+        hide_env = true
+    )]
+    manifest_path: Option<PathBuf>,
+
+    /// Package to apply the edits to
+    #[clap(
+        long = "package",
+        short = 'p',
+        value_name = "PKGID",
+        conflicts_with = "workspace",
+        env = "CARGO_EDIT_APPLY_PACKAGE",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    pkgid: Vec<String>,
+
+    /// Apply the edits to all packages in the workspace
+    #[clap(long, env = "CARGO_EDIT_APPLY_WORKSPACE", hide_env = true)]
+    workspace: bool,
+
+    /// Apply the edits to the members in directories matching this glob
+    #[clap(
+        long,
+        value_name = "GLOB",
+        conflicts_with = "pkgid",
+        env = "CARGO_EDIT_APPLY_MEMBERS",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    members: Vec<String>,
+
+    /// Print changes to be made without making them
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        arg_enum,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "summary",
+        env = "CARGO_EDIT_APPLY_DRY_RUN",
+        hide_env = true
+    )]
+    dry_run: Option<DryRunArg>,
+}
+
+impl ApplyArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+/// The contents of an edit script
+#[derive(Debug, Deserialize)]
+struct EditScript {
+    #[serde(default)]
+    edit: Vec<Edit>,
+}
+
+/// One change to the dependencies of the selected packages, or only those in `packages`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+enum Edit {
+    /// Add the dependency, or update its requirement and features if it is already there
+    Add {
+        name: String,
+        version: String,
+        #[serde(default)]
+        kind: Kind,
+        features: Option<Vec<String>>,
+        #[serde(default)]
+        packages: Vec<String>,
+    },
+    Remove {
+        name: String,
+        #[serde(default)]
+        kind: Kind,
+        #[serde(default)]
+        packages: Vec<String>,
+    },
+    /// Change the requirement wherever the dependency is, or only for one kind
+    SetVersion {
+        name: String,
+        version: String,
+        kind: Option<Kind>,
+        #[serde(default)]
+        packages: Vec<String>,
+    },
+    /// Replace the features wherever the dependency is, or only for one kind
+    SetFeatures {
+        name: String,
+        features: Vec<String>,
+        kind: Option<Kind>,
+        #[serde(default)]
+        packages: Vec<String>,
+    },
+}
+
+impl Edit {
+    fn name(&self) -> &str {
+        match self {
+            Self::Add { name, .. }
+            | Self::Remove { name, .. }
+            | Self::SetVersion { name, .. }
+            | Self::SetFeatures { name, .. } => name,
+        }
+    }
+
+    fn applies_to(&self, package: &str) -> bool {
+        let packages = match self {
+            Self::Add { packages, .. }
+            | Self::Remove { packages, .. }
+            | Self::SetVersion { packages, .. }
+            | Self::SetFeatures { packages, .. } => packages,
+        };
+        packages.is_empty() || packages.iter().any(|p| p == package)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Kind {
+    Normal,
+    Dev,
+    Build,
+}
+
+impl Default for Kind {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+impl From<Kind> for DepKind {
+    fn from(kind: Kind) -> Self {
+        match kind {
+            Kind::Normal => DepKind::Normal,
+            Kind::Dev => DepKind::Development,
+            Kind::Build => DepKind::Build,
+        }
+    }
+}
+
+fn exec(args: ApplyArgs) -> CargoResult<()> {
+    let content = std::fs::read_to_string(&args.file)
+        .with_context(|| format!("Failed to read {}", args.file.display()))?;
+    let script: EditScript = toml_edit::easy::from_str(&content)
+        .with_context(|| format!("Invalid edit script {}", args.file.display()))?;
+
+    let packages = resolve_manifests(
+        args.manifest_path.as_deref(),
+        args.workspace || !args.members.is_empty(),
+        args.pkgid.iter().map(|s| s.as_str()).collect(),
+        CargoFlags::default(),
+    )?;
+    let packages = filter_members(packages, &args.members)?;
+
+    let mut transaction = ManifestTransaction::new();
+    for (i, edit) in script.edit.iter().enumerate() {
+        let mut applied = false;
+        for package in packages.iter().filter(|p| edit.applies_to(&p.name)) {
+            let manifest = transaction.load(package.manifest_path.as_std_path())?;
+            applied |= apply(manifest, &package.name, edit)
+                .with_context(|| format!("Failed to apply edit {} to {}", i + 1, package.name))?;
+        }
+        if !applied {
+            anyhow::bail!(
+                "edit {}: {} isn't a dependency of any selected package",
+                i + 1,
+                edit.name()
+            );
+        }
+    }
+
+    if let Some(format) = args.dry_run {
+        transaction.report_dry_run(format.into())?;
+    } else {
+        for warning in transaction.future_keys_warnings() {
+            shell_warn(&warning)?;
+        }
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Apply `edit` to the package's manifest, returning whether it had the dependency to edit
+fn apply(manifest: &mut LocalManifest, package: &str, edit: &Edit) -> CargoResult<bool> {
+    let crate_root = manifest
+        .path
+        .parent()
+        .expect("manifest is always in a directory")
+        .to_owned();
+    match edit {
+        Edit::Add {
+            name,
+            version,
+            kind,
+            features,
+            ..
+        } => {
+            let section = DepTable::from(DepKind::from(*kind)).kind_table().to_owned();
+            shell_status(
+                "Adding",
+                &format!("{} {} to {}'s {}", name, version, package, section),
+            )?;
+            let table = manifest
+                .data
+                .entry(&section)
+                .or_insert(toml_edit::table())
+                .as_table_like_mut()
+                .with_context(|| format!("`{}` isn't a table", section))?;
+            let existing = table.iter_mut().find(|(key, _)| key.get() == name);
+            match existing {
+                Some((mut key, item)) => {
+                    let mut dependency = Dependency::from_toml(&crate_root, name, item)?
+                        .set_source(RegistrySource::new(version));
+                    if let Some(features) = features {
+                        dependency = dependency.set_features(features.clone());
+                    }
+                    dependency.update_toml(&crate_root, &mut key, item);
+                }
+                None => {
+                    let mut dependency =
+                        Dependency::new(name).set_source(RegistrySource::new(version));
+                    if let Some(features) = features {
+                        dependency = dependency.set_features(features.clone());
+                    }
+                    table.insert(name, dependency.to_toml(&crate_root));
+                }
+            }
+            Ok(true)
+        }
+        Edit::Remove { name, kind, .. } => {
+            let section = DepTable::from(DepKind::from(*kind)).kind_table().to_owned();
+            let has_dependency = manifest
+                .data
+                .get(&section)
+                .and_then(|table| table.get(name))
+                .is_some();
+            if !has_dependency {
+                return Ok(false);
+            }
+            shell_status(
+                "Removing",
+                &format!("{} from {}'s {}", name, package, section),
+            )?;
+            manifest.remove_from_table(&[section], name)?;
+            manifest.gc_dep(name);
+            Ok(true)
+        }
+        Edit::SetVersion {
+            name,
+            version,
+            kind,
+            ..
+        } => {
+            let mut applied = false;
+            for (section, table) in manifest.get_dependency_sections_mut() {
+                if !selects(*kind, &section) {
+                    continue;
+                }
+                if let Some(item) = table.get_mut(name) {
+                    shell_status(
+                        "Updating",
+                        &format!(
+                            "{} to {} in {}'s {}",
+                            name,
+                            version,
+                            package,
+                            section.kind_table()
+                        ),
+                    )?;
+                    set_dep_version(item, version)?;
+                    applied = true;
+                }
+            }
+            Ok(applied)
+        }
+        Edit::SetFeatures {
+            name,
+            features,
+            kind,
+            ..
+        } => {
+            let mut applied = false;
+            for (section, table) in manifest.get_dependency_sections_mut() {
+                if !selects(*kind, &section) {
+                    continue;
+                }
+                if let Some(item) = table.get_mut(name) {
+                    shell_status(
+                        "Updating",
+                        &format!(
+                            "{}'s features in {}'s {}",
+                            name,
+                            package,
+                            section.kind_table()
+                        ),
+                    )?;
+                    set_features(item, features)?;
+                    applied = true;
+                }
+            }
+            Ok(applied)
+        }
+    }
+}
+
+/// Replace the dependency's features, unlike [`Dependency::update_toml`] which adds to them
+fn set_features(item: &mut toml_edit::Item, features: &[String]) -> CargoResult<()> {
+    if let Some(version) = item.as_str() {
+        let mut table = toml_edit::InlineTable::default();
+        table.insert("version", version.into());
+        *item = toml_edit::value(table);
+    }
+    let table = item
+        .as_table_like_mut()
+        .context("Expected a version or a table")?;
+    if features.is_empty() {
+        table.remove("features");
+    } else {
+        let features = features
+            .iter()
+            .map(|f| f.as_str())
+            .collect::<toml_edit::Value>();
+        table.insert("features", toml_edit::value(features));
+    }
+    Ok(())
+}
+
+/// Whether edits for `kind`, or every kind, apply to the section
+///
+/// Platform-specific sections are left alone.
+fn selects(kind: Option<Kind>, section: &DepTable) -> bool {
+    section.target().is_none() && kind.map_or(true, |kind| section.kind() == kind.into())
+}
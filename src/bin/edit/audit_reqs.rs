@@ -0,0 +1,287 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use cargo_edit::{
+    find, find_workspace_root, get_dep_version, normalize_requirement, set_dep_version,
+    shell_status, shell_warn, workspace_members, CargoFlags, CargoResult, LocalManifest,
+    ManifestTransaction, ReqStyle,
+};
+use clap::Args;
+
+use crate::cli::DryRunArg;
+
+/// Report which styles of version requirements the workspace uses
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct AuditReqsArgs {
+    /// Rewrite requirements by these policies
+    #[clap(
+        long,
+        value_name = "POLICY",
+        arg_enum,
+        env = "CARGO_EDIT_AUDIT_REQS_FIX",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    fix: Vec<Policy>,
+
+    /// Path to the manifest of the workspace to audit
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        env = "CARGO_EDIT_AUDIT_REQS_MANIFEST_PATH",
+        hide_env = true
+    )]
+    manifest_path: Option<PathBuf>,
+
+    /// Run without accessing the network
+    #[clap(long, env = "CARGO_EDIT_AUDIT_REQS_OFFLINE", hide_env = true)]
+    offline: bool,
+
+    /// Print changes to be made without making them
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        arg_enum,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "summary",
+        requires = "fix",
+        env = "CARGO_EDIT_AUDIT_REQS_DRY_RUN",
+        hide_env = true
+    )]
+    dry_run: Option<DryRunArg>,
+}
+
+impl AuditReqsArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum Policy {
+    /// Turn exact and tilde requirements into caret ones, like `=1.2.3` to `1.2.3`
+    Caret,
+    /// Write caret requirements in the `normalize-reqs` style of `cargo upgrade`
+    Style,
+}
+
+/// How a dependency constrains its version, in the order they are reported
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum ReqKind {
+    Caret,
+    Exact,
+    Tilde,
+    Range,
+    Wildcard,
+    /// A git or path dependency without a version
+    Unversioned,
+}
+
+impl ReqKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Caret => "caret",
+            Self::Exact => "exact",
+            Self::Tilde => "tilde",
+            Self::Range => "range",
+            Self::Wildcard => "wildcard",
+            Self::Unversioned => "unversioned",
+        }
+    }
+}
+
+/// A dependency of one of the audited manifests
+struct Entry {
+    /// The package declaring it, or `workspace` for `[workspace.dependencies]`
+    owner: String,
+    name: String,
+    req: String,
+}
+
+fn exec(args: AuditReqsArgs) -> CargoResult<()> {
+    let manifest_path = dunce::canonicalize(find(args.manifest_path.as_deref())?)?;
+    let flags = CargoFlags {
+        offline: args.offline,
+        ..Default::default()
+    };
+    let root = find_workspace_root(&manifest_path).unwrap_or_else(|_| manifest_path.clone());
+    let style = ReqStyle::for_manifest(&root)?;
+
+    let mut transaction = ManifestTransaction::new();
+    let mut entries: BTreeMap<ReqKind, Vec<Entry>> = BTreeMap::new();
+    let mut rewrites = 0;
+    let root_manifest = transaction.load(&root)?;
+    if let Some(table) = root_manifest
+        .data
+        .get_mut("workspace")
+        .and_then(|w| w.get_mut("dependencies"))
+        .and_then(|d| d.as_table_like_mut())
+    {
+        rewrites += audit_table(table, "workspace", &args.fix, style, &mut entries)?;
+    }
+    for member in workspace_members(Some(&root), flags)? {
+        let manifest = transaction.load(member.manifest_path.as_std_path())?;
+        rewrites += audit_manifest(manifest, &member.name, &args.fix, style, &mut entries)?;
+    }
+
+    let total = entries.values().map(Vec::len).sum::<usize>();
+    println!("{} dependencies", total);
+    for (kind, entries) in &entries {
+        println!("{:<12}{:>4}", kind.name(), entries.len());
+        if *kind == ReqKind::Caret {
+            continue;
+        }
+        for entry in entries {
+            println!("    {}: {} {}", entry.owner, entry.name, entry.req);
+        }
+    }
+
+    if rewrites == 0 {
+        return Ok(());
+    }
+    if let Some(format) = args.dry_run {
+        transaction.report_dry_run(format.into())?;
+    } else {
+        for warning in transaction.future_keys_warnings() {
+            shell_warn(&warning)?;
+        }
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+fn audit_manifest(
+    manifest: &mut LocalManifest,
+    owner: &str,
+    fix: &[Policy],
+    style: ReqStyle,
+    entries: &mut BTreeMap<ReqKind, Vec<Entry>>,
+) -> CargoResult<usize> {
+    let mut rewrites = 0;
+    for table in manifest.get_dependency_tables_mut() {
+        rewrites += audit_table(table, owner, fix, style, entries)?;
+    }
+    Ok(rewrites)
+}
+
+/// Record the requirement of each dependency in `table`, rewriting it by the `fix` policies
+fn audit_table(
+    table: &mut dyn toml_edit::TableLike,
+    owner: &str,
+    fix: &[Policy],
+    style: ReqStyle,
+    entries: &mut BTreeMap<ReqKind, Vec<Entry>>,
+) -> CargoResult<usize> {
+    let mut rewrites = 0;
+    for (key, item) in table.iter_mut() {
+        let kind = match req_kind(&item) {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let req = match get_dep_version(&item) {
+            Ok(req) => req.to_owned(),
+            Err(_) => {
+                let source = if item.get("git").is_some() {
+                    "(git)"
+                } else {
+                    "(path)"
+                };
+                source.to_owned()
+            }
+        };
+        if let Some(new_req) = fixed_req(&req, kind, fix, style)? {
+            shell_status(
+                "Rewriting",
+                &format!("{}'s {} from {} to {}", owner, key.get(), req, new_req),
+            )?;
+            set_dep_version(item, &new_req)?;
+            rewrites += 1;
+        }
+        entries.entry(kind).or_default().push(Entry {
+            owner: owner.to_owned(),
+            name: key.get().to_owned(),
+            req,
+        });
+    }
+    Ok(rewrites)
+}
+
+/// The style of the dependency's requirement, skipping the ones inherited from the workspace
+fn req_kind(item: &toml_edit::Item) -> Option<ReqKind> {
+    let inherited = item
+        .get("workspace")
+        .and_then(|w| w.as_bool())
+        .unwrap_or(false);
+    if inherited {
+        return None;
+    }
+    let req = match get_dep_version(item) {
+        Ok(req) => req,
+        Err(_) if item.get("git").is_some() || item.get("path").is_some() => {
+            return Some(ReqKind::Unversioned)
+        }
+        Err(_) => return None,
+    };
+    let req = semver::VersionReq::parse(req).ok()?;
+    let kind = match req.comparators.as_slice() {
+        // `*` parses to no comparators at all
+        [] => ReqKind::Wildcard,
+        [comparator] => match comparator.op {
+            semver::Op::Caret => ReqKind::Caret,
+            semver::Op::Exact => ReqKind::Exact,
+            semver::Op::Tilde => ReqKind::Tilde,
+            semver::Op::Wildcard => ReqKind::Wildcard,
+            _ => ReqKind::Range,
+        },
+        _ => ReqKind::Range,
+    };
+    Some(kind)
+}
+
+/// The requirement `fix` asks for instead of `req`, if it differs
+fn fixed_req(
+    req: &str,
+    kind: ReqKind,
+    fix: &[Policy],
+    style: ReqStyle,
+) -> CargoResult<Option<String>> {
+    if kind == ReqKind::Unversioned {
+        return Ok(None);
+    }
+    let mut new_req = req.to_owned();
+    if fix.contains(&Policy::Caret) && matches!(kind, ReqKind::Exact | ReqKind::Tilde) {
+        let parsed = semver::VersionReq::parse(req)?;
+        let mut comparator = parsed.comparators[0].clone();
+        comparator.op = semver::Op::Caret;
+        new_req = comparator.to_string().trim_start_matches('^').to_owned();
+    }
+    if fix.contains(&Policy::Style) {
+        if let Some(normalized) = normalize_requirement(&new_req, style)? {
+            new_req = normalized;
+        }
+    }
+    if new_req == req {
+        Ok(None)
+    } else {
+        Ok(Some(new_req))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fixes_to_caret() {
+        let fix = [Policy::Caret];
+        let style = ReqStyle::default();
+        let fixed = |req, kind| fixed_req(req, kind, &fix, style).unwrap();
+        assert_eq!(fixed("=1.2.3", ReqKind::Exact).as_deref(), Some("1.2.3"));
+        assert_eq!(fixed("~0.4", ReqKind::Tilde).as_deref(), Some("0.4"));
+        assert_eq!(fixed("1.2", ReqKind::Caret), None);
+        assert_eq!(fixed(">=1, <2", ReqKind::Range), None);
+    }
+}
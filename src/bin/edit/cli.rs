@@ -0,0 +1,45 @@
+use cargo_edit::{CargoResult, DryRun};
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+#[clap(bin_name = "cargo")]
+pub enum Command {
+    Edit(crate::edit::EditArgs),
+}
+
+impl Command {
+    pub fn exec(self) -> CargoResult<()> {
+        match self {
+            Self::Edit(edit) => edit.exec(),
+        }
+    }
+}
+
+/// How `--dry-run` reports the edits it didn't write
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+pub enum DryRunArg {
+    /// Only the command's usual status output
+    // Like the plain flag `--dry-run` used to be, including from the environment
+    #[clap(alias = "true", alias = "1")]
+    Summary,
+    /// A unified diff of every edited manifest
+    Diff,
+    /// The contents of every edited manifest before and after, as JSON
+    Json,
+}
+
+impl From<DryRunArg> for DryRun {
+    fn from(format: DryRunArg) -> Self {
+        match format {
+            DryRunArg::Summary => DryRun::Summary,
+            DryRunArg::Diff => DryRun::Diff,
+            DryRunArg::Json => DryRun::Json,
+        }
+    }
+}
+
+#[test]
+fn verify_app() {
+    use clap::CommandFactory;
+    Command::command().debug_assert()
+}
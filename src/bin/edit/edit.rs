@@ -0,0 +1,50 @@
+use cargo_edit::{CargoResult, UnstableFlag, UnstableFlags, UnstableOption};
+use clap::{Args, Subcommand};
+
+/// Maintain Cargo.toml manifest files
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct EditArgs {
+    #[clap(subcommand)]
+    command: EditCommand,
+
+    /// Unstable (nightly-only) flags
+    #[clap(short = 'Z', value_name = "FLAG", global = true)]
+    unstable_features: Vec<UnstableFlag>,
+}
+
+impl EditArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        UnstableFlags::new(&self.unstable_features, UNSTABLE_OPTIONS)?;
+        match self.command {
+            EditCommand::Apply(apply) => apply.exec(),
+            EditCommand::AuditReqs(audit) => audit.exec(),
+            EditCommand::Import(import) => import.exec(),
+            EditCommand::Inherit(inherit) => inherit.exec(),
+            EditCommand::Licenses(licenses) => licenses.exec(),
+            EditCommand::Lints(lints) => lints.exec(),
+            EditCommand::PreparePublish(prepare) => prepare.exec(),
+            EditCommand::Promote(promote) => promote.exec(),
+            EditCommand::SelfCmd(self_cmd) => self_cmd.exec(),
+            EditCommand::WarmCache(warm) => warm.exec(),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum EditCommand {
+    Apply(crate::apply::ApplyArgs),
+    AuditReqs(crate::audit_reqs::AuditReqsArgs),
+    Import(crate::import::ImportArgs),
+    Inherit(crate::inherit::InheritArgs),
+    Licenses(crate::licenses::LicensesArgs),
+    Lints(crate::lints::LintsArgs),
+    PreparePublish(crate::prepare_publish::PreparePublishArgs),
+    Promote(crate::promote::PromoteArgs),
+    #[clap(name = "self")]
+    SelfCmd(crate::self_check::SelfArgs),
+    WarmCache(crate::warm_cache::WarmCacheArgs),
+}
+
+/// Options for `-Z`, see `-Z help`
+const UNSTABLE_OPTIONS: &[UnstableOption] = &[];
@@ -0,0 +1,345 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use cargo_edit::{
+    find, get_latest_dependency, manifest_from_pkgid, registry_url, shell_note, shell_status,
+    shell_warn, update_registry_index, CargoFlags, CargoResult, Context, DepKind, DepTable,
+    Dependency, ManifestTransaction, RegistrySource,
+};
+use clap::Args;
+
+use crate::cli::DryRunArg;
+
+/// Suggest crates for the dependencies of a JavaScript or Python project
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct ImportArgs {
+    /// `package.json` or `requirements.txt` to read the dependencies from
+    #[clap(value_name = "FILE", parse(from_os_str))]
+    file: PathBuf,
+
+    /// Ecosystem the file is from
+    #[clap(
+        long,
+        value_name = "ECOSYSTEM",
+        arg_enum,
+        env = "CARGO_EDIT_IMPORT_FROM",
+        hide_env = true
+    )]
+    from: Ecosystem,
+
+    /// Add the suggested crates instead of only listing them
+    #[clap(long, env = "CARGO_EDIT_IMPORT_ADD", hide_env = true)]
+    add: bool,
+
+    /// Path to the manifest to add the crates to
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        env = "CARGO_EDIT_IMPORT_MANIFEST_PATH",
+        hide_env = true
+    )]
+    manifest_path: Option<PathBuf>,
+
+    /// Package to add the crates to
+    #[clap(
+        long = "package",
+        short = 'p',
+        value_name = "PKGID",
+        env = "CARGO_EDIT_IMPORT_PACKAGE",
+        hide_env = true
+    )]
+    pkgid: Option<String>,
+
+    /// Run without accessing the network
+    #[clap(long, env = "CARGO_EDIT_IMPORT_OFFLINE", hide_env = true)]
+    offline: bool,
+
+    /// Print changes to be made without making them
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        arg_enum,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "summary",
+        requires = "add",
+        env = "CARGO_EDIT_IMPORT_DRY_RUN",
+        hide_env = true
+    )]
+    dry_run: Option<DryRunArg>,
+}
+
+impl ImportArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum Ecosystem {
+    /// `package.json`
+    Npm,
+    /// `requirements.txt`
+    Pip,
+}
+
+impl Ecosystem {
+    /// Known crates for packages of the ecosystem, sorted by package
+    fn crates(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::Npm => NPM_CRATES,
+            Self::Pip => PIP_CRATES,
+        }
+    }
+}
+
+const NPM_CRATES: &[(&str, &str)] = &[
+    ("@grpc/grpc-js", "tonic"),
+    ("axios", "reqwest"),
+    ("bcrypt", "bcrypt"),
+    ("better-sqlite3", "rusqlite"),
+    ("commander", "clap"),
+    ("date-fns", "chrono"),
+    ("dayjs", "chrono"),
+    ("dotenv", "dotenvy"),
+    ("express", "axum"),
+    ("glob", "glob"),
+    ("ioredis", "redis"),
+    ("js-yaml", "serde_yaml"),
+    ("jsonwebtoken", "jsonwebtoken"),
+    ("moment", "chrono"),
+    ("node-fetch", "reqwest"),
+    ("openssl", "openssl-sys"),
+    ("pg", "tokio-postgres"),
+    ("pino", "tracing"),
+    ("protobufjs", "prost"),
+    ("redis", "redis"),
+    ("semver", "semver"),
+    ("sharp", "image"),
+    ("sqlite3", "rusqlite"),
+    ("toml", "toml"),
+    ("uuid", "uuid"),
+    ("winston", "tracing"),
+    ("ws", "tokio-tungstenite"),
+    ("yaml", "serde_yaml"),
+    ("yargs", "clap"),
+    ("zeromq", "zmq"),
+];
+
+/// Keyed by the normalized name, see [`normalize_pip_name`]
+const PIP_CRATES: &[(&str, &str)] = &[
+    ("aiohttp", "reqwest"),
+    ("bcrypt", "bcrypt"),
+    ("click", "clap"),
+    ("cryptography", "openssl"),
+    ("fastapi", "axum"),
+    ("flask", "axum"),
+    ("grpcio", "tonic"),
+    ("lz4", "lz4"),
+    ("numpy", "ndarray"),
+    ("pillow", "image"),
+    ("protobuf", "prost"),
+    ("psycopg2", "postgres"),
+    ("psycopg2-binary", "postgres"),
+    ("pyjwt", "jsonwebtoken"),
+    ("pyopenssl", "openssl"),
+    ("python-dateutil", "chrono"),
+    ("python-dotenv", "dotenvy"),
+    ("pyyaml", "serde_yaml"),
+    ("pyzmq", "zmq"),
+    ("redis", "redis"),
+    ("regex", "regex"),
+    ("requests", "reqwest"),
+    ("sqlalchemy", "diesel"),
+    ("toml", "toml"),
+    ("zstandard", "zstd"),
+];
+
+/// A dependency of the other project
+struct Package {
+    name: String,
+    kind: DepKind,
+}
+
+fn exec(args: ImportArgs) -> CargoResult<()> {
+    let content = std::fs::read_to_string(&args.file)
+        .with_context(|| format!("Failed to read {}", args.file.display()))?;
+    let packages = match args.from {
+        Ecosystem::Npm => npm_packages(&content)
+            .with_context(|| format!("Invalid package.json {}", args.file.display()))?,
+        Ecosystem::Pip => pip_packages(&content),
+    };
+
+    let known = args.from.crates();
+    let mut suggestions = Vec::new();
+    let mut unknown = Vec::new();
+    for package in &packages {
+        let key = match args.from {
+            Ecosystem::Npm => package.name.clone(),
+            Ecosystem::Pip => normalize_pip_name(&package.name),
+        };
+        match known.binary_search_by_key(&key.as_str(), |&(name, _)| name) {
+            Ok(i) => suggestions.push((package, known[i].1)),
+            Err(_) => unknown.push(package.name.as_str()),
+        }
+    }
+
+    if args.add {
+        add(&args, &suggestions)?;
+    } else {
+        for (package, krate) in &suggestions {
+            let kind = match package.kind {
+                DepKind::Development => " (dev)",
+                _ => "",
+            };
+            println!("{} -> {}{}", package.name, krate, kind);
+        }
+    }
+    if !unknown.is_empty() {
+        shell_note(&format!("no known crates for {}", unknown.join(", ")))?;
+    }
+    if !suggestions.is_empty() {
+        shell_note(
+            "suggestions are best-effort, from a short list of crates with similar purposes; \
+            check each one fits",
+        )?;
+    }
+
+    Ok(())
+}
+
+fn add(args: &ImportArgs, suggestions: &[(&Package, &str)]) -> CargoResult<()> {
+    let manifest_path = match &args.pkgid {
+        Some(pkgid) => {
+            let flags = CargoFlags {
+                offline: args.offline,
+                ..Default::default()
+            };
+            manifest_from_pkgid(args.manifest_path.as_deref(), pkgid, flags)?
+                .manifest_path
+                .into_std_path_buf()
+        }
+        None => find(args.manifest_path.as_deref())?,
+    };
+    let registry_url = registry_url(&manifest_path, None)?;
+    if !args.offline {
+        update_registry_index(&registry_url, false)?;
+    }
+
+    let mut transaction = ManifestTransaction::new();
+    let manifest = transaction.load(&manifest_path)?;
+    let crate_root = manifest
+        .path
+        .parent()
+        .expect("manifest is always in a directory")
+        .to_owned();
+    // Several packages can map to the same crate
+    let mut added = BTreeSet::new();
+    for (package, krate) in suggestions {
+        let section = DepTable::from(package.kind).kind_table().to_owned();
+        let exists = manifest
+            .data
+            .get(&section)
+            .and_then(|table| table.get(krate))
+            .is_some();
+        if exists || !added.insert((section.clone(), *krate)) {
+            continue;
+        }
+        let latest = get_latest_dependency(krate, false, &manifest_path, Some(&registry_url))?;
+        let version = latest
+            .version()
+            .expect("registry packages always have a version");
+        // Cargo ignores build metadata in requirements
+        let version = version.split('+').next().unwrap_or(version);
+        shell_status(
+            "Adding",
+            &format!("{} {} to {} for {}", krate, version, section, package.name),
+        )?;
+        let dependency = Dependency::new(krate).set_source(RegistrySource::new(version));
+        manifest
+            .data
+            .entry(&section)
+            .or_insert(toml_edit::table())
+            .as_table_like_mut()
+            .with_context(|| format!("`{}` isn't a table", section))?
+            .insert(krate, dependency.to_toml(&crate_root));
+    }
+
+    if let Some(format) = args.dry_run {
+        transaction.report_dry_run(format.into())?;
+    } else {
+        for warning in transaction.future_keys_warnings() {
+            shell_warn(&warning)?;
+        }
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Packages in `dependencies` and `devDependencies`
+fn npm_packages(content: &str) -> CargoResult<Vec<Package>> {
+    let manifest: serde_json::Value = serde_json::from_str(content)?;
+    let mut packages = Vec::new();
+    for (field, kind) in [
+        ("dependencies", DepKind::Normal),
+        ("devDependencies", DepKind::Development),
+    ] {
+        if let Some(dependencies) = manifest.get(field).and_then(|d| d.as_object()) {
+            packages.extend(dependencies.keys().map(|name| Package {
+                name: name.clone(),
+                kind,
+            }));
+        }
+    }
+    Ok(packages)
+}
+
+/// Packages required one per line, ignoring comments and options like `-r other.txt`
+fn pip_packages(content: &str) -> Vec<Package> {
+    content
+        .lines()
+        .map(|line| line.split_once('#').map(|(l, _)| l).unwrap_or(line).trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('-'))
+        .map(|line| {
+            let end = line
+                .find(|c: char| !(c.is_ascii_alphanumeric() || "-_.".contains(c)))
+                .unwrap_or(line.len());
+            Package {
+                name: line[..end].to_owned(),
+                kind: DepKind::Normal,
+            }
+        })
+        .filter(|package| !package.name.is_empty())
+        .collect()
+}
+
+/// Compare names like pip does, with `-`, `_` and `.` interchangeable and case ignored
+fn normalize_pip_name(name: &str) -> String {
+    name.to_ascii_lowercase().replace(['_', '.'], "-")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn known_crates_are_sorted() {
+        for ecosystem in [Ecosystem::Npm, Ecosystem::Pip] {
+            let crates = ecosystem.crates();
+            assert!(crates.windows(2).all(|w| w[0].0 < w[1].0));
+        }
+    }
+
+    #[test]
+    fn requirements() {
+        let packages = pip_packages(
+            "# Web\nrequests>=2.28  # HTTP\nPyYAML==6.0\n-r dev.txt\nuvicorn[standard]\n",
+        );
+        let names = packages.iter().map(|p| p.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["requests", "PyYAML", "uvicorn"]);
+        assert_eq!(normalize_pip_name("Python_Dateutil"), "python-dateutil");
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use cargo_edit::{
+    find, find_workspace_root, shell_status, shell_warn, workspace_members, CargoFlags,
+    CargoResult, Context, LocalManifest, ManifestTransaction,
+};
+use clap::Args;
+
+use crate::cli::DryRunArg;
+
+/// Move settings shared by workspace members into the workspace
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct InheritArgs {
+    /// Inherit common `[package]` fields from `[workspace.package]`
+    #[clap(long, env = "CARGO_EDIT_INHERIT_PACKAGE_FIELDS", hide_env = true)]
+    package_fields: bool,
+
+    /// Path to the workspace's manifest
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        env = "CARGO_EDIT_INHERIT_MANIFEST_PATH",
+        hide_env = true
+    )]
+    manifest_path: Option<PathBuf>,
+
+    /// Print changes to be made without making them.
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        arg_enum,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "summary",
+        env = "CARGO_EDIT_INHERIT_DRY_RUN",
+        hide_env = true
+    )]
+    dry_run: Option<DryRunArg>,
+}
+
+impl InheritArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+/// `[package]` fields that are usually the same across a workspace
+const PACKAGE_FIELDS: &[&str] = &[
+    "edition",
+    "license",
+    "authors",
+    "repository",
+    "rust-version",
+];
+
+fn exec(args: InheritArgs) -> CargoResult<()> {
+    if !args.package_fields {
+        anyhow::bail!("Nothing to inherit, pass `--package-fields`");
+    }
+
+    let manifest_path = dunce::canonicalize(find(args.manifest_path.as_deref())?)?;
+    let root = find_workspace_root(&manifest_path)?;
+    let members = workspace_members(Some(&root), CargoFlags::default())?;
+
+    let mut transaction = ManifestTransaction::new();
+    for field in PACKAGE_FIELDS {
+        inherit_package_field(&mut transaction, &root, &members, field)?;
+    }
+
+    if let Some(format) = args.dry_run {
+        transaction.report_dry_run(format.into())?;
+    } else {
+        for warning in transaction.future_keys_warnings() {
+            shell_warn(&warning)?;
+        }
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Hoist `field` into `[workspace.package]` and have members with the same value inherit it
+fn inherit_package_field(
+    transaction: &mut ManifestTransaction,
+    root: &Path,
+    members: &[cargo_metadata::Package],
+    field: &str,
+) -> CargoResult<()> {
+    let mut declared = Vec::new();
+    for member in members {
+        let manifest = transaction.load(member.manifest_path.as_std_path())?;
+        if let Some(value) = package_field(manifest, field) {
+            declared.push((member, value));
+        }
+    }
+
+    let root_manifest = transaction.load(root)?;
+    let shared = match workspace_package_field(root_manifest, field) {
+        Some(shared) => shared,
+        None => {
+            let distinct = declared
+                .iter()
+                .map(|(_, value)| value.to_string())
+                .collect::<BTreeSet<_>>();
+            match distinct.len() {
+                0 => return Ok(()),
+                1 => {
+                    let shared = declared[0].1.clone();
+                    let workspace = root_manifest.data["workspace"]
+                        .as_table_mut()
+                        .with_context(|| format!("Invalid `[workspace]` in {}", root.display()))?;
+                    workspace.entry("package").or_insert(toml_edit::table())[field] =
+                        toml_edit::Item::Value(shared.clone());
+                    shared
+                }
+                _ => {
+                    let values = declared
+                        .iter()
+                        .map(|(member, value)| format!("{} = {}", member.name, value))
+                        .collect::<Vec<_>>();
+                    shell_warn(&format!(
+                        "not inheriting `{}`, members disagree: {}",
+                        field,
+                        values.join(", ")
+                    ))?;
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    let mut inheriting = Vec::new();
+    let mut conflicting = Vec::new();
+    for (member, value) in declared {
+        if value.to_string() == shared.to_string() {
+            let manifest = transaction.load(member.manifest_path.as_std_path())?;
+            inherit_field(manifest, field);
+            inheriting.push(member.name.as_str());
+        } else {
+            conflicting.push(format!("{} = {}", member.name, value));
+        }
+    }
+    if !inheriting.is_empty() {
+        shell_status(
+            "Inheriting",
+            &format!("`{}` in {}", field, inheriting.join(", ")),
+        )?;
+    }
+    if !conflicting.is_empty() {
+        shell_warn(&format!(
+            "not inheriting `{}` where it differs from the workspace's {}: {}",
+            field,
+            shared,
+            conflicting.join(", ")
+        ))?;
+    }
+    Ok(())
+}
+
+/// A member's own value for `package.<field>`, ignoring inherited values
+fn package_field(manifest: &LocalManifest, field: &str) -> Option<toml_edit::Value> {
+    let value = manifest.data.get("package")?.get(field)?.as_value()?;
+    let inherited = value
+        .as_inline_table()
+        .map(|t| t.contains_key("workspace"))
+        .unwrap_or(false);
+    if inherited {
+        None
+    } else {
+        Some(normalize(value))
+    }
+}
+
+fn workspace_package_field(manifest: &LocalManifest, field: &str) -> Option<toml_edit::Value> {
+    let value = manifest
+        .data
+        .get("workspace")?
+        .get("package")?
+        .get(field)?
+        .as_value()?;
+    Some(normalize(value))
+}
+
+/// Strip formatting so values can be compared
+fn normalize(value: &toml_edit::Value) -> toml_edit::Value {
+    let mut value = value.clone();
+    value.fmt();
+    value.decor_mut().clear();
+    value
+}
+
+/// Replace `package.<field>` with `<field>.workspace = true`
+fn inherit_field(manifest: &mut LocalManifest, field: &str) {
+    let mut inherited = toml_edit::Table::new();
+    inherited.set_dotted(true);
+    inherited["workspace"] = toml_edit::value(true);
+    manifest.data["package"][field] = toml_edit::Item::Table(inherited);
+}
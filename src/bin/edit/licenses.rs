@@ -0,0 +1,281 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use cargo_edit::{
+    find, shell_warn, CargoFlags, CargoResult, Context, LocalManifest, ManifestTransaction,
+};
+use cargo_metadata::{Metadata, Package, PackageId};
+use clap::Args;
+
+use crate::cli::DryRunArg;
+
+/// List the licenses of the direct dependencies
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct LicensesArgs {
+    /// Fail when a dependency can only be used under one of these licenses
+    #[clap(
+        long,
+        value_name = "LICENSE",
+        env = "CARGO_EDIT_LICENSES_DENY",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    deny: Vec<String>,
+
+    /// Comment each dependency in the manifest with its license
+    #[clap(long, env = "CARGO_EDIT_LICENSES_ANNOTATE", hide_env = true)]
+    annotate: bool,
+
+    /// Path to the manifest to list the dependencies of
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        env = "CARGO_EDIT_LICENSES_MANIFEST_PATH",
+        hide_env = true
+    )]
+    manifest_path: Option<PathBuf>,
+
+    /// Run without accessing the network
+    #[clap(long, env = "CARGO_EDIT_LICENSES_OFFLINE", hide_env = true)]
+    offline: bool,
+
+    /// Require `Cargo.toml` to be up to date
+    #[clap(long, env = "CARGO_EDIT_LICENSES_LOCKED", hide_env = true)]
+    locked: bool,
+
+    /// Print changes to be made without making them
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        arg_enum,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "summary",
+        requires = "annotate",
+        env = "CARGO_EDIT_LICENSES_DRY_RUN",
+        hide_env = true
+    )]
+    dry_run: Option<DryRunArg>,
+}
+
+impl LicensesArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+
+    fn cargo_flags(&self) -> CargoFlags {
+        CargoFlags {
+            offline: self.offline,
+            locked: self.locked,
+            ..Default::default()
+        }
+    }
+}
+
+/// Prefix of the comments `--annotate` writes, so they can be told apart from the user's
+const ANNOTATION: &str = "# license: ";
+
+fn exec(args: LicensesArgs) -> CargoResult<()> {
+    let manifest_path = dunce::canonicalize(find(args.manifest_path.as_deref())?)?;
+    let mut cmd = cargo_metadata::MetadataCommand::new();
+    cmd.manifest_path(&manifest_path);
+    // Optional dependencies are shipped too, whether or not the default features enable them
+    cmd.features(cargo_metadata::CargoOpt::AllFeatures);
+    args.cargo_flags().apply(&mut cmd);
+    let metadata = cmd.exec().with_context(|| "Invalid manifest")?;
+
+    let package = metadata
+        .packages
+        .iter()
+        .find(|p| dunce::canonicalize(&p.manifest_path).ok().as_ref() == Some(&manifest_path))
+        .with_context(|| format!("No package found at {}", manifest_path.display()))?;
+
+    // Several entries can resolve to the same package, like for different targets
+    let mut by_license: BTreeMap<String, BTreeSet<(&str, String)>> = BTreeMap::new();
+    let mut by_key = BTreeMap::new();
+    let mut denied = BTreeMap::new();
+    for dependency in &package.dependencies {
+        let resolved = match resolved_dependency(&metadata, package, dependency) {
+            Some(resolved) => resolved,
+            // Dependencies for other platforms
+            None => continue,
+        };
+        let license = license(resolved);
+        by_license
+            .entry(license.clone())
+            .or_default()
+            .insert((resolved.name.as_str(), resolved.version.to_string()));
+        let key = dependency.rename.as_ref().unwrap_or(&dependency.name);
+        by_key.insert(key.as_str(), license.clone());
+        if let Some(expression) = &resolved.license {
+            if !is_allowed(expression, &args.deny) {
+                denied.insert(
+                    (resolved.name.as_str(), resolved.version.to_string()),
+                    expression.as_str(),
+                );
+            }
+        }
+    }
+
+    for (license, packages) in &by_license {
+        println!("{}", license);
+        for (name, version) in packages {
+            println!("    {} {}", name, version);
+        }
+    }
+
+    if args.annotate {
+        let mut transaction = ManifestTransaction::new();
+        annotate(transaction.load(&manifest_path)?, &by_key);
+        if let Some(format) = args.dry_run {
+            transaction.report_dry_run(format.into())?;
+        } else {
+            for warning in transaction.future_keys_warnings() {
+                shell_warn(&warning)?;
+            }
+            transaction.commit()?;
+        }
+    }
+
+    for ((name, version), license) in &denied {
+        shell_warn(&format!(
+            "{} {} is only available under denied licenses: {}",
+            name, version, license
+        ))?;
+    }
+    match denied.len() {
+        0 => Ok(()),
+        1 => anyhow::bail!("1 dependency has denied licenses"),
+        n => anyhow::bail!("{} dependencies have denied licenses", n),
+    }
+}
+
+/// The package a dependency resolved to
+fn resolved_dependency<'m>(
+    metadata: &'m Metadata,
+    package: &Package,
+    dependency: &cargo_metadata::Dependency,
+) -> Option<&'m Package> {
+    let resolve = metadata.resolve.as_ref()?;
+    let node = resolve.nodes.iter().find(|n| n.id == package.id)?;
+    let packages = metadata
+        .packages
+        .iter()
+        .map(|p| (&p.id, p))
+        .collect::<BTreeMap<&PackageId, _>>();
+    node.deps
+        .iter()
+        .filter_map(|dep| packages.get(&dep.pkg).copied())
+        .find(|resolved| {
+            resolved.name == dependency.name && dependency.req.matches(&resolved.version)
+        })
+}
+
+/// The SPDX expression from the package's manifest, or where to find the license text
+fn license(package: &Package) -> String {
+    match (&package.license, &package.license_file) {
+        (Some(license), _) => license.clone(),
+        (None, Some(file)) => format!("(see {})", file),
+        (None, None) => "(unknown)".to_owned(),
+    }
+}
+
+/// Comment the dependencies with their license, leaving alone those with other comments
+fn annotate(manifest: &mut LocalManifest, by_key: &BTreeMap<&str, String>) {
+    for (_, table) in manifest.get_dependency_sections_mut() {
+        for (key, item) in table.iter_mut() {
+            let license = match by_key.get(key.get()) {
+                Some(license) => license,
+                None => continue,
+            };
+            // `[dependencies.foo]` tables have nowhere on the line to put the comment
+            let value = match item.as_value_mut() {
+                Some(value) => value,
+                None => continue,
+            };
+            let suffix = value.decor().suffix().unwrap_or("").trim();
+            if suffix.is_empty() || suffix.starts_with(ANNOTATION) {
+                value
+                    .decor_mut()
+                    .set_suffix(format!(" {}{}", ANNOTATION, license));
+            }
+        }
+    }
+}
+
+/// Whether some choice of licenses in the SPDX `expression` avoids the `denied` ones
+fn is_allowed(expression: &str, denied: &[String]) -> bool {
+    // Older manifests separate the alternatives with `/`
+    let expression = expression
+        .replace('/', " OR ")
+        .replace('(', " ( ")
+        .replace(')', " ) ");
+    let mut tokens = expression.split_whitespace().peekable();
+    allowed_any(&mut tokens, denied)
+}
+
+type Tokens<'e> = std::iter::Peekable<std::str::SplitWhitespace<'e>>;
+
+fn allowed_any(tokens: &mut Tokens<'_>, denied: &[String]) -> bool {
+    let mut allowed = allowed_all(tokens, denied);
+    while tokens.next_if_eq(&"OR").is_some() {
+        // Evaluated either way, to consume the tokens
+        allowed |= allowed_all(tokens, denied);
+    }
+    allowed
+}
+
+fn allowed_all(tokens: &mut Tokens<'_>, denied: &[String]) -> bool {
+    let mut allowed = allowed_license(tokens, denied);
+    while tokens.next_if_eq(&"AND").is_some() {
+        allowed &= allowed_license(tokens, denied);
+    }
+    allowed
+}
+
+fn allowed_license(tokens: &mut Tokens<'_>, denied: &[String]) -> bool {
+    match tokens.next() {
+        Some("(") => {
+            let allowed = allowed_any(tokens, denied);
+            tokens.next_if_eq(&")");
+            allowed
+        }
+        Some(license) => {
+            // Exceptions only ever grant more
+            if tokens.next_if_eq(&"WITH").is_some() {
+                tokens.next();
+            }
+            !denied.iter().any(|denied| is_license(license, denied))
+        }
+        None => true,
+    }
+}
+
+/// `GPL-3.0` also covers `GPL-3.0-only`, `GPL-3.0-or-later` and `GPL-3.0+`
+fn is_license(license: &str, name: &str) -> bool {
+    let license = license.to_ascii_lowercase();
+    let name = name.to_ascii_lowercase();
+    match license.strip_prefix(&name) {
+        Some(rest) => matches!(rest, "" | "+" | "-only" | "-or-later"),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn denied_licenses() {
+        let denied = ["GPL-3.0".to_owned()];
+        assert!(is_allowed("MIT OR Apache-2.0", &denied));
+        assert!(is_allowed("MIT/GPL-3.0", &denied));
+        assert!(is_allowed("GPL-2.0-only", &denied));
+        assert!(!is_allowed("GPL-3.0-or-later", &denied));
+        assert!(!is_allowed("(MIT OR Apache-2.0) AND GPL-3.0+", &denied));
+        assert!(is_allowed("(MIT AND GPL-3.0) OR Apache-2.0", &denied));
+        assert!(!is_allowed("gpl-3.0 WITH GCC-exception-3.1", &denied));
+    }
+}
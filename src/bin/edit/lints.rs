@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use cargo_edit::{
+    filter_members, find_workspace_root, resolve_manifests, shell_status, shell_warn, CargoFlags,
+    CargoResult, Context, LocalManifest, ManifestTransaction,
+};
+use clap::Args;
+
+use crate::cli::DryRunArg;
+
+/// Inherit `[workspace.lints]` in members whose `[lints]` match it
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct LintsArgs {
+    /// Replace members' `[lints]` that match `[workspace.lints]` with `lints.workspace = true`
+    #[clap(long, env = "CARGO_EDIT_LINTS_INHERIT", hide_env = true)]
+    inherit: bool,
+
+    /// Path to the workspace's manifest
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        env = "CARGO_EDIT_LINTS_MANIFEST_PATH",
+        hide_env = true
+    )]
+    manifest_path: Option<PathBuf>,
+
+    /// Package to compare the lints of
+    #[clap(
+        long = "package",
+        short = 'p',
+        value_name = "PKGID",
+        env = "CARGO_EDIT_LINTS_PACKAGE",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    pkgid: Vec<String>,
+
+    /// Compare the members in directories matching this glob
+    #[clap(
+        long,
+        value_name = "GLOB",
+        conflicts_with = "pkgid",
+        env = "CARGO_EDIT_LINTS_MEMBERS",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    members: Vec<String>,
+
+    /// Print changes to be made without making them
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        arg_enum,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "summary",
+        requires = "inherit",
+        env = "CARGO_EDIT_LINTS_DRY_RUN",
+        hide_env = true
+    )]
+    dry_run: Option<DryRunArg>,
+}
+
+impl LintsArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+/// Lint levels by tool and lint name, with their priority
+type Lints = BTreeMap<String, BTreeMap<String, (String, i64)>>;
+
+fn exec(args: LintsArgs) -> CargoResult<()> {
+    let packages = resolve_manifests(
+        args.manifest_path.as_deref(),
+        args.pkgid.is_empty(),
+        args.pkgid.iter().map(|s| s.as_str()).collect(),
+        CargoFlags::default(),
+    )?;
+    let packages = filter_members(packages, &args.members)?;
+    let first = match packages.first() {
+        Some(first) => first.manifest_path.as_std_path().to_owned(),
+        None => return Ok(()),
+    };
+    let root_manifest = find_workspace_root(&first)?;
+    let workspace_lints = LocalManifest::try_new(&root_manifest)?
+        .data
+        .get("workspace")
+        .and_then(|w| w.get("lints"))
+        .map(|lints| parse_lints(lints).with_context(|| "Invalid `[workspace.lints]`"))
+        .transpose()?
+        .with_context(|| "the workspace has no `[workspace.lints]` to inherit")?;
+
+    let mut transaction = ManifestTransaction::new();
+    for package in &packages {
+        let manifest = transaction.load(package.manifest_path.as_std_path())?;
+        let lints = match manifest.data.get("lints") {
+            Some(lints) => lints,
+            None => continue,
+        };
+        let inherited = lints
+            .get("workspace")
+            .and_then(|w| w.as_bool())
+            .unwrap_or(false);
+        let matches = !inherited
+            && parse_lints(lints)
+                .with_context(|| format!("Invalid `[lints]` in {}", package.name))?
+                == workspace_lints;
+        if !args.inherit {
+            let status = if inherited {
+                "inherits the workspace's lints"
+            } else if matches {
+                "has the workspace's lints and can inherit them"
+            } else {
+                "has its own lints"
+            };
+            println!("{} {}", package.name, status);
+            continue;
+        }
+        if inherited {
+            continue;
+        }
+        if !matches {
+            shell_warn(&format!(
+                "{}'s lints differ from the workspace's, leaving them",
+                package.name
+            ))?;
+            continue;
+        }
+
+        shell_status(
+            "Inheriting",
+            &format!("{}'s lints from the workspace", package.name),
+        )?;
+        let lints = manifest.data["lints"]
+            .as_table_mut()
+            .with_context(|| format!("`lints` in {} must be a table", package.name))?;
+        // `[lints.rust]` headers leave `[lints]` implicit, so take the place of its first table
+        let first_table = lints
+            .iter()
+            .filter_map(|(_, item)| item.as_table())
+            .min_by_key(|table| table.position());
+        let (position, decor) = match first_table {
+            Some(table) if lints.is_implicit() => (table.position(), table.decor().clone()),
+            _ => (lints.position(), lints.decor().clone()),
+        };
+        let mut inherit = toml_edit::Table::new();
+        inherit.insert("workspace", toml_edit::value(true));
+        if let Some(position) = position {
+            inherit.set_position(position);
+        }
+        *inherit.decor_mut() = decor;
+        *lints = inherit;
+    }
+
+    if let Some(format) = args.dry_run {
+        transaction.report_dry_run(format.into())?;
+    } else {
+        for warning in transaction.future_keys_warnings() {
+            shell_warn(&warning)?;
+        }
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Read a `[lints]` table, where each lint is a level or a `{ level, priority }` table
+fn parse_lints(lints: &toml_edit::Item) -> CargoResult<Lints> {
+    let lints = lints
+        .as_table_like()
+        .with_context(|| "`lints` must be a table")?;
+    let mut parsed = Lints::new();
+    for (tool, tool_lints) in lints.iter() {
+        let tool_lints = tool_lints
+            .as_table_like()
+            .with_context(|| format!("`lints.{}` must be a table", tool))?;
+        let parsed_tool = parsed.entry(tool.to_owned()).or_default();
+        for (lint, config) in tool_lints.iter() {
+            let level = config
+                .as_str()
+                .or_else(|| config.get("level")?.as_str())
+                .with_context(|| format!("`lints.{}.{}` has no level", tool, lint))?;
+            let priority = config
+                .get("priority")
+                .and_then(|p| p.as_integer())
+                .unwrap_or(0);
+            parsed_tool.insert(lint.to_owned(), (level.to_owned(), priority));
+        }
+    }
+    Ok(parsed)
+}
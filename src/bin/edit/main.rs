@@ -0,0 +1,39 @@
+//! `cargo edit`
+#![warn(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+mod apply;
+mod audit_reqs;
+mod cli;
+mod edit;
+mod import;
+mod inherit;
+mod licenses;
+mod lints;
+mod prepare_publish;
+mod promote;
+mod self_check;
+mod warm_cache;
+
+use std::process;
+
+use clap::Parser;
+
+fn main() {
+    let args = cli::Command::parse();
+
+    if let Err(err) = args.exec() {
+        eprintln!("Error: {:?}", err);
+
+        process::exit(1);
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use cargo_edit::{
+    filter_members, find_workspace_root, manifest_package, resolve_manifests, shell_status,
+    shell_warn, workspace_members, CargoFlags, CargoResult, Context, DepKind, Dependency,
+    ManifestTransaction, Source,
+};
+use clap::Args;
+
+use crate::cli::DryRunArg;
+
+/// Give path dependencies the version requirement needed to publish
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct PreparePublishArgs {
+    /// Report path dependencies that can't be published instead of fixing them
+    #[clap(long, env = "CARGO_EDIT_PREPARE_PUBLISH_CHECK", hide_env = true)]
+    check: bool,
+
+    /// Path to the manifest to prepare
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        env = "CARGO_EDIT_PREPARE_PUBLISH_MANIFEST_PATH",
+        hide_env = true
+    )]
+    manifest_path: Option<PathBuf>,
+
+    /// Package to prepare
+    #[clap(
+        long = "package",
+        short = 'p',
+        value_name = "PKGID",
+        conflicts_with = "workspace",
+        env = "CARGO_EDIT_PREPARE_PUBLISH_PACKAGE",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    pkgid: Vec<String>,
+
+    /// Prepare all packages in the workspace
+    #[clap(long, env = "CARGO_EDIT_PREPARE_PUBLISH_WORKSPACE", hide_env = true)]
+    workspace: bool,
+
+    /// Prepare the members in directories matching this glob
+    #[clap(
+        long,
+        value_name = "GLOB",
+        conflicts_with = "pkgid",
+        env = "CARGO_EDIT_PREPARE_PUBLISH_MEMBERS",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    members: Vec<String>,
+
+    /// Print changes to be made without making them
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        arg_enum,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "summary",
+        conflicts_with = "check",
+        env = "CARGO_EDIT_PREPARE_PUBLISH_DRY_RUN",
+        hide_env = true
+    )]
+    dry_run: Option<DryRunArg>,
+}
+
+impl PreparePublishArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+fn exec(args: PreparePublishArgs) -> CargoResult<()> {
+    let packages = resolve_manifests(
+        args.manifest_path.as_deref(),
+        args.workspace || !args.members.is_empty(),
+        args.pkgid.iter().map(|s| s.as_str()).collect(),
+        CargoFlags::default(),
+    )?;
+    let packages = filter_members(packages, &args.members)?;
+    let first = match packages.first() {
+        Some(first) => first.manifest_path.as_std_path().to_owned(),
+        None => return Ok(()),
+    };
+    let root_manifest = find_workspace_root(&first).ok();
+    let root_dir = root_manifest
+        .as_deref()
+        .unwrap_or(&first)
+        .parent()
+        .expect("manifest is always in a directory")
+        .to_owned();
+
+    // `cargo metadata` has already resolved versions inherited from the workspace
+    let member_manifest = root_manifest.as_deref().unwrap_or(&first);
+    let versions = workspace_members(Some(member_manifest), CargoFlags::default())?
+        .into_iter()
+        .filter_map(|member| {
+            let dir = member.manifest_path.parent()?.as_std_path().to_owned();
+            Some((dir, member.version))
+        })
+        .collect::<BTreeMap<_, _>>();
+    let mut preparer = Preparer {
+        root_dir,
+        versions,
+        check: args.check,
+        problems: 0,
+    };
+
+    let mut transaction = ManifestTransaction::new();
+    for package in &packages {
+        let manifest = transaction.load(package.manifest_path.as_std_path())?;
+        let crate_root = manifest
+            .path
+            .parent()
+            .expect("manifest is always in a directory")
+            .to_owned();
+        for (kind, table) in manifest.get_dependency_kind_tables_mut() {
+            // `cargo publish` drops path dev-dependencies that have no version
+            if kind == DepKind::Development {
+                continue;
+            }
+            preparer.prepare_table(table, &crate_root, &package.name)?;
+        }
+    }
+    if let Some(root_manifest) = &root_manifest {
+        let root_dir = preparer.root_dir.clone();
+        let manifest = transaction.load(root_manifest)?;
+        if let Some(table) = manifest
+            .data
+            .get_mut("workspace")
+            .and_then(|w| w.get_mut("dependencies"))
+            .and_then(|d| d.as_table_like_mut())
+        {
+            preparer.prepare_table(table, &root_dir, "the workspace")?;
+        }
+    }
+
+    if args.check {
+        match preparer.problems {
+            0 => {}
+            1 => anyhow::bail!("1 path dependency isn't ready to publish"),
+            n => anyhow::bail!("{} path dependencies aren't ready to publish", n),
+        }
+    } else if let Some(format) = args.dry_run {
+        transaction.report_dry_run(format.into())?;
+    } else {
+        for warning in transaction.future_keys_warnings() {
+            shell_warn(&warning)?;
+        }
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Adds versions to, and flags problems with, path dependencies
+struct Preparer {
+    /// Path dependencies outside of this directory won't be published alongside the package
+    root_dir: PathBuf,
+    /// Versions of the workspace members, by package directory
+    versions: BTreeMap<PathBuf, semver::Version>,
+    check: bool,
+    problems: usize,
+}
+
+impl Preparer {
+    fn prepare_table(
+        &mut self,
+        table: &mut dyn toml_edit::TableLike,
+        crate_root: &Path,
+        owner: &str,
+    ) -> CargoResult<()> {
+        for (mut key, item) in table.iter_mut() {
+            let dependency = match Dependency::from_toml(crate_root, key.get(), item) {
+                Ok(dependency) => dependency,
+                Err(_) => continue,
+            };
+            let source = match dependency.source() {
+                Some(Source::Path(source)) => source.clone(),
+                _ => continue,
+            };
+            let path = dunce::canonicalize(&source.path).unwrap_or_else(|_| source.path.clone());
+
+            if !path.starts_with(&self.root_dir) {
+                shell_warn(&format!(
+                    "{}'s dependency {} at {} is outside the workspace",
+                    owner,
+                    key.get(),
+                    path.display()
+                ))?;
+                self.problems += 1;
+            }
+            if source.version.is_some() {
+                continue;
+            }
+            if self.check {
+                shell_warn(&format!(
+                    "{}'s path dependency {} has no version",
+                    owner,
+                    key.get()
+                ))?;
+                self.problems += 1;
+                continue;
+            }
+
+            let version = match self.versions.get(&path) {
+                Some(version) => version.clone(),
+                None => {
+                    manifest_package(&path.join("Cargo.toml"))
+                        .with_context(|| format!("Failed to read the version of {}", key.get()))?
+                        .version
+                }
+            };
+            let version = semver::Version {
+                build: semver::BuildMetadata::EMPTY,
+                ..version
+            };
+            shell_status(
+                "Adding",
+                &format!(
+                    "version {} to {}'s dependency {}",
+                    version,
+                    owner,
+                    key.get()
+                ),
+            )?;
+            dependency
+                .set_source(source.set_version(version.to_string()))
+                .update_toml(crate_root, &mut key, item);
+        }
+        Ok(())
+    }
+}
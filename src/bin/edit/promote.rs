@@ -0,0 +1,240 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use cargo_edit::{
+    find, get_latest_dependency, manifest_from_pkgid, registry_url, shell_status, shell_warn,
+    update_registry_index, CargoFlags, CargoResult, Context, Dependency, ManifestTransaction,
+    RegistrySource, Source,
+};
+use clap::Args;
+
+use crate::cli::DryRunArg;
+
+/// Registry name cargo uses when a dependency doesn't name one
+const CRATES_IO_REGISTRY: &str = "crates-io";
+
+/// Replace git and path dependencies with their latest published version
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct PromoteArgs {
+    /// Dependencies to promote
+    #[clap(value_name = "DEP_ID", required = true)]
+    crates: Vec<String>,
+
+    /// Path to the manifest to promote dependencies in
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        env = "CARGO_EDIT_PROMOTE_MANIFEST_PATH",
+        hide_env = true
+    )]
+    manifest_path: Option<PathBuf>,
+
+    /// Package to promote dependencies in
+    #[clap(
+        long = "package",
+        short = 'p',
+        value_name = "PKGID",
+        env = "CARGO_EDIT_PROMOTE_PACKAGE",
+        hide_env = true
+    )]
+    pkgid: Option<String>,
+
+    /// Run without accessing the network
+    #[clap(long, env = "CARGO_EDIT_PROMOTE_OFFLINE", hide_env = true)]
+    offline: bool,
+
+    /// Print changes to be made without making them
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        arg_enum,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "summary",
+        env = "CARGO_EDIT_PROMOTE_DRY_RUN",
+        hide_env = true
+    )]
+    dry_run: Option<DryRunArg>,
+
+    /// Confirm moving dependencies to a registry they weren't trusted from before
+    #[clap(long, env = "CARGO_EDIT_PROMOTE_TRUST_SOURCE", hide_env = true)]
+    trust_source: bool,
+}
+
+impl PromoteArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+fn exec(args: PromoteArgs) -> CargoResult<()> {
+    let manifest_path = match &args.pkgid {
+        Some(pkgid) => {
+            let flags = CargoFlags {
+                offline: args.offline,
+                ..Default::default()
+            };
+            manifest_from_pkgid(args.manifest_path.as_deref(), pkgid, flags)?
+                .manifest_path
+                .into_std_path_buf()
+        }
+        None => find(args.manifest_path.as_deref())?,
+    };
+
+    let mut transaction = ManifestTransaction::new();
+    let manifest = transaction.load(&manifest_path)?;
+    let crate_root = manifest
+        .path
+        .parent()
+        .expect("manifest is always in a directory")
+        .to_owned();
+    let trusted = trusted_sources(&manifest.data);
+    let mut newly_trusted = Vec::new();
+    let mut found = BTreeSet::new();
+    let mut updated_registries = BTreeSet::new();
+    for table in manifest.get_dependency_tables_mut() {
+        for (mut key, item) in table.iter_mut() {
+            if !args.crates.iter().any(|c| c == key.get()) {
+                continue;
+            }
+            found.insert(key.get().to_owned());
+            let dependency = Dependency::from_toml(&crate_root, key.get(), item)?;
+            match dependency.source() {
+                Some(Source::Git(_)) | Some(Source::Path(_)) => {}
+                Some(Source::Workspace(_)) => {
+                    shell_warn(&format!(
+                        "{} is inherited from the workspace, promote it there",
+                        key.get()
+                    ))?;
+                    continue;
+                }
+                _ => {
+                    shell_warn(&format!("{} is already a registry dependency", key.get()))?;
+                    continue;
+                }
+            }
+
+            let registry = dependency.registry().unwrap_or(CRATES_IO_REGISTRY);
+            if trusted.get(key.get()).map(String::as_str) != Some(registry) {
+                if !args.trust_source {
+                    anyhow::bail!(
+                        "promoting {} would switch it from {} to the `{}` registry; \
+                         confirm with `--trust-source`",
+                        key.get(),
+                        dependency.source().expect("checked above"),
+                        registry
+                    );
+                }
+                newly_trusted.push((key.get().to_owned(), registry.to_owned()));
+            }
+
+            let registry_url = registry_url(&manifest_path, dependency.registry())?;
+            if !args.offline && updated_registries.insert(registry_url.clone()) {
+                update_registry_index(&registry_url, false)?;
+            }
+            let latest = get_latest_dependency(
+                &dependency.name,
+                false,
+                &manifest_path,
+                Some(&registry_url),
+            )?;
+            let version = latest
+                .version()
+                .expect("registry packages always have a version");
+            // Cargo ignores build metadata in requirements
+            let version = version.split('+').next().unwrap_or(version);
+            shell_status(
+                "Promoting",
+                &format!("{} to version {}", dependency.toml_key(), version),
+            )?;
+            dependency
+                .set_source(RegistrySource::new(version))
+                .update_toml(&crate_root, &mut key, item);
+            collapse_version_only(item);
+        }
+    }
+
+    let missing = args
+        .crates
+        .iter()
+        .filter(|c| !found.contains(c.as_str()))
+        .map(|c| c.as_str())
+        .collect::<Vec<_>>();
+    match missing.len() {
+        0 => {}
+        1 => anyhow::bail!("dependency {} doesn't exist", missing.join(", ")),
+        _ => anyhow::bail!("dependencies {} don't exist", missing.join(", ")),
+    }
+    if !newly_trusted.is_empty() {
+        trust_sources(&mut manifest.data, &newly_trusted)?;
+    }
+
+    if let Some(format) = args.dry_run {
+        transaction.report_dry_run(format.into())?;
+    } else {
+        for warning in transaction.future_keys_warnings() {
+            shell_warn(&warning)?;
+        }
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Registries each dependency was confirmed to come from, per
+/// `[package.metadata.cargo-edit.trusted-sources]`
+fn trusted_sources(data: &toml_edit::Document) -> BTreeMap<String, String> {
+    data.get("package")
+        .and_then(|p| p.get("metadata"))
+        .and_then(|m| m.get("cargo-edit"))
+        .and_then(|c| c.get("trusted-sources"))
+        .and_then(|t| t.as_table_like())
+        .into_iter()
+        .flat_map(|t| t.iter())
+        .filter_map(|(name, registry)| Some((name.to_owned(), registry.as_str()?.to_owned())))
+        .collect()
+}
+
+/// Record `--trust-source` confirmations where [`trusted_sources`] reads them
+fn trust_sources(data: &mut toml_edit::Document, trusted: &[(String, String)]) -> CargoResult<()> {
+    let mut table: &mut dyn toml_edit::TableLike = data.as_table_mut();
+    for key in ["package", "metadata", "cargo-edit"] {
+        let mut implicit = toml_edit::Table::new();
+        implicit.set_implicit(true);
+        table = table
+            .entry(key)
+            .or_insert(toml_edit::Item::Table(implicit))
+            .as_table_like_mut()
+            .with_context(|| format!("`{}` must be a table", key))?;
+    }
+    let sources = table
+        .entry("trusted-sources")
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+        .with_context(|| "`trusted-sources` must be a table")?;
+    for (name, registry) in trusted {
+        shell_status(
+            "Trusting",
+            &format!("{} from the `{}` registry", name, registry),
+        )?;
+        sources.insert(name, toml_edit::value(registry.as_str()));
+    }
+    Ok(())
+}
+
+/// Write `foo = { version = "1.0" }` as `foo = "1.0"`, like newly added dependencies
+fn collapse_version_only(item: &mut toml_edit::Item) {
+    let collapsed = item
+        .as_inline_table()
+        .filter(|table| table.len() == 1)
+        .and_then(|table| {
+            let mut version = toml_edit::Value::from(table.get("version")?.as_str()?);
+            *version.decor_mut() = table.decor().clone();
+            Some(version)
+        });
+    if let Some(version) = collapsed {
+        *item = toml_edit::Item::Value(version);
+    }
+}
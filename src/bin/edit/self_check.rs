@@ -0,0 +1,103 @@
+use std::process::Command;
+
+use cargo_edit::{
+    latest_published_version, registry_url, shell_status, shell_warn, CargoResult, Context,
+};
+use clap::{Args, Subcommand};
+
+/// Manage cargo-edit itself
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct SelfArgs {
+    #[clap(subcommand)]
+    command: SelfCommand,
+}
+
+impl SelfArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        match self.command {
+            SelfCommand::Check(check) => check.exec(),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum SelfCommand {
+    Check(CheckArgs),
+}
+
+/// Compare this cargo-edit with the latest release and the active cargo
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct CheckArgs {
+    /// Check against the cached registry index without updating it
+    #[clap(long, env = "CARGO_EDIT_SELF_CHECK_OFFLINE", hide_env = true)]
+    offline: bool,
+}
+
+impl CheckArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+/// A problem between this cargo-edit and the cargo releases matching `cargo`
+struct KnownIssue {
+    cargo: &'static str,
+    message: &'static str,
+}
+
+const KNOWN_ISSUES: &[KnownIssue] = &[
+    KnownIssue {
+        cargo: ">=1.62",
+        message: "this cargo has its own `cargo add`, which runs instead of cargo-edit's",
+    },
+    KnownIssue {
+        cargo: "<1.64",
+        message: "this cargo can't parse the `workspace = true` fields `cargo inherit` writes",
+    },
+];
+
+fn exec(args: CheckArgs) -> CargoResult<()> {
+    let installed = semver::Version::parse(env!("CARGO_PKG_VERSION"))?;
+    let manifest_path = std::env::current_dir()?.join("Cargo.toml");
+    let registry = registry_url(&manifest_path, None)?;
+    let latest = latest_published_version(env!("CARGO_PKG_NAME"), &registry, !args.offline)?;
+    shell_status("Checked", &format!("cargo-edit {}", installed))?;
+    if let Some(latest) = latest.filter(|latest| installed < *latest) {
+        shell_warn(&format!(
+            "cargo-edit {} is available, update with `cargo install cargo-edit`",
+            latest
+        ))?;
+    }
+
+    let cargo = cargo_version()?;
+    shell_status("Checked", &format!("cargo {}", cargo))?;
+    for issue in KNOWN_ISSUES {
+        let req = semver::VersionReq::parse(issue.cargo).expect("known issues are valid");
+        if req.matches(&cargo) {
+            shell_warn(issue.message)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Version of the cargo that runs cargo-edit, or the one in `PATH`
+fn cargo_version() -> CargoResult<semver::Version> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let output = Command::new(cargo)
+        .arg("--version")
+        .output()
+        .context("Failed to run `cargo --version`")?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // Like `cargo 1.64.0 (387270bc7 2022-09-16)`
+    let version = stdout
+        .split_whitespace()
+        .nth(1)
+        .with_context(|| format!("Unexpected `cargo --version` output: {}", stdout.trim()))?;
+    let mut version = semver::Version::parse(version)?;
+    // Nightlies count as the release they become
+    version.pre = semver::Prerelease::EMPTY;
+    Ok(version)
+}
@@ -0,0 +1,100 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use cargo_edit::{
+    find, find_workspace_root, is_crate_indexed, registry_url, shell_status, shell_warn,
+    update_registry_index, workspace_members, CargoFlags, CargoResult, LocalManifest,
+};
+use clap::Args;
+use url::Url;
+
+/// Fetch the registry indexes the workspace needs for `--offline` runs
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct WarmCacheArgs {
+    /// Path to the manifest of the workspace to warm the cache for
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        env = "CARGO_EDIT_WARM_CACHE_MANIFEST_PATH",
+        hide_env = true
+    )]
+    manifest_path: Option<PathBuf>,
+
+    /// Do not print any output in case of success
+    #[clap(long, short, env = "CARGO_EDIT_WARM_CACHE_QUIET", hide_env = true)]
+    quiet: bool,
+}
+
+impl WarmCacheArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+fn exec(args: WarmCacheArgs) -> CargoResult<()> {
+    let manifest_path = dunce::canonicalize(find(args.manifest_path.as_deref())?)?;
+    let root = find_workspace_root(&manifest_path).unwrap_or_else(|_| manifest_path.clone());
+
+    let mut crates = BTreeMap::new();
+    let root_manifest = LocalManifest::try_new(&root)?;
+    if let Some(table) = root_manifest
+        .data
+        .get("workspace")
+        .and_then(|w| w.get("dependencies"))
+        .and_then(|d| d.as_table_like())
+    {
+        collect_registry_crates(&root, table, &mut crates)?;
+    }
+    for member in workspace_members(Some(&root), CargoFlags::default())? {
+        let manifest = LocalManifest::try_new(member.manifest_path.as_std_path())?;
+        for (_, table) in manifest.get_sections() {
+            if let Some(table) = table.as_table_like() {
+                collect_registry_crates(&manifest.path, table, &mut crates)?;
+            }
+        }
+    }
+
+    let mut cached = 0;
+    for (registry, names) in &crates {
+        update_registry_index(registry, args.quiet)?;
+        for name in names {
+            if is_crate_indexed(name, registry)? {
+                cached += 1;
+            } else {
+                shell_warn(&format!("{} is not in the '{}' index", name, registry))?;
+            }
+        }
+    }
+    if !args.quiet {
+        shell_status(
+            "Cached",
+            &format!("{} crates from {} registries", cached, crates.len()),
+        )?;
+    }
+    Ok(())
+}
+
+/// Record the crates of the registry dependencies in `table` by the index they come from
+fn collect_registry_crates(
+    manifest_path: &Path,
+    table: &dyn toml_edit::TableLike,
+    crates: &mut BTreeMap<Url, BTreeSet<String>>,
+) -> CargoResult<()> {
+    for (key, item) in table.iter() {
+        // Inherited dependencies are collected from `[workspace.dependencies]`
+        let inherited = item
+            .get("workspace")
+            .and_then(|w| w.as_bool())
+            .unwrap_or(false);
+        if inherited || item.get("path").is_some() || item.get("git").is_some() {
+            continue;
+        }
+        let registry = item.get("registry").and_then(|r| r.as_str());
+        let name = item.get("package").and_then(|p| p.as_str()).unwrap_or(key);
+        let url = registry_url(manifest_path, registry)?;
+        crates.entry(url).or_default().insert(name.to_owned());
+    }
+    Ok(())
+}
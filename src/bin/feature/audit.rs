@@ -0,0 +1,337 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use cargo_edit::{
+    find, shell_note, shell_status, shell_warn, CargoFlags, CargoResult, Context, Dependency,
+    FeatureValue, LocalManifest, ManifestTransaction,
+};
+use cargo_metadata::{Metadata, Package, PackageId};
+use clap::Args;
+
+use crate::cli::DryRunArg;
+
+/// Report the features enabled on each dependency
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct AuditArgs {
+    /// Set `default-features = false` on these dependencies, keeping their explicit features
+    #[clap(
+        long,
+        value_name = "DEP_ID",
+        env = "CARGO_FEATURE_AUDIT_DISABLE_DEFAULTS",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    disable_defaults: Vec<String>,
+
+    /// Path to the manifest to audit
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        env = "CARGO_FEATURE_AUDIT_MANIFEST_PATH",
+        hide_env = true
+    )]
+    manifest_path: Option<PathBuf>,
+
+    /// Run without accessing the network
+    #[clap(long, env = "CARGO_FEATURE_AUDIT_OFFLINE", hide_env = true)]
+    offline: bool,
+
+    /// Require `Cargo.toml` to be up to date
+    #[clap(long, env = "CARGO_FEATURE_AUDIT_LOCKED", hide_env = true)]
+    locked: bool,
+
+    /// Print changes to be made without making them
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        arg_enum,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "summary",
+        env = "CARGO_FEATURE_AUDIT_DRY_RUN",
+        hide_env = true
+    )]
+    dry_run: Option<DryRunArg>,
+}
+
+impl AuditArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+
+    fn cargo_flags(&self) -> CargoFlags {
+        CargoFlags {
+            offline: self.offline,
+            locked: self.locked,
+            ..Default::default()
+        }
+    }
+}
+
+fn exec(args: AuditArgs) -> CargoResult<()> {
+    let manifest_path = dunce::canonicalize(find(args.manifest_path.as_deref())?)?;
+    let mut cmd = cargo_metadata::MetadataCommand::new();
+    cmd.manifest_path(&manifest_path);
+    args.cargo_flags().apply(&mut cmd);
+    let metadata = cmd.exec().with_context(|| "Invalid manifest")?;
+
+    let package = metadata
+        .packages
+        .iter()
+        .find(|p| dunce::canonicalize(&p.manifest_path).ok().as_ref() == Some(&manifest_path))
+        .with_context(|| format!("No package found at {}", manifest_path.display()))?;
+
+    let mut heavy_defaults = Vec::new();
+    let mut audited = BTreeSet::new();
+    for dependency in &package.dependencies {
+        if !audited.insert(dependency.rename.as_ref().unwrap_or(&dependency.name)) {
+            continue;
+        }
+        let (resolved, enabled) = match resolved_dependency(&metadata, package, dependency) {
+            Some(resolved) => resolved,
+            // Dependencies for other platforms, or disabled optional dependencies
+            None => continue,
+        };
+        let audit = FeatureAudit::new(dependency, resolved, enabled);
+        audit.print();
+        if !audit.default_optional_deps.is_empty() {
+            shell_warn(&format!(
+                "{}'s default features enable optional dependencies: {}",
+                audit.name,
+                audit
+                    .default_optional_deps
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))?;
+            heavy_defaults.push(audit.name.clone());
+        }
+    }
+
+    if args.disable_defaults.is_empty() {
+        if !heavy_defaults.is_empty() {
+            shell_note(&format!(
+                "Re-run with `--disable-defaults {}` to set `default-features = false`",
+                heavy_defaults.join(",")
+            ))?;
+        }
+        return Ok(());
+    }
+
+    let mut transaction = ManifestTransaction::new();
+    disable_defaults(transaction.load(&manifest_path)?, &args.disable_defaults)?;
+    if let Some(format) = args.dry_run {
+        transaction.report_dry_run(format.into())?;
+    } else {
+        for warning in transaction.future_keys_warnings() {
+            shell_warn(&warning)?;
+        }
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+/// The package and enabled features a dependency resolved to
+fn resolved_dependency<'m>(
+    metadata: &'m Metadata,
+    package: &Package,
+    dependency: &cargo_metadata::Dependency,
+) -> Option<(&'m Package, &'m [String])> {
+    let resolve = metadata.resolve.as_ref()?;
+    let node = resolve.nodes.iter().find(|n| n.id == package.id)?;
+    let packages = metadata
+        .packages
+        .iter()
+        .map(|p| (&p.id, p))
+        .collect::<BTreeMap<&PackageId, _>>();
+    node.deps.iter().find_map(|dep| {
+        let resolved = packages.get(&dep.pkg)?;
+        if resolved.name != dependency.name || !dependency.req.matches(&resolved.version) {
+            return None;
+        }
+        let features = resolve.nodes.iter().find(|n| n.id == dep.pkg)?;
+        Some((*resolved, features.features.as_slice()))
+    })
+}
+
+/// Where each enabled feature of a dependency comes from
+struct FeatureAudit {
+    name: String,
+    version: String,
+    enabled: Vec<String>,
+    /// Enabled only through `default`
+    from_defaults: BTreeSet<String>,
+    /// Enabled by another package in the dependency graph
+    unified: BTreeSet<String>,
+    /// Optional dependencies only the default features pull in
+    default_optional_deps: BTreeSet<String>,
+}
+
+impl FeatureAudit {
+    fn new(
+        dependency: &cargo_metadata::Dependency,
+        resolved: &Package,
+        enabled: &[String],
+    ) -> Self {
+        let optional_deps = resolved
+            .dependencies
+            .iter()
+            .filter(|d| d.optional)
+            .map(|d| d.rename.as_ref().unwrap_or(&d.name).as_str())
+            .collect::<BTreeSet<_>>();
+
+        let explicit = expand_features(
+            &resolved.features,
+            &optional_deps,
+            dependency.features.iter().map(|f| f.as_str()),
+        );
+        let defaults = if dependency.uses_default_features {
+            expand_features(&resolved.features, &optional_deps, ["default"])
+        } else {
+            Expanded::default()
+        };
+
+        let enabled_set = enabled.iter().map(|f| f.as_str()).collect::<BTreeSet<_>>();
+        let from_defaults = defaults
+            .features
+            .difference(&explicit.features)
+            .filter(|f| *f != "default" && enabled_set.contains(f.as_str()))
+            .cloned()
+            .collect();
+        let unified = enabled
+            .iter()
+            .filter(|f| !defaults.features.contains(*f) && !explicit.features.contains(*f))
+            .cloned()
+            .collect();
+        let default_optional_deps = defaults
+            .optional_deps
+            .difference(&explicit.optional_deps)
+            .cloned()
+            .collect();
+
+        Self {
+            name: dependency
+                .rename
+                .clone()
+                .unwrap_or_else(|| dependency.name.clone()),
+            version: resolved.version.to_string(),
+            enabled: enabled.to_vec(),
+            from_defaults,
+            unified,
+            default_optional_deps,
+        }
+    }
+
+    fn print(&self) {
+        println!("{} {}", self.name, self.version);
+        if self.enabled.is_empty() {
+            println!("  enabled: (none)");
+        } else {
+            println!("  enabled: {}", self.enabled.join(", "));
+        }
+        if !self.from_defaults.is_empty() {
+            println!("  from defaults: {}", join(&self.from_defaults));
+        }
+        if !self.unified.is_empty() {
+            println!("  from other packages: {}", join(&self.unified));
+        }
+    }
+}
+
+fn join(values: &BTreeSet<String>) -> String {
+    values.iter().cloned().collect::<Vec<_>>().join(", ")
+}
+
+/// Features and optional dependencies activated by some features, transitively
+#[derive(Default)]
+struct Expanded {
+    features: BTreeSet<String>,
+    optional_deps: BTreeSet<String>,
+}
+
+fn expand_features<'f>(
+    available: &BTreeMap<String, Vec<String>>,
+    optional_deps: &BTreeSet<&str>,
+    features: impl IntoIterator<Item = &'f str>,
+) -> Expanded {
+    let mut expanded = Expanded::default();
+    let mut pending = features
+        .into_iter()
+        .map(|f| f.to_owned())
+        .collect::<Vec<_>>();
+    while let Some(feature) = pending.pop() {
+        match FeatureValue::parse(&feature) {
+            FeatureValue::Feature(name) => {
+                if available.contains_key(name) {
+                    if expanded.features.insert(name.to_owned()) {
+                        pending.extend(available[name].iter().cloned());
+                    }
+                } else if optional_deps.contains(name) {
+                    // Implicit feature of an optional dependency
+                    expanded.features.insert(name.to_owned());
+                    expanded.optional_deps.insert(name.to_owned());
+                }
+            }
+            FeatureValue::Dep(name) => {
+                expanded.optional_deps.insert(name.to_owned());
+            }
+            FeatureValue::DepFeature { dep, weak, .. } => {
+                if !weak && optional_deps.contains(dep) {
+                    expanded.optional_deps.insert(dep.to_owned());
+                    if !available.contains_key(dep) {
+                        expanded.features.insert(dep.to_owned());
+                    }
+                }
+            }
+        }
+    }
+    expanded
+}
+
+/// Set `default-features = false` on `deps`, wherever they are declared
+fn disable_defaults(manifest: &mut LocalManifest, deps: &[String]) -> CargoResult<()> {
+    let crate_root = manifest
+        .path
+        .parent()
+        .expect("manifest is always in a directory")
+        .to_owned();
+    let mut found = BTreeSet::new();
+    for table in manifest.get_dependency_tables_mut() {
+        for (mut key, item) in table.iter_mut() {
+            if !deps.iter().any(|d| d == key.get()) {
+                continue;
+            }
+            found.insert(key.get().to_owned());
+            let dependency = Dependency::from_toml(&crate_root, key.get(), item)?;
+            if dependency.source().and_then(|s| s.as_workspace()).is_some() {
+                shell_warn(&format!(
+                    "{} is inherited from the workspace, set `default-features = false` there",
+                    key.get()
+                ))?;
+                continue;
+            }
+            shell_status(
+                "Disabling",
+                &format!("default features of {}", dependency.toml_key()),
+            )?;
+            dependency
+                .set_default_features(false)
+                .update_toml(&crate_root, &mut key, item);
+        }
+    }
+
+    let missing = deps
+        .iter()
+        .filter(|d| !found.contains(d.as_str()))
+        .map(|d| d.as_str())
+        .collect::<Vec<_>>();
+    match missing.len() {
+        0 => Ok(()),
+        1 => anyhow::bail!("dependency {} doesn't exist", missing.join(", ")),
+        _ => anyhow::bail!("dependencies {} don't exist", missing.join(", ")),
+    }
+}
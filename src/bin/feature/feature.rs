@@ -0,0 +1,33 @@
+use cargo_edit::{CargoResult, UnstableFlag, UnstableFlags, UnstableOption};
+use clap::{Args, Subcommand};
+
+/// Tidy up the `[features]` table
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct FeatureArgs {
+    #[clap(subcommand)]
+    command: FeatureCommand,
+
+    /// Unstable (nightly-only) flags
+    #[clap(short = 'Z', value_name = "FLAG", global = true)]
+    unstable_features: Vec<UnstableFlag>,
+}
+
+impl FeatureArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        UnstableFlags::new(&self.unstable_features, UNSTABLE_OPTIONS)?;
+        match self.command {
+            FeatureCommand::Prune(prune) => prune.exec(),
+            FeatureCommand::Audit(audit) => audit.exec(),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum FeatureCommand {
+    Prune(crate::prune::PruneArgs),
+    Audit(crate::audit::AuditArgs),
+}
+
+/// Options for `-Z`, see `-Z help`
+const UNSTABLE_OPTIONS: &[UnstableOption] = &[];
@@ -0,0 +1,218 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use cargo_edit::{
+    filter_members, resolve_manifests, shell_note, shell_status, shell_warn, CargoFlags,
+    CargoResult, FeatureValue, LocalManifest, ManifestTransaction,
+};
+use clap::Args;
+
+use crate::cli::DryRunArg;
+
+/// Remove optional dependencies that no feature enables
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct PruneArgs {
+    /// Remove the unused optional dependencies
+    #[clap(
+        long,
+        conflicts_with = "into-feature",
+        env = "CARGO_FEATURE_PRUNE_REMOVE",
+        hide_env = true
+    )]
+    remove: bool,
+
+    /// Enable the unused optional dependencies from this feature instead
+    #[clap(
+        long,
+        value_name = "FEATURE",
+        env = "CARGO_FEATURE_PRUNE_INTO_FEATURE",
+        hide_env = true
+    )]
+    into_feature: Option<String>,
+
+    /// Path to the manifest to prune
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        env = "CARGO_FEATURE_PRUNE_MANIFEST_PATH",
+        hide_env = true
+    )]
+    manifest_path: Option<PathBuf>,
+
+    /// Package to prune
+    #[clap(
+        long = "package",
+        short = 'p',
+        value_name = "PKGID",
+        conflicts_with = "workspace",
+        env = "CARGO_FEATURE_PRUNE_PACKAGE",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    pkgid: Vec<String>,
+
+    /// Prune all packages in the workspace
+    #[clap(long, env = "CARGO_FEATURE_PRUNE_WORKSPACE", hide_env = true)]
+    workspace: bool,
+
+    /// Prune the members in directories matching this glob
+    #[clap(
+        long,
+        value_name = "GLOB",
+        conflicts_with = "pkgid",
+        env = "CARGO_FEATURE_PRUNE_MEMBERS",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    members: Vec<String>,
+
+    /// Print changes to be made without making them
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        arg_enum,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "summary",
+        env = "CARGO_FEATURE_PRUNE_DRY_RUN",
+        hide_env = true
+    )]
+    dry_run: Option<DryRunArg>,
+}
+
+impl PruneArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+fn exec(args: PruneArgs) -> CargoResult<()> {
+    let packages = resolve_manifests(
+        args.manifest_path.as_deref(),
+        args.workspace || !args.members.is_empty(),
+        args.pkgid.iter().map(|s| s.as_str()).collect(),
+        CargoFlags::default(),
+    )?;
+    let packages = filter_members(packages, &args.members)?;
+
+    let mut any_unused = false;
+    let mut transaction = ManifestTransaction::new();
+    for package in &packages {
+        let manifest = transaction.load(package.manifest_path.as_std_path())?;
+        let unused = unused_optional_deps(manifest);
+        if unused.is_empty() {
+            continue;
+        }
+        any_unused = true;
+
+        for (table_path, dep) in &unused {
+            if args.remove {
+                shell_status(
+                    "Removing",
+                    &format!("{} from {}'s {}", dep, package.name, table_path.join(".")),
+                )?;
+                manifest.remove_from_table(table_path, dep)?;
+            } else if args.into_feature.is_none() {
+                shell_warn(&format!(
+                    "{}'s optional dependency `{}` isn't enabled by any feature",
+                    package.name, dep
+                ))?;
+            }
+        }
+
+        if let Some(feature) = &args.into_feature {
+            let deps = unused
+                .iter()
+                .map(|(_, dep)| dep.as_str())
+                .collect::<BTreeSet<_>>();
+            for dep in deps {
+                shell_status(
+                    "Adding",
+                    &format!("`dep:{}` to {}'s `{}` feature", dep, package.name, feature),
+                )?;
+                enable_from_feature(manifest, feature, dep)?;
+            }
+        }
+    }
+
+    if !any_unused {
+        shell_note("every optional dependency is enabled by a feature")?;
+    } else if !args.remove && args.into_feature.is_none() {
+        shell_note(
+            "Re-run with `--remove` to drop them or `--into-feature <FEATURE>` to enable them",
+        )?;
+    } else if let Some(format) = args.dry_run {
+        transaction.report_dry_run(format.into())?;
+    } else {
+        for warning in transaction.future_keys_warnings() {
+            shell_warn(&warning)?;
+        }
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Optional dependencies that no feature refers to, with the table they are declared in
+///
+/// When `dep:` is never used, every optional dependency is implicitly a feature of the package,
+/// so none of them are considered unused.
+fn unused_optional_deps(manifest: &LocalManifest) -> Vec<(Vec<String>, String)> {
+    let features = manifest.features();
+    let values = features
+        .values()
+        .flatten()
+        .map(|v| FeatureValue::parse(v))
+        .collect::<Vec<_>>();
+    if !values.iter().any(|v| matches!(v, FeatureValue::Dep(_))) {
+        return Vec::new();
+    }
+    let referenced = values
+        .iter()
+        .map(|v| match v {
+            FeatureValue::Feature(name) | FeatureValue::Dep(name) => *name,
+            FeatureValue::DepFeature { dep, .. } => *dep,
+        })
+        .collect::<BTreeSet<_>>();
+
+    let mut unused = Vec::new();
+    for (table, item) in manifest.get_sections() {
+        let deps = match item.as_table_like() {
+            Some(deps) => deps,
+            None => continue,
+        };
+        for (dep, dep_item) in deps.iter() {
+            let optional = dep_item
+                .get("optional")
+                .and_then(|o| o.as_bool())
+                .unwrap_or(false);
+            if optional && !referenced.contains(dep) {
+                let mut table_path = Vec::new();
+                if let Some(target) = table.target() {
+                    table_path.push("target".to_owned());
+                    table_path.push(target.to_owned());
+                }
+                table_path.push(table.kind_table().to_owned());
+                unused.push((table_path, dep.to_owned()));
+            }
+        }
+    }
+    unused
+}
+
+/// Add `dep:<dep>` to `feature`, declaring the feature if needed
+fn enable_from_feature(manifest: &mut LocalManifest, feature: &str, dep: &str) -> CargoResult<()> {
+    let features = manifest.data["features"]
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+        .ok_or_else(|| anyhow::format_err!("`[features]` is not a table"))?;
+    let values = features
+        .entry(feature)
+        .or_insert(toml_edit::value(toml_edit::Array::new()))
+        .as_array_mut()
+        .ok_or_else(|| anyhow::format_err!("feature `{}` is not an array", feature))?;
+    values.push(format!("dep:{}", dep));
+    Ok(())
+}
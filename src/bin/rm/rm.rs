@@ -1,10 +1,16 @@
 use cargo_edit::shell_status;
 use cargo_edit::shell_warn;
 use cargo_edit::CargoResult;
-use cargo_edit::{manifest_from_pkgid, LocalManifest};
+use cargo_edit::{
+    find, find_nested_crates, find_workspace_root, manifest_from_pkgid, normalize_crate_name,
+    workspace_members, CargoFlags, Context, FeatureValue, LocalManifest, ManifestTransaction,
+    UnstableFlag, UnstableFlags, UnstableOption,
+};
 use clap::Args;
 use std::borrow::Cow;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::cli::DryRunArg;
 
 /// Remove a dependency from a Cargo.toml manifest file.
 #[derive(Debug, Args)]
@@ -16,36 +22,142 @@ pub struct RmArgs {
     crates: Vec<String>,
 
     /// Remove as development dependency
-    #[clap(long, short = 'D', conflicts_with = "build", help_heading = "SECTION")]
+    #[clap(
+        long,
+        short = 'D',
+        conflicts_with = "build",
+        help_heading = "SECTION",
+        env = "CARGO_RM_DEV",
+        hide_env = true
+    )]
     dev: bool,
 
     /// Remove as build dependency
-    #[clap(long, short = 'B', conflicts_with = "dev", help_heading = "SECTION")]
+    #[clap(
+        long,
+        short = 'B',
+        conflicts_with = "dev",
+        help_heading = "SECTION",
+        env = "CARGO_RM_BUILD",
+        hide_env = true
+    )]
     build: bool,
 
     /// Remove as dependency from the given target platform
-    #[clap(long, forbid_empty_values = true, help_heading = "SECTION")]
+    #[clap(
+        long,
+        forbid_empty_values = true,
+        help_heading = "SECTION",
+        env = "CARGO_RM_TARGET",
+        hide_env = true
+    )]
     target: Option<String>,
 
+    /// Remove from the workspace's `[workspace.dependencies]`
+    #[clap(
+        long,
+        conflicts_with_all = &["dev", "build", "target", "pkgid"],
+        help_heading = "SECTION",
+        env = "CARGO_RM_WORKSPACE_DEP",
+        hide_env = true
+    )]
+    workspace_dep: bool,
+
     /// Path to the manifest to remove a dependency from
-    #[clap(long, value_name = "PATH", parse(from_os_str))]
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        env = "CARGO_RM_MANIFEST_PATH",
+        hide_env = true
+    )]
     manifest_path: Option<PathBuf>,
 
     /// Package to remove from
-    #[clap(long = "package", short = 'p', value_name = "PKGID")]
+    #[clap(
+        long = "package",
+        short = 'p',
+        value_name = "PKGID",
+        env = "CARGO_RM_PACKAGE",
+        hide_env = true
+    )]
     pkgid: Option<String>,
 
     /// Unstable (nightly-only) flags
-    #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
-    unstable_features: Vec<UnstableOptions>,
+    #[clap(short = 'Z', value_name = "FLAG", global = true)]
+    unstable_features: Vec<UnstableFlag>,
 
     /// Don't actually write the manifest
-    #[clap(long)]
-    dry_run: bool,
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        arg_enum,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "summary",
+        env = "CARGO_RM_DRY_RUN",
+        hide_env = true
+    )]
+    dry_run: Option<DryRunArg>,
+
+    /// Edit the manifest piped to stdin, printing it to stdout
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "manifest-path",
+            "pkgid",
+            "workspace-dep",
+            "dry-run",
+            "recursive",
+            "include-nested"
+        ],
+        env = "CARGO_RM_STDIN",
+        hide_env = true
+    )]
+    stdin: bool,
+
+    /// Print the edited manifest to stdout instead of writing it
+    #[clap(
+        long,
+        conflicts_with_all = &["dry-run", "recursive", "include-nested"],
+        env = "CARGO_RM_STDOUT",
+        hide_env = true
+    )]
+    stdout: bool,
+
+    /// Also drop path crates left unused from the workspace
+    #[clap(
+        long,
+        conflicts_with = "workspace-dep",
+        env = "CARGO_RM_RECURSIVE",
+        hide_env = true
+    )]
+    recursive: bool,
+
+    /// Also remove from non-member crates like `fuzz/` and `xtask/`
+    #[clap(
+        long,
+        conflicts_with = "workspace-dep",
+        env = "CARGO_RM_INCLUDE_NESTED",
+        hide_env = true
+    )]
+    include_nested: bool,
+
+    /// Keep dependency tables left empty by the removal
+    #[clap(long, env = "CARGO_RM_KEEP_EMPTY_TABLES", hide_env = true)]
+    keep_empty_tables: bool,
+
+    /// Remove optional dependencies that features or code still use
+    #[clap(long, env = "CARGO_RM_FORCE", hide_env = true)]
+    force: bool,
 
     /// Do not print any output in case of success
-    #[clap(long, short)]
+    #[clap(long, short, env = "CARGO_RM_QUIET", hide_env = true)]
     quiet: bool,
+
+    /// Require `Cargo.lock` and cache are up to date
+    #[clap(long, env = "CARGO_RM_FROZEN", hide_env = true)]
+    frozen: bool,
 }
 
 impl RmArgs {
@@ -53,8 +165,26 @@ impl RmArgs {
         exec(self)
     }
 
+    /// Remove `dep` from `section`, dropping the table if it's left empty unless asked not to
+    fn remove_from(
+        &self,
+        manifest: &mut LocalManifest,
+        section: &[String],
+        dep: &str,
+    ) -> CargoResult<()> {
+        if self.keep_empty_tables {
+            manifest.remove_from_table_keeping_empty(section, dep)
+        } else {
+            manifest.remove_from_table(section, dep)
+        }
+    }
+
     /// Get dependency section
     pub fn get_section(&self) -> Vec<String> {
+        if self.workspace_dep {
+            return vec!["workspace".to_owned(), "dependencies".to_owned()];
+        }
+
         let section_name = if self.dev {
             "dev-dependencies"
         } else if self.build {
@@ -73,47 +203,559 @@ impl RmArgs {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
-enum UnstableOptions {}
+/// Options for `-Z`, see `-Z help`
+const UNSTABLE_OPTIONS: &[UnstableOption] = &[];
 
 fn exec(args: &RmArgs) -> CargoResult<()> {
+    UnstableFlags::new(&args.unstable_features, UNSTABLE_OPTIONS)?;
+    if args.stdin {
+        let mut manifest = LocalManifest::from_stdin()?;
+        remove_deps(&mut manifest, args)?;
+        print!("{}", manifest.manifest);
+        return Ok(());
+    }
     let manifest_path = if let Some(ref pkgid) = args.pkgid {
-        let pkg = manifest_from_pkgid(args.manifest_path.as_deref(), pkgid)?;
+        let flags = CargoFlags {
+            frozen: args.frozen,
+            ..Default::default()
+        };
+        let pkg = manifest_from_pkgid(args.manifest_path.as_deref(), pkgid, flags)?;
         Cow::Owned(Some(pkg.manifest_path.into_std_path_buf()))
     } else {
         Cow::Borrowed(&args.manifest_path)
     };
-    let mut manifest = LocalManifest::find(manifest_path.as_deref())?;
-    let deps = &args.crates;
+    let manifest_path = if args.workspace_dep {
+        let manifest_path = dunce::canonicalize(find(manifest_path.as_deref())?)?;
+        let root = find_workspace_root(&manifest_path)?;
+        check_not_inherited(&root, &args.crates, args.frozen)?;
+        root
+    } else {
+        find(manifest_path.as_deref())?
+    };
+    let mut transaction = ManifestTransaction::new();
+    let removed_paths = remove_deps(transaction.load(&manifest_path)?, args)?;
+
+    if args.include_nested {
+        remove_from_nested(&mut transaction, &manifest_path, args)?;
+    }
+
+    if !removed_paths.is_empty() {
+        remove_unused_members(&mut transaction, &manifest_path, removed_paths, args)?;
+    }
+
+    if args.stdout {
+        print!("{}", transaction.load(&manifest_path)?.manifest);
+    } else if let Some(format) = args.dry_run {
+        transaction.report_dry_run(format.into())?;
+    } else {
+        for warning in transaction.future_keys_warnings() {
+            shell_warn(&warning)?;
+        }
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Remove the dependencies from `manifest`, returning the path crates they pointed to when
+/// `--recursive` asks to drop them too
+fn remove_deps(manifest: &mut LocalManifest, args: &RmArgs) -> CargoResult<Vec<PathDependency>> {
+    let deps = &args
+        .crates
+        .iter()
+        .map(|dep| manifest_key(manifest, &args.get_section(), dep))
+        .collect::<Vec<_>>();
+
+    if !args.workspace_dep && !args.force {
+        // A manifest from stdin has no sources beside it to scan
+        let scan_code = !args.stdin;
+        for dep in deps {
+            let references = feature_references(manifest, &args.get_section(), dep, scan_code)?;
+            if !references.is_empty() {
+                anyhow::bail!(
+                    "cannot remove {}, it is still used by:\n  {}\nRe-run with `--force` to remove \
+                    it anyway",
+                    dep,
+                    references.join("\n  ")
+                );
+            }
+        }
+    }
+
+    // A manifest from stdin has no build script beside it to check
+    let build_section = args.get_section().last().map(String::as_str) == Some("build-dependencies");
+    if build_section && !args.stdin {
+        for dep in deps {
+            let references = build_script_references(manifest, dep)?;
+            if !references.is_empty() {
+                shell_warn(&format!(
+                    "{} is still used by the build script at {}",
+                    dep,
+                    references.join(", ")
+                ))?;
+            }
+        }
+    }
+
+    let removed_paths = if args.recursive {
+        deps.iter()
+            .filter_map(|dep| path_dependency(manifest, &args.get_section(), dep))
+            .collect::<Vec<_>>()
+    } else {
+        Vec::new()
+    };
 
     deps.iter()
         .map(|dep| {
             if !args.quiet {
-                let section = args.get_section();
-                let section = if section.len() >= 3 {
-                    format!("{} for target `{}`", &section[2], &section[1])
-                } else {
-                    section[0].clone()
-                };
+                let section = section_label(&args.get_section());
                 shell_status("Removing", &format!("{dep} from {section}",))?;
             }
-            let result = manifest
-                .remove_from_table(&args.get_section(), dep)
-                .map_err(Into::into);
+            let result = args.remove_from(manifest, &args.get_section(), dep);
 
             // Now that we have removed the crate, if that was the last reference to that crate,
             // then we need to drop any explicitly activated features on that crate.
-            manifest.gc_dep(dep);
+            if !args.workspace_dep {
+                manifest.gc_dep(dep);
+            }
 
             result
         })
         .collect::<CargoResult<Vec<_>>>()?;
 
-    if args.dry_run {
-        shell_warn("aborting rm due to dry run")?;
+    Ok(removed_paths)
+}
+
+/// Remove the dependencies from the companion crates that have them, like `fuzz/`
+fn remove_from_nested(
+    transaction: &mut ManifestTransaction,
+    manifest_path: &Path,
+    args: &RmArgs,
+) -> CargoResult<()> {
+    let flags = CargoFlags {
+        frozen: args.frozen,
+        ..Default::default()
+    };
+    let section = args.get_section();
+    let cwd = dunce::canonicalize(std::env::current_dir()?)?;
+    for nested in find_nested_crates(manifest_path, flags)? {
+        let manifest = transaction.load(&nested)?;
+        let label = nested
+            .strip_prefix(&cwd)
+            .unwrap_or(&nested)
+            .display()
+            .to_string();
+        for dep in &args.crates {
+            let dep = &manifest_key(manifest, &section, dep);
+            let present = section
+                .iter()
+                .try_fold(manifest.data.as_item(), |item, key| item.get(key.as_str()))
+                .and_then(|table| table.get(dep))
+                .is_some();
+            if !present {
+                continue;
+            }
+            if !args.force {
+                let references = feature_references(manifest, &section, dep, true)?;
+                if !references.is_empty() {
+                    anyhow::bail!(
+                        "cannot remove {} from {}, it is still used by:\n  {}\nRe-run with \
+                        `--force` to remove it anyway",
+                        dep,
+                        label,
+                        references.join("\n  ")
+                    );
+                }
+            }
+            if !args.quiet {
+                shell_status(
+                    "Removing",
+                    &format!("{} from {} of {}", dep, section_label(&section), label),
+                )?;
+            }
+            args.remove_from(manifest, &section, dep)?;
+            manifest.gc_dep(dep);
+        }
+    }
+    Ok(())
+}
+
+/// The section as it reads in messages, naming the target of platform-specific ones
+fn section_label(section: &[String]) -> String {
+    if section.len() >= 3 {
+        format!("{} for target `{}`", &section[2], &section[1])
     } else {
-        manifest.write()?;
+        section.join(".")
     }
+}
+
+/// The key of `dep` in `section`, which may spell `-` and `_` the other way around
+fn manifest_key(manifest: &LocalManifest, section: &[String], dep: &str) -> String {
+    let table = section
+        .iter()
+        .try_fold(manifest.data.as_item(), |item, key| item.get(key.as_str()))
+        .and_then(|table| table.as_table_like());
+    let table = match table {
+        Some(table) if !table.contains_key(dep) => table,
+        _ => return dep.to_owned(),
+    };
+    let normalized = normalize_crate_name(dep);
+    table
+        .iter()
+        .map(|(key, _)| key)
+        .find(|key| normalize_crate_name(key) == normalized)
+        .unwrap_or(dep)
+        .to_owned()
+}
+
+/// Where a removed dependency pointed to a local crate
+enum PathDependency {
+    /// Directory of the crate
+    Path(PathBuf),
+    /// Name of the `[workspace.dependencies]` entry it inherited
+    Workspace(String),
+}
 
+/// The local crate that `dep` points to, if it is a path dependency
+fn path_dependency(
+    manifest: &LocalManifest,
+    section: &[String],
+    dep: &str,
+) -> Option<PathDependency> {
+    let item = section
+        .iter()
+        .try_fold(manifest.data.as_item(), |item, key| item.get(key.as_str()))?
+        .get(dep)?;
+    if let Some(path) = item.get("path").and_then(|path| path.as_str()) {
+        let crate_root = manifest
+            .path
+            .parent()
+            .expect("manifest is always in a directory");
+        Some(PathDependency::Path(canonical(&crate_root.join(path))))
+    } else if item.get("workspace")?.as_bool()? {
+        Some(PathDependency::Workspace(dep.to_owned()))
+    } else {
+        None
+    }
+}
+
+/// Remove the workspace members that only the removed path dependencies used, and in turn the
+/// ones only those members used
+fn remove_unused_members(
+    transaction: &mut ManifestTransaction,
+    manifest_path: &Path,
+    removed_paths: Vec<PathDependency>,
+    args: &RmArgs,
+) -> CargoResult<()> {
+    let root = match find_workspace_root(&canonical(manifest_path)) {
+        Ok(root) => root,
+        // Outside of a workspace, there are no members to remove
+        Err(_) => return Ok(()),
+    };
+    let root_dir = root
+        .parent()
+        .expect("manifest is always in a directory")
+        .to_owned();
+    let flags = CargoFlags {
+        frozen: args.frozen,
+        ..Default::default()
+    };
+
+    // Members can reach path crates through `[workspace.dependencies]`
+    let inherited = workspace_path_dependencies(transaction.load(&root)?);
+    let mut candidates = Vec::new();
+    for removed_path in removed_paths {
+        match removed_path {
+            PathDependency::Path(dir) => candidates.push(dir),
+            PathDependency::Workspace(dep) => candidates.extend(
+                inherited
+                    .iter()
+                    .filter(|(name, _)| *name == dep)
+                    .map(|(_, dir)| dir.clone()),
+            ),
+        }
+    }
+    let mut members = Vec::new();
+    for member in workspace_members(Some(&root), flags)? {
+        let manifest = transaction.load(member.manifest_path.as_std_path())?;
+        let dir = canonical(
+            manifest
+                .path
+                .parent()
+                .expect("manifest is always in a directory"),
+        );
+        let dependencies = member_path_dependencies(manifest, &inherited);
+        members.push((member.name, dir, dependencies));
+    }
+
+    let mut removed = Vec::new();
+    while let Some(candidate) = candidates.pop() {
+        let index = match members.iter().position(|(_, dir, _)| *dir == candidate) {
+            Some(index) => index,
+            None => continue,
+        };
+        if removed.contains(&index) || candidate == root_dir {
+            continue;
+        }
+        let used = members.iter().enumerate().any(|(i, (_, _, deps))| {
+            i != index && !removed.contains(&i) && deps.contains(&candidate)
+        });
+        if used {
+            // Another removal may still free it up
+            continue;
+        }
+        removed.push(index);
+        candidates.extend(members[index].2.iter().cloned());
+    }
+
+    let root_manifest = transaction.load(&root)?;
+    for &index in &removed {
+        let (name, dir, _) = &members[index];
+        if !args.quiet {
+            shell_status("Removing", &format!("{} from the workspace", name))?;
+        }
+        remove_member(root_manifest, &root_dir, dir);
+        for (key, path) in &inherited {
+            if path == dir {
+                root_manifest
+                    .remove_from_table(&["workspace".to_owned(), "dependencies".to_owned()], key)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Directories of the path crates in `[workspace.dependencies]`, by dependency name
+fn workspace_path_dependencies(root: &LocalManifest) -> Vec<(String, PathBuf)> {
+    let root_dir = root
+        .path
+        .parent()
+        .expect("manifest is always in a directory");
+    root.data
+        .get("workspace")
+        .and_then(|workspace| workspace.get("dependencies"))
+        .and_then(|deps| deps.as_table_like())
+        .into_iter()
+        .flat_map(|deps| deps.iter())
+        .filter_map(|(key, dep)| {
+            let path = dep.get("path")?.as_str()?;
+            Some((key.to_owned(), canonical(&root_dir.join(path))))
+        })
+        .collect()
+}
+
+/// Directories of the path crates a member depends on, directly or through the workspace
+fn member_path_dependencies(
+    manifest: &LocalManifest,
+    inherited: &[(String, PathBuf)],
+) -> Vec<PathBuf> {
+    let crate_root = manifest
+        .path
+        .parent()
+        .expect("manifest is always in a directory");
+    let mut dependencies = Vec::new();
+    for (_, table) in manifest.get_sections() {
+        let table = table.as_table_like().expect("sections are always tables");
+        for (key, dep) in table.iter() {
+            if let Some(path) = dep.get("path").and_then(|path| path.as_str()) {
+                dependencies.push(canonical(&crate_root.join(path)));
+            } else if dep
+                .get("workspace")
+                .and_then(|workspace| workspace.as_bool())
+                .unwrap_or(false)
+            {
+                dependencies.extend(
+                    inherited
+                        .iter()
+                        .filter(|(name, _)| name == key)
+                        .map(|(_, path)| path.clone()),
+                );
+            }
+        }
+    }
+    dependencies
+}
+
+/// Drop `dir` from `workspace.members`, or exclude it when a glob brings it in
+fn remove_member(root: &mut LocalManifest, root_dir: &Path, dir: &Path) {
+    let workspace = &mut root.data["workspace"];
+    if let Some(members) = workspace
+        .get_mut("members")
+        .and_then(|members| members.as_array_mut())
+    {
+        let index = members.iter().position(|member| {
+            member
+                .as_str()
+                .map_or(false, |member| canonical(&root_dir.join(member)) == dir)
+        });
+        if let Some(index) = index {
+            members.remove(index);
+            return;
+        }
+    }
+    let relative = dir.strip_prefix(root_dir).unwrap_or(dir);
+    let relative = relative.display().to_string().replace('\\', "/");
+    let exclude = &mut workspace["exclude"];
+    if exclude.is_none() {
+        *exclude = toml_edit::value(toml_edit::Array::new());
+    }
+    if let Some(exclude) = exclude.as_array_mut() {
+        exclude.push(relative);
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    dunce::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+/// Features and, with `scan_code`, `#[cfg(feature = "..")]` code that still need `dep`, if it is
+/// optional
+fn feature_references(
+    manifest: &LocalManifest,
+    section: &[String],
+    dep: &str,
+    scan_code: bool,
+) -> CargoResult<Vec<String>> {
+    let optional = section
+        .iter()
+        .try_fold(manifest.data.as_item(), |item, key| item.get(key.as_str()))
+        .and_then(|table| table.get(dep))
+        .and_then(|dep| dep.get("optional"))
+        .and_then(|optional| optional.as_bool())
+        .unwrap_or(false);
+    if !optional {
+        return Ok(Vec::new());
+    }
+
+    let features = manifest.features();
+    let mut references = features
+        .iter()
+        .filter(|(_, values)| {
+            values.iter().any(|value| match FeatureValue::parse(value) {
+                FeatureValue::Feature(name) | FeatureValue::Dep(name) => name == dep,
+                FeatureValue::DepFeature { dep: name, .. } => name == dep,
+            })
+        })
+        .map(|(feature, _)| format!("feature `{}`", feature))
+        .collect::<Vec<_>>();
+
+    // Code can only refer to the dependency's implicit feature if no explicit one replaces it
+    if scan_code && !features.contains_key(dep) {
+        let crate_root = manifest
+            .path
+            .parent()
+            .expect("manifest is always in a directory");
+        let pattern = regex::Regex::new(&format!(r#"feature\s*=\s*"{}""#, regex::escape(dep)))?;
+        scan_sources(
+            crate_root,
+            &crate_root.join("src"),
+            &pattern,
+            &mut references,
+        )?;
+    }
+    Ok(references)
+}
+
+/// The `file:line` of each `use` or `extern crate` of `dep` in the package's build script
+fn build_script_references(manifest: &LocalManifest, dep: &str) -> CargoResult<Vec<String>> {
+    let build = manifest.data.get("package").and_then(|p| p.get("build"));
+    let build = match build {
+        None => "build.rs",
+        Some(build) => match build.as_str() {
+            Some(build) => build,
+            // `build = false` turns off the build script
+            None => return Ok(Vec::new()),
+        },
+    };
+    let crate_root = manifest
+        .path
+        .parent()
+        .expect("manifest is always in a directory");
+    let path = crate_root.join(build);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    // The crate is named with `_` in code, even when its package uses `-`
+    let ident = regex::escape(&dep.replace('-', "_"));
+    let pattern = regex::Regex::new(&format!(r"\b(use\s+(::)?|extern\s+crate\s+){}\b", ident))?;
+    Ok(content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| pattern.is_match(line))
+        .map(|(i, _)| format!("{}:{}", build, i + 1))
+        .collect())
+}
+
+/// Record the `file:line` of every line matching `pattern` in the Rust sources beneath `dir`
+fn scan_sources(
+    crate_root: &Path,
+    dir: &Path,
+    pattern: &regex::Regex,
+    references: &mut Vec<String>,
+) -> CargoResult<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    let mut entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<CargoResult<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            scan_sources(crate_root, &path, pattern, references)?;
+        } else if path.extension().map_or(false, |ext| ext == "rs") {
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let relative = path.strip_prefix(crate_root).unwrap_or(&path);
+            let relative = relative.display().to_string().replace('\\', "/");
+            for (i, line) in content.lines().enumerate() {
+                if pattern.is_match(line) {
+                    references.push(format!("{}:{}", relative, i + 1));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Refuse to remove workspace dependencies that members still inherit
+fn check_not_inherited(root: &Path, deps: &[String], frozen: bool) -> CargoResult<()> {
+    let flags = CargoFlags {
+        frozen,
+        ..Default::default()
+    };
+    let members = workspace_members(Some(root), flags)?
+        .into_iter()
+        .map(|member| {
+            let manifest = LocalManifest::try_new(member.manifest_path.as_std_path())?;
+            Ok((member.name, manifest))
+        })
+        .collect::<CargoResult<Vec<_>>>()?;
+    for dep in deps {
+        let inheriting = members
+            .iter()
+            .filter(|(_, manifest)| {
+                manifest.get_sections().iter().any(|(_, table)| {
+                    table
+                        .get(dep)
+                        .and_then(|d| d.get("workspace"))
+                        .and_then(|w| w.as_bool())
+                        .unwrap_or(false)
+                })
+            })
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        if !inheriting.is_empty() {
+            anyhow::bail!(
+                "cannot remove {} from workspace.dependencies, it is inherited by {}",
+                dep,
+                inheriting.join(", ")
+            );
+        }
+    }
     Ok(())
 }
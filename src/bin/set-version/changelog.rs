@@ -0,0 +1,101 @@
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use cargo_edit::ensure_writable;
+
+use crate::errors::*;
+
+/// Add a [keep-a-changelog](https://keepachangelog.com) section for `version` to the file
+pub fn update_changelog(path: &Path, version: &semver::Version) -> CargoResult<()> {
+    let changelog = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let changelog = add_release_section(&changelog, version, &today());
+    ensure_writable(path)?;
+    std::fs::write(path, changelog).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Insert the section header for a release.
+///
+/// Entries under `## [Unreleased]` move to the new release; without that header, the new section
+/// goes before the most recent release.
+fn add_release_section(changelog: &str, version: &semver::Version, date: &str) -> String {
+    let header = format!("## [{}] - {}", version, date);
+    let mut lines = changelog.lines().collect::<Vec<_>>();
+    let is_unreleased = |line: &&str| {
+        let line = line.trim().to_lowercase();
+        line == "## [unreleased]" || line == "## unreleased"
+    };
+    if let Some(unreleased) = lines.iter().position(is_unreleased) {
+        lines.insert(unreleased + 1, "");
+        lines.insert(unreleased + 2, &header);
+    } else if let Some(latest) = lines.iter().position(|line| line.starts_with("## ")) {
+        lines.insert(latest, "");
+        lines.insert(latest, &header);
+    } else {
+        lines.push("");
+        lines.push(&header);
+    }
+
+    let mut changelog = lines.join("\n");
+    changelog.push('\n');
+    changelog
+}
+
+/// Current date in UTC, as `YYYY-MM-DD`
+fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Convert days since the Unix epoch to a Gregorian date.
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unreleased_entries_move_to_release() {
+        let changelog =
+            "# Changelog\n\n## [Unreleased]\n\n- Fixed a bug\n\n## [0.1.0] - 2020-01-01\n";
+        let actual = add_release_section(changelog, &semver::Version::new(0, 2, 0), "2022-05-04");
+        assert_eq!(
+            actual,
+            "# Changelog\n\n## [Unreleased]\n\n## [0.2.0] - 2022-05-04\n\n- Fixed a bug\n\n## [0.1.0] - 2020-01-01\n"
+        );
+    }
+
+    #[test]
+    fn release_goes_before_latest() {
+        let changelog = "# Changelog\n\n## [0.1.0] - 2020-01-01\n";
+        let actual = add_release_section(changelog, &semver::Version::new(0, 2, 0), "2022-05-04");
+        assert_eq!(
+            actual,
+            "# Changelog\n\n## [0.2.0] - 2022-05-04\n\n## [0.1.0] - 2020-01-01\n"
+        );
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_116), (2022, 5, 4));
+    }
+}
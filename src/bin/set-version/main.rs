@@ -12,6 +12,7 @@
 )]
 #![allow(clippy::comparison_chain)]
 
+mod changelog;
 mod cli;
 mod errors;
 mod set_version;
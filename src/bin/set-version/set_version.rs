@@ -1,13 +1,19 @@
+use std::collections::BTreeSet;
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 
 use cargo_edit::{
-    colorize_stderr, resolve_manifests, upgrade_requirement, workspace_members, LocalManifest,
+    catch_interrupts, colorize_stderr, filter_members, find_workspace_root, find_workspace_roots,
+    is_version_published, latest_published_version, registry_url, resolve_manifests, shell_command,
+    shell_note, shell_warn, upgrade_requirement, workspace_members, CargoFlags, LocalManifest,
+    ManifestTransaction, UnstableFlag, UnstableFlags, UnstableOption,
 };
 use clap::Args;
 use termcolor::{BufferWriter, Color, ColorSpec, WriteColor};
 
+use crate::changelog::update_changelog;
+use crate::cli::DryRunArg;
 use crate::errors::*;
 use crate::version::BumpLevel;
 use crate::version::TargetVersion;
@@ -21,20 +27,37 @@ pub struct VersionArgs {
     #[clap(parse(try_from_str), group = "ver")]
     target: Option<semver::Version>,
 
+    /// Bump from the highest version published to the registry instead of the local version
+    #[clap(
+        long,
+        conflicts_with = "target",
+        env = "CARGO_SET_VERSION_NEXT_UNPUBLISHED",
+        hide_env = true
+    )]
+    next_unpublished: bool,
+
     /// Increment manifest version
     #[clap(
         long,
         possible_values(crate::version::BumpLevel::variants()),
-        group = "ver"
+        group = "ver",
+        env = "CARGO_SET_VERSION_BUMP",
+        hide_env = true
     )]
     bump: Option<BumpLevel>,
 
     /// Specify the version metadata field (e.g. a wrapped libraries version)
-    #[clap(short, long)]
+    #[clap(short, long, env = "CARGO_SET_VERSION_METADATA", hide_env = true)]
     pub metadata: Option<String>,
 
     /// Path to the manifest to upgrade
-    #[clap(long, value_name = "PATH", parse(from_os_str))]
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        env = "CARGO_SET_VERSION_MANIFEST_PATH",
+        hide_env = true
+    )]
     manifest_path: Option<PathBuf>,
 
     /// Package id of the crate to change the version of.
@@ -43,7 +66,9 @@ pub struct VersionArgs {
         short = 'p',
         value_name = "PKGID",
         conflicts_with = "all",
-        conflicts_with = "workspace"
+        conflicts_with = "workspace",
+        env = "CARGO_SET_VERSION_PACKAGE",
+        hide_env = true
     )]
     pkgid: Option<String>,
 
@@ -57,20 +82,121 @@ pub struct VersionArgs {
     all: bool,
 
     /// Modify all packages in the workspace.
-    #[clap(long, conflicts_with = "all", conflicts_with = "pkgid")]
+    #[clap(
+        long,
+        conflicts_with = "all",
+        conflicts_with = "pkgid",
+        env = "CARGO_SET_VERSION_WORKSPACE",
+        hide_env = true
+    )]
     workspace: bool,
 
+    /// Modify the members in directories matching this glob.
+    #[clap(
+        long,
+        value_name = "GLOB",
+        conflicts_with = "pkgid",
+        env = "CARGO_SET_VERSION_MEMBERS",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    members: Vec<String>,
+
     /// Print changes to be made without making them.
-    #[clap(long)]
-    dry_run: bool,
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        arg_enum,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "summary",
+        env = "CARGO_SET_VERSION_DRY_RUN",
+        hide_env = true
+    )]
+    dry_run: Option<DryRunArg>,
 
     /// Crates to exclude and not modify.
-    #[clap(long)]
+    #[clap(
+        long,
+        env = "CARGO_SET_VERSION_EXCLUDE",
+        hide_env = true,
+        value_delimiter = ','
+    )]
     exclude: Vec<String>,
 
+    /// Require `Cargo.lock` and cache are up to date
+    #[clap(long, env = "CARGO_SET_VERSION_FROZEN", hide_env = true)]
+    frozen: bool,
+
+    /// Look up published versions without updating the registry index
+    #[clap(long, env = "CARGO_SET_VERSION_OFFLINE", hide_env = true)]
+    offline: bool,
+
+    /// Give packages their own version instead of the workspace's
+    #[clap(long, env = "CARGO_SET_VERSION_BREAK_INHERITANCE", hide_env = true)]
+    break_inheritance: bool,
+
+    /// Package's changelog to add a section for the new version to
+    #[clap(
+        long,
+        value_name = "FILE",
+        parse(from_os_str),
+        env = "CARGO_SET_VERSION_CHANGELOG",
+        hide_env = true
+    )]
+    changelog: Option<PathBuf>,
+
+    /// Shell command to run after each version change
+    #[clap(
+        long,
+        value_name = "CMD",
+        env = "CARGO_SET_VERSION_HOOK",
+        hide_env = true
+    )]
+    hook: Option<String>,
+
+    /// Edit the manifest piped to stdin, printing it to stdout
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "manifest-path",
+            "pkgid",
+            "all",
+            "workspace",
+            "members",
+            "exclude",
+            "dry-run",
+            "next-unpublished",
+            "changelog",
+            "hook"
+        ],
+        env = "CARGO_SET_VERSION_STDIN",
+        hide_env = true
+    )]
+    stdin: bool,
+
+    /// Print the edited manifest to stdout instead of writing it
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "pkgid",
+            "all",
+            "workspace",
+            "members",
+            "exclude",
+            "dry-run",
+            "next-unpublished",
+            "changelog",
+            "hook"
+        ],
+        env = "CARGO_SET_VERSION_STDOUT",
+        hide_env = true
+    )]
+    stdout: bool,
+
     /// Unstable (nightly-only) flags
-    #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
-    unstable_features: Vec<UnstableOptions>,
+    #[clap(short = 'Z', value_name = "FLAG", global = true)]
+    unstable_features: Vec<UnstableFlag>,
 }
 
 impl VersionArgs {
@@ -79,14 +205,16 @@ impl VersionArgs {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
-enum UnstableOptions {}
+/// Options for `-Z`, see `-Z help`
+const UNSTABLE_OPTIONS: &[UnstableOption] = &[];
 
 /// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
 /// messages.
 fn exec(args: VersionArgs) -> CargoResult<()> {
+    UnstableFlags::new(&args.unstable_features, UNSTABLE_OPTIONS)?;
     let VersionArgs {
         target,
+        next_unpublished,
         bump,
         metadata,
         manifest_path,
@@ -94,55 +222,114 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
         all,
         dry_run,
         workspace,
+        members,
         exclude,
+        frozen,
+        offline,
+        break_inheritance,
+        changelog,
+        hook,
+        stdin,
+        stdout,
         unstable_features: _,
     } = args;
 
     let target = match (target, bump) {
+        (None, None) if next_unpublished => TargetVersion::Relative(BumpLevel::Patch),
         (None, None) => TargetVersion::Relative(BumpLevel::Release),
         (None, Some(level)) => TargetVersion::Relative(level),
         (Some(version), None) => TargetVersion::Absolute(version),
         (Some(_), Some(_)) => unreachable!("clap groups should prevent this"),
     };
 
+    if stdin || stdout {
+        let manifest = if stdin {
+            LocalManifest::from_stdin()?
+        } else {
+            LocalManifest::find(manifest_path.as_deref())?
+        };
+        return print_with_version(manifest, &target, metadata.as_deref());
+    }
+
+    catch_interrupts()?;
+
     if all {
         deprecated_message("The flag `--all` has been deprecated in favor of `--workspace`")?;
     }
-    let all = workspace || all;
-    let manifests = Manifests(resolve_manifests(
-        manifest_path.as_deref(),
-        all,
-        pkgid.as_deref().into_iter().collect::<Vec<_>>(),
+    let all = workspace || all || !members.is_empty();
+    let flags = CargoFlags {
+        frozen,
+        ..Default::default()
+    };
+    let manifests = Manifests(filter_members(
+        resolve_manifests(
+            manifest_path.as_deref(),
+            all,
+            pkgid.as_deref().into_iter().collect::<Vec<_>>(),
+            flags,
+        )?,
+        &members,
     )?);
 
-    if dry_run {
-        dry_run_message()?;
-    }
+    let workspace_members = workspace_members(manifest_path.as_deref(), flags)?;
 
-    let workspace_members = workspace_members(manifest_path.as_deref())?;
+    let mut transaction = ManifestTransaction::new();
+    if !break_inheritance {
+        check_inheritance(&mut transaction, &manifests.0, &exclude, &workspace_members)?;
+    }
 
+    let external = external_manifests(&manifests.0, &workspace_members)?;
+    let mut updated_registries = BTreeSet::new();
+    let mut changes = Vec::new();
+    let mut workspace_version_changed = false;
     for package in manifests.0 {
         if exclude.contains(&package.name) {
             continue;
         }
         let current = &package.version;
-        let next = target.bump(current, metadata.as_deref())?;
+        let next = if next_unpublished {
+            match latest_published(&package, offline, &mut updated_registries)? {
+                // A local version already ahead of the registry is still unpublished
+                Some(published) => target
+                    .bump(&published, metadata.as_deref())?
+                    .filter(|next| next > current),
+                None => target.bump(current, metadata.as_deref())?,
+            }
+        } else {
+            target.bump(current, metadata.as_deref())?
+        };
         if let Some(next) = next {
             {
-                let mut manifest = LocalManifest::try_new(Path::new(&package.manifest_path))?;
-                manifest.set_package_version(&next);
+                let manifest = transaction.load(Path::new(&package.manifest_path))?;
+                if break_inheritance || !inherits_version(manifest) {
+                    manifest.set_package_version(&next);
+                } else if !workspace_version_changed {
+                    let root = find_workspace_root(package.manifest_path.as_std_path())?;
+                    let root_manifest = transaction.load(&root)?;
+                    root_manifest.data["workspace"]["package"]["version"] =
+                        toml_edit::value(next.to_string());
+                    workspace_version_changed = true;
+                }
 
                 upgrade_message(package.name.as_str(), current, &next)?;
-                if !dry_run {
-                    manifest.write()?;
-                }
             }
+            changes.push((package.clone(), next.clone()));
+            check_unpublished(&package, &next, offline, &mut updated_registries)?;
 
             let crate_root =
                 dunce::canonicalize(package.manifest_path.parent().expect("at least a parent"))?;
+            for (relative, manifest) in &external {
+                if depends_by_path(manifest, &crate_root) {
+                    shell_note(&format!(
+                        "{} also depends on {} by path, but isn't in the workspace so it won't be \
+                         updated",
+                        relative.display(),
+                        package.name
+                    ))?;
+                }
+            }
             for member in workspace_members.iter() {
-                let mut dep_manifest = LocalManifest::try_new(member.manifest_path.as_std_path())?;
-                let mut changed = false;
+                let dep_manifest = transaction.load(member.manifest_path.as_std_path())?;
                 let dep_crate_root = dep_manifest
                     .path
                     .parent()
@@ -171,40 +358,252 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
                     if let Some(new_req) = upgrade_requirement(old_req, &next)? {
                         upgrade_dependent_message(member.name.as_str(), old_req, &new_req)?;
                         dep.insert("version", toml_edit::value(new_req));
-                        changed = true;
                     }
                 }
-                if changed && !dry_run {
-                    dep_manifest.write()?;
+            }
+        }
+    }
+
+    if let Some(format) = dry_run {
+        transaction.report_dry_run(format.into())?;
+    } else {
+        for warning in transaction.future_keys_warnings() {
+            shell_warn(&warning)?;
+        }
+        transaction.commit()?;
+
+        for (package, next) in &changes {
+            let package_root = package.manifest_path.parent().expect("at least a parent");
+            if let Some(changelog) = &changelog {
+                let changelog = package_root.as_std_path().join(changelog);
+                if changelog.exists() {
+                    update_changelog(&changelog, next)?;
+                } else {
+                    shell_warn(&format!(
+                        "{} has no changelog at {}",
+                        package.name,
+                        changelog.display()
+                    ))?;
                 }
             }
+            if let Some(hook) = &hook {
+                run_hook(hook, package, next)?;
+            }
         }
     }
 
     Ok(())
 }
 
-/// A collection of manifests.
-struct Manifests(Vec<cargo_metadata::Package>);
+/// Change the version of a single manifest and print it to stdout instead of writing it
+fn print_with_version(
+    mut manifest: LocalManifest,
+    target: &TargetVersion,
+    metadata: Option<&str>,
+) -> CargoResult<()> {
+    if inherits_version(&manifest) {
+        anyhow::bail!(
+            "the package inherits its version from the workspace, which `--stdin` and `--stdout` \
+             leave alone"
+        );
+    }
+    let package = manifest.data.get(manifest.package_key());
+    let name = package
+        .and_then(|p| p.get("name"))
+        .and_then(|n| n.as_str())
+        .unwrap_or_default()
+        .to_owned();
+    let current = package
+        .and_then(|p| p.get("version"))
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::format_err!("the manifest has no `package.version` to change"))?;
+    let current = semver::Version::parse(current)
+        .with_context(|| format!("Invalid version {} in the manifest", current))?;
+    if let Some(next) = target.bump(&current, metadata)? {
+        manifest.set_package_version(&next);
+        upgrade_message(&name, &current, &next)?;
+    }
+    print!("{}", manifest.manifest);
+    Ok(())
+}
 
-fn dry_run_message() -> CargoResult<()> {
-    let colorchoice = colorize_stderr();
-    let bufwtr = BufferWriter::stderr(colorchoice);
-    let mut buffer = bufwtr.buffer();
-    buffer
-        .set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true))
-        .with_context(|| "Failed to set output colour")?;
-    write!(&mut buffer, "Starting dry run. ").with_context(|| "Failed to write dry run message")?;
-    buffer
-        .set_color(&ColorSpec::new())
-        .with_context(|| "Failed to clear output colour")?;
-    writeln!(&mut buffer, "Changes will not be saved.")
-        .with_context(|| "Failed to write dry run message")?;
-    bufwtr
-        .print(&buffer)
-        .with_context(|| "Failed to print dry run message")
+/// Warn when `next` was already published, since `cargo publish` would refuse it
+fn check_unpublished(
+    package: &cargo_metadata::Package,
+    next: &semver::Version,
+    offline: bool,
+    updated_registries: &mut BTreeSet<url::Url>,
+) -> CargoResult<()> {
+    let registry = match publish_registry(package) {
+        Some(registry) => registry,
+        None => return Ok(()),
+    };
+    let published = registry_url(package.manifest_path.as_std_path(), registry).and_then(|url| {
+        let update_index = !offline && updated_registries.insert(url.clone());
+        is_version_published(&package.name, next, &url, update_index)
+    });
+    match published {
+        Ok(true) => shell_warn(&format!(
+            "{} {} is already published to {}",
+            package.name,
+            next,
+            registry.unwrap_or("crates.io")
+        ))?,
+        Ok(false) => {}
+        Err(err) => shell_warn(&format!(
+            "couldn't check whether {} {} is already published: {:#}",
+            package.name, next, err
+        ))?,
+    }
+    Ok(())
+}
+
+/// The highest version of the package published to its registry, if it has one
+fn latest_published(
+    package: &cargo_metadata::Package,
+    offline: bool,
+    updated_registries: &mut BTreeSet<url::Url>,
+) -> CargoResult<Option<semver::Version>> {
+    let registry = match publish_registry(package) {
+        Some(registry) => registry,
+        None => return Ok(None),
+    };
+    let url = registry_url(package.manifest_path.as_std_path(), registry)?;
+    let update_index = !offline && updated_registries.insert(url.clone());
+    latest_published_version(&package.name, &url, update_index).with_context(|| {
+        format!(
+            "Failed to look up the published versions of {}",
+            package.name
+        )
+    })
+}
+
+/// The registry `cargo publish` uploads the package to, or `None` if it can't be published
+fn publish_registry(package: &cargo_metadata::Package) -> Option<Option<&str>> {
+    match package.publish.as_deref() {
+        Some([]) => None,
+        Some([registry, ..]) => Some(Some(registry.as_str())),
+        None => Some(None),
+    }
 }
 
+/// Manifests beneath the workspace that aren't part of it, like examples with a workspace of
+/// their own, by their path relative to the workspace
+fn external_manifests(
+    selected: &[cargo_metadata::Package],
+    workspace_members: &[cargo_metadata::Package],
+) -> CargoResult<Vec<(PathBuf, LocalManifest)>> {
+    let first = match selected.first() {
+        Some(first) => first.manifest_path.as_std_path(),
+        None => return Ok(Vec::new()),
+    };
+    let root = find_workspace_root(first).unwrap_or_else(|_| first.to_owned());
+    let root_dir = dunce::canonicalize(root.parent().expect("at least a parent"))?;
+    let members = workspace_members
+        .iter()
+        .map(|member| member.manifest_path.as_std_path())
+        .collect::<BTreeSet<_>>();
+
+    let mut external = Vec::new();
+    for manifest_path in find_workspace_roots(&root_dir)? {
+        if manifest_path == root || members.contains(manifest_path.as_path()) {
+            continue;
+        }
+        // A broken manifest here isn't this command's concern
+        if let Ok(manifest) = LocalManifest::try_new(&manifest_path) {
+            let relative = manifest_path
+                .strip_prefix(&root_dir)
+                .unwrap_or(&manifest_path)
+                .to_owned();
+            external.push((relative, manifest));
+        }
+    }
+    Ok(external)
+}
+
+/// Whether any of the manifest's dependencies are on the package at `crate_root`
+fn depends_by_path(manifest: &LocalManifest, crate_root: &Path) -> bool {
+    let manifest_dir = manifest.path.parent().expect("at least a parent");
+    manifest.get_sections().iter().any(|(_, table)| {
+        table.as_table_like().map_or(false, |table| {
+            table.iter().any(|(_, dep)| {
+                dep.get("path")
+                    .and_then(|path| path.as_str())
+                    .and_then(|path| dunce::canonicalize(manifest_dir.join(path)).ok())
+                    .map_or(false, |path| path == crate_root)
+            })
+        })
+    })
+}
+
+/// Ensure changing the workspace's version won't affect packages the user didn't select
+fn check_inheritance(
+    transaction: &mut ManifestTransaction,
+    selected: &[cargo_metadata::Package],
+    exclude: &[String],
+    workspace_members: &[cargo_metadata::Package],
+) -> CargoResult<()> {
+    let mut selected_inheriting = Vec::new();
+    let mut unselected_inheriting = Vec::new();
+    for member in workspace_members {
+        if !inherits_version(transaction.load(member.manifest_path.as_std_path())?) {
+            continue;
+        }
+        let is_selected = !exclude.contains(&member.name)
+            && selected
+                .iter()
+                .any(|p| p.manifest_path == member.manifest_path);
+        if is_selected {
+            selected_inheriting.push(member.name.as_str());
+        } else {
+            unselected_inheriting.push(member.name.as_str());
+        }
+    }
+
+    if !selected_inheriting.is_empty() && !unselected_inheriting.is_empty() {
+        anyhow::bail!(
+            "the workspace version is also inherited by {}; select all of them with \
+             `--workspace` or pass `--break-inheritance`",
+            unselected_inheriting.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Whether the manifest has `version.workspace = true`
+fn inherits_version(manifest: &LocalManifest) -> bool {
+    manifest
+        .data
+        .get("package")
+        .and_then(|p| p.get("version"))
+        .and_then(|v| v.get("workspace"))
+        .and_then(|w| w.as_bool())
+        .unwrap_or(false)
+}
+
+/// Run the user's hook for a version change, with the details in environment variables
+fn run_hook(
+    hook: &str,
+    package: &cargo_metadata::Package,
+    next: &semver::Version,
+) -> CargoResult<()> {
+    let status = shell_command(hook)
+        .current_dir(package.manifest_path.parent().expect("at least a parent"))
+        .env("CARGO_EDIT_PACKAGE", &package.name)
+        .env("CARGO_EDIT_MANIFEST_PATH", &package.manifest_path)
+        .env("CARGO_EDIT_OLD_VERSION", package.version.to_string())
+        .env("CARGO_EDIT_NEW_VERSION", next.to_string())
+        .status()
+        .with_context(|| format!("Failed to run hook `{}`", hook))?;
+    if !status.success() {
+        anyhow::bail!("Hook `{}` failed for {}: {}", hook, package.name, status);
+    }
+    Ok(())
+}
+
+/// A collection of manifests.
+struct Manifests(Vec<cargo_metadata::Package>);
+
 fn deprecated_message(message: &str) -> CargoResult<()> {
     let colorchoice = colorize_stderr();
     let bufwtr = BufferWriter::stderr(colorchoice);
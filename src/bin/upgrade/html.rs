@@ -0,0 +1,158 @@
+//! Upgrades as a standalone HTML page, for publishing dependency freshness from CI
+use std::fmt::Write;
+use std::path::Path;
+
+use cargo_edit::{ReportReason, UpgradeReport};
+
+/// Render `report` as a page, with manifest paths relative to `root_dir`
+pub fn render(report: &UpgradeReport, root_dir: &Path) -> Result<String, std::fmt::Error> {
+    let dependencies = report
+        .packages
+        .iter()
+        .flat_map(|package| &package.dependencies);
+    let total = dependencies.clone().count();
+    let upgraded = dependencies
+        .filter(|dep| dep.old_req != dep.new_req)
+        .count();
+
+    let mut page = String::new();
+    page.push_str(HEADER);
+    writeln!(
+        page,
+        "<p>{} of {} dependencies upgraded</p>",
+        upgraded, total
+    )?;
+    for package in &report.packages {
+        let manifest_path = crate::upgrade::package_file(root_dir, &package.manifest_path);
+        writeln!(page, "<section>")?;
+        writeln!(page, "<h2>{}</h2>", escape(&package.name))?;
+        writeln!(page, "<p><code>{}</code></p>", escape(&manifest_path))?;
+        if package.dependencies.is_empty() {
+            writeln!(page, "<p>No dependencies</p>")?;
+            writeln!(page, "</section>")?;
+            continue;
+        }
+        writeln!(page, "<table>")?;
+        writeln!(
+            page,
+            "<thead><tr><th>name</th><th>old req</th><th>locked</th><th>latest</th>\
+            <th>new req</th><th>note</th></tr></thead>"
+        )?;
+        writeln!(page, "<tbody>")?;
+        for dep in &package.dependencies {
+            let class = if dep.old_req != dep.new_req {
+                " class=\"upgraded\""
+            } else {
+                ""
+            };
+            let name = escape(&dep.name);
+            writeln!(
+                page,
+                "<tr{}><td><a href=\"https://crates.io/crates/{}\">{}</a></td><td>{}</td>\
+                <td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                class,
+                name,
+                name,
+                escape(&dep.old_req),
+                escape(dep.locked.as_deref().unwrap_or("-")),
+                escape(dep.latest.as_deref().unwrap_or("-")),
+                escape(&dep.new_req),
+                dep.reason.map(note).unwrap_or(""),
+            )?;
+        }
+        writeln!(page, "</tbody>")?;
+        writeln!(page, "</table>")?;
+        writeln!(page, "</section>")?;
+    }
+    if !report.warnings.is_empty() {
+        writeln!(page, "<section>")?;
+        writeln!(page, "<h2>Warnings</h2>")?;
+        writeln!(page, "<ul>")?;
+        for warning in &report.warnings {
+            writeln!(page, "<li>{}</li>", escape(&warning.message))?;
+        }
+        writeln!(page, "</ul>")?;
+        writeln!(page, "</section>")?;
+    }
+    page.push_str(FOOTER);
+    Ok(page)
+}
+
+/// Like the note column of the tables
+pub fn note(reason: ReportReason) -> &'static str {
+    match reason {
+        ReportReason::Unchanged => "",
+        ReportReason::Compatible => "compatible",
+        ReportReason::Pinned => "pinned",
+        ReportReason::Constrained => "constrained",
+        ReportReason::TildeConstrained => "tilde-constrained",
+        ReportReason::Excluded => "excluded",
+        ReportReason::MsrvIncompatible => "msrv",
+        ReportReason::Filtered => "filtered",
+        ReportReason::Declined => "declined",
+        _ => "",
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const HEADER: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Dependency upgrades</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+th, td { padding: 0.25em 0.75em; text-align: left; border-bottom: 1px solid #ddd; }
+th { cursor: pointer; user-select: none; }
+tr.upgraded td { background: #eaf6ea; }
+</style>
+</head>
+<body>
+<h1>Dependency upgrades</h1>
+"#;
+
+/// Clicking a column header sorts its table, and clicking again reverses the order
+const FOOTER: &str = r#"<script>
+document.querySelectorAll("th").forEach((th) => {
+  th.addEventListener("click", () => {
+    const body = th.closest("table").tBodies[0];
+    const column = th.cellIndex;
+    const ascending = th.dataset.order !== "ascending";
+    th.dataset.order = ascending ? "ascending" : "descending";
+    const rows = Array.from(body.rows).sort((a, b) => {
+      const order = a.cells[column].textContent.localeCompare(
+        b.cells[column].textContent, undefined, { numeric: true });
+      return ascending ? order : -order;
+    });
+    body.append(...rows);
+  });
+});
+</script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escapes_markup() {
+        assert_eq!(escape("<1.0 & >=0.5"), "&lt;1.0 &amp; &gt;=0.5");
+    }
+}
@@ -12,6 +12,9 @@
 )]
 
 mod cli;
+mod html;
+mod renovate;
+mod summary;
 mod upgrade;
 
 use std::process;
@@ -0,0 +1,98 @@
+//! Upgrades in the shape of the package files Renovate's `cargo` manager extracts, so bots can
+//! group them into update PRs
+use cargo_edit::{DepTable, TimingsReport};
+use serde_derive::Serialize;
+
+/// Package files, grouped by manager like Renovate does
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub cargo: Vec<PackageFile>,
+    /// Where the time of the run went, with `--timings`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<TimingsReport>,
+}
+
+/// Dependencies of one manifest
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageFile {
+    /// Path to the manifest, relative to the workspace root
+    pub package_file: String,
+    pub deps: Vec<Dependency>,
+}
+
+/// A registry dependency, with the update `cargo upgrade` settled on
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dependency {
+    /// Name as written in the manifest
+    pub dep_name: String,
+    /// Name of the crate in the registry, when the dependency is renamed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package_name: Option<String>,
+    pub dep_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    pub datasource: &'static str,
+    pub current_value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locked_version: Option<String>,
+    /// Empty when the requirement is left alone
+    pub updates: Vec<Update>,
+}
+
+impl Dependency {
+    pub fn new(
+        dep_name: &str,
+        package_name: &str,
+        section: &DepTable,
+        current_value: &str,
+    ) -> Self {
+        Self {
+            dep_name: dep_name.to_owned(),
+            package_name: (dep_name != package_name).then(|| package_name.to_owned()),
+            dep_type: section.kind_table().to_owned(),
+            target: section.target().map(|target| target.to_owned()),
+            datasource: "crate",
+            current_value: current_value.to_owned(),
+            locked_version: None,
+            updates: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Update {
+    pub update_type: UpdateType,
+    pub new_value: String,
+    /// Newest known version matching `new_value`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_version: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdateType {
+    Major,
+    Minor,
+    Patch,
+}
+
+impl UpdateType {
+    /// Classify the update from the locked version, or `None` if it isn't known
+    pub fn new(breaking: bool, locked: Option<&str>, new_version: Option<&str>) -> Self {
+        if breaking {
+            return Self::Major;
+        }
+        let minor = |version: Option<&str>| {
+            version
+                .and_then(|v| semver::Version::parse(v).ok())
+                .map(|v| (v.major, v.minor))
+        };
+        match (minor(locked), minor(new_version)) {
+            (Some(locked), Some(new)) if locked != new => Self::Minor,
+            _ => Self::Patch,
+        }
+    }
+}
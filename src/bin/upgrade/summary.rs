@@ -0,0 +1,70 @@
+//! Upgrades as Markdown, for keeping a workspace run's results as a CI artifact
+use std::fmt::Write;
+use std::path::Path;
+
+use cargo_edit::UpgradeReport;
+
+use crate::html::note;
+
+/// Render `report` as a Markdown document, with manifest paths relative to `root_dir`
+pub fn render(report: &UpgradeReport, root_dir: &Path) -> Result<String, std::fmt::Error> {
+    let dependencies = report
+        .packages
+        .iter()
+        .flat_map(|package| &package.dependencies);
+    let total = dependencies.clone().count();
+    let upgraded = dependencies
+        .filter(|dep| dep.old_req != dep.new_req)
+        .count();
+
+    let mut summary = String::new();
+    writeln!(summary, "# Dependency upgrades")?;
+    writeln!(summary)?;
+    writeln!(summary, "{} of {} dependencies upgraded", upgraded, total)?;
+    for package in &report.packages {
+        let manifest_path = crate::upgrade::package_file(root_dir, &package.manifest_path);
+        writeln!(summary)?;
+        writeln!(summary, "## {}", package.name)?;
+        writeln!(summary)?;
+        writeln!(summary, "`{}`", manifest_path)?;
+        writeln!(summary)?;
+        if package.dependencies.is_empty() {
+            writeln!(summary, "No dependencies")?;
+            continue;
+        }
+        writeln!(
+            summary,
+            "| name | old req | locked | latest | new req | note |"
+        )?;
+        writeln!(
+            summary,
+            "| ---- | ------- | ------ | ------ | ------- | ---- |"
+        )?;
+        for dep in &package.dependencies {
+            writeln!(
+                summary,
+                "| {} | {} | {} | {} | {} | {} |",
+                cell(&dep.name),
+                cell(&dep.old_req),
+                cell(dep.locked.as_deref().unwrap_or("-")),
+                cell(dep.latest.as_deref().unwrap_or("-")),
+                cell(&dep.new_req),
+                dep.reason.map(note).unwrap_or(""),
+            )?;
+        }
+    }
+    if !report.warnings.is_empty() {
+        writeln!(summary)?;
+        writeln!(summary, "## Warnings")?;
+        writeln!(summary)?;
+        for warning in &report.warnings {
+            writeln!(summary, "- {}", warning.message)?;
+        }
+    }
+    Ok(summary)
+}
+
+/// Keep `|` from ending a table cell early
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
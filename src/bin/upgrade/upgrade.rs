@@ -1,18 +1,32 @@
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
-use std::io::Write;
-use std::path::PathBuf;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use cargo_edit::{
-    colorize_stderr, find, get_latest_dependency, registry_url, resolve_manifests, set_dep_version,
-    shell_note, shell_status, shell_warn, shell_write_stderr, update_registry_index, CargoResult,
-    Context, CrateSpec, Dependency, LocalManifest,
+    catch_interrupts, check_interrupted, colorize_stderr, crate_glob_match, enforce_read_only,
+    ensure_writable, filter_members, find, find_nested_crates, find_workspace_root,
+    find_workspace_roots, get_compatible_dependency, get_dependency_for_rust_version,
+    get_latest_git_rev, get_latest_git_tag, get_rust_version, git_tag_version,
+    is_published_manifest, is_read_only, manifest_package, normalize_crate_name,
+    normalize_requirement, package_rust_version, registry_index_age, registry_url,
+    resolve_manifests, set_dep_git_ref, set_dep_version, shell_note, shell_status, shell_warn,
+    shell_write_stderr, target_cfgs, update_registry_index, workspace_members, CargoFlags,
+    CargoResult, Context, CrateSpec, DepKind, DepTable, Dependency, DependencyReport, GitSource,
+    LatestCompatibleVersion, LatestVersion, LocalManifest, Lockfile, ManifestTransaction,
+    MinimalVersion, NotifyHooks, PackageReport, PublishedBefore, ReportReason, ReqStyle,
+    TimingsReport, UnstableFlag, UnstableFlags, UnstableOption, UpgradeReport, VersionQuery,
+    VersionStrategy, WarningReport,
 };
 use clap::Args;
 use indexmap::IndexMap;
 use semver::{Op, VersionReq};
 use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
 
+use crate::cli::DryRunArg;
+use crate::{html, renovate, summary};
+
 /// Upgrade dependency version requirements in Cargo.toml manifest files
 #[derive(Debug, Args)]
 #[clap(version)]
@@ -28,9 +42,27 @@ pub struct UpgradeArgs {
     #[clap(value_name = "DEP_ID")]
     dependency: Vec<String>,
 
-    /// Path to the manifest to upgrade
-    #[clap(long, value_name = "PATH", parse(from_os_str))]
-    manifest_path: Option<PathBuf>,
+    /// Path to the manifest to upgrade, repeat to upgrade several
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        env = "CARGO_UPGRADE_MANIFEST_PATH",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    manifest_path: Vec<PathBuf>,
+
+    /// File listing manifests to upgrade in turn, one path per line
+    #[clap(
+        long,
+        value_name = "FILE",
+        parse(from_os_str),
+        conflicts_with_all = &["published-crate", "recursive-dirs"],
+        env = "CARGO_UPGRADE_MANIFEST_LIST",
+        hide_env = true
+    )]
+    manifest_list: Option<PathBuf>,
 
     /// Package id of the crate to add this dependency to.
     #[clap(
@@ -38,10 +70,35 @@ pub struct UpgradeArgs {
         short = 'p',
         value_name = "PKGID",
         conflicts_with = "all",
-        conflicts_with = "workspace"
+        conflicts_with = "workspace",
+        env = "CARGO_UPGRADE_PACKAGE",
+        hide_env = true,
+        value_delimiter = ','
     )]
     pkgid: Vec<String>,
 
+    /// Package to leave out, like `cargo build --exclude`.
+    #[clap(
+        long,
+        value_name = "NAME",
+        conflicts_with = "pkgid",
+        env = "CARGO_UPGRADE_EXCLUDE_PACKAGE",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    exclude_package: Vec<String>,
+
+    /// Report upgrades for an extracted `.crate` without modifying it.
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        conflicts_with_all = &["manifest-path", "pkgid", "all", "workspace", "to-lockfile"],
+        env = "CARGO_UPGRADE_PUBLISHED_CRATE",
+        hide_env = true
+    )]
+    published_crate: Option<PathBuf>,
+
     /// Upgrade all packages in the workspace.
     #[clap(
         long,
@@ -52,40 +109,371 @@ pub struct UpgradeArgs {
     all: bool,
 
     /// Upgrade all packages in the workspace.
-    #[clap(long, conflicts_with = "all", conflicts_with = "pkgid")]
+    #[clap(
+        long,
+        conflicts_with = "all",
+        conflicts_with = "pkgid",
+        env = "CARGO_UPGRADE_WORKSPACE",
+        hide_env = true
+    )]
     workspace: bool,
 
+    /// Upgrade the members in directories matching this glob
+    #[clap(
+        long,
+        value_name = "GLOB",
+        conflicts_with_all = &["pkgid", "published-crate"],
+        env = "CARGO_UPGRADE_MEMBERS",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    members: Vec<String>,
+
+    /// Upgrade every workspace found beneath this directory
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        conflicts_with_all = &["manifest-path", "pkgid", "published-crate", "all", "workspace"],
+        env = "CARGO_UPGRADE_RECURSIVE_DIRS",
+        hide_env = true
+    )]
+    recursive_dirs: Option<PathBuf>,
+
+    /// Also upgrade path crates reached through dev-dependencies
+    #[clap(
+        long,
+        conflicts_with = "published-crate",
+        env = "CARGO_UPGRADE_TEST_SUPPORT",
+        hide_env = true
+    )]
+    test_support: bool,
+
+    /// Also upgrade non-member crates like `fuzz/` and `xtask/`
+    #[clap(
+        long,
+        conflicts_with_all = &["published-crate", "recursive-dirs"],
+        env = "CARGO_UPGRADE_INCLUDE_NESTED",
+        hide_env = true
+    )]
+    include_nested: bool,
+
     /// Print changes to be made without making them.
-    #[clap(long)]
-    dry_run: bool,
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        arg_enum,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "summary",
+        env = "CARGO_UPGRADE_DRY_RUN",
+        hide_env = true
+    )]
+    dry_run: Option<DryRunArg>,
+
+    /// Report the upgrades without writing any file or running hooks
+    #[clap(
+        long,
+        conflicts_with_all = &["remember", "update-lock-for-excluded"],
+        env = "CARGO_UPGRADE_READ_ONLY",
+        hide_env = true
+    )]
+    read_only: bool,
+
+    /// Report a table per package, or a row per dependency across them
+    #[clap(
+        long,
+        value_name = "GROUP",
+        arg_enum,
+        default_value = "package",
+        env = "CARGO_UPGRADE_GROUP_BY",
+        hide_env = true
+    )]
+    group_by: GroupBy,
+
+    /// Format to report the upgrades in
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        arg_enum,
+        default_value = "human",
+        conflicts_with_all = &["check-majors", "recursive-dirs"],
+        env = "CARGO_UPGRADE_OUTPUT_FORMAT",
+        hide_env = true
+    )]
+    output_format: OutputFormat,
+
+    /// Also write the report to `cargo-upgrade-report.json` and `.md` in the target directory
+    #[clap(
+        long,
+        conflicts_with_all = &["read-only", "check-majors"],
+        env = "CARGO_UPGRADE_SUMMARY",
+        hide_env = true
+    )]
+    summary: bool,
+
+    /// List dependencies with a new major version and fail if any
+    #[clap(
+        long,
+        conflicts_with = "to-lockfile",
+        env = "CARGO_UPGRADE_CHECK_MAJORS",
+        hide_env = true
+    )]
+    check_majors: bool,
+
+    /// Choose which of the upgrades to write before writing them
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "dry-run",
+            "read-only",
+            "check-majors",
+            "published-crate",
+            "recursive-dirs",
+        ],
+        env = "CARGO_UPGRADE_INTERACTIVE",
+        hide_env = true
+    )]
+    interactive: bool,
 
-    /// Upgrade dependencies pinned in the manifest.
-    #[clap(long)]
-    pinned: bool,
+    /// Warn when the upgrade leaves a crate locked at several versions
+    #[clap(long, env = "CARGO_UPGRADE_CHECK_DUPLICATES", hide_env = true)]
+    check_duplicates: bool,
+
+    /// Only upgrade this kind of dependency.
+    #[clap(
+        long,
+        arg_enum,
+        env = "CARGO_UPGRADE_KIND",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    kind: Vec<KindArg>,
+
+    /// Only write these kinds of upgrades
+    #[clap(
+        long,
+        value_name = "KIND",
+        arg_enum,
+        env = "CARGO_UPGRADE_ONLY",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    only: Vec<OnlyArg>,
+
+    /// Only upgrade dependencies that apply to this target triple
+    #[clap(
+        long,
+        value_name = "TRIPLE",
+        env = "CARGO_UPGRADE_TARGET",
+        hide_env = true
+    )]
+    target: Option<String>,
+
+    /// Upgrade pinned dependencies: `exact`, `ranges`, or `all` of them, or `<PACKAGE>:<LEVEL>`
+    /// for one package
+    #[clap(
+        long,
+        value_name = "LEVEL",
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "all",
+        env = "CARGO_UPGRADE_PINNED",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    pinned: Vec<PinnedSpec>,
 
     /// Run without accessing the network
-    #[clap(long)]
+    #[clap(long, env = "CARGO_UPGRADE_OFFLINE", hide_env = true)]
     offline: bool,
 
+    /// Use the cached registry indexes without updating them
+    #[clap(
+        long,
+        conflicts_with = "max-index-age",
+        env = "CARGO_UPGRADE_SKIP_INDEX_UPDATE",
+        hide_env = true
+    )]
+    skip_index_update: bool,
+
+    /// Only update indexes older than this, like `30m` or `2d`
+    #[clap(
+        long,
+        value_name = "DURATION",
+        parse(try_from_str = parse_duration),
+        env = "CARGO_UPGRADE_MAX_INDEX_AGE",
+        hide_env = true
+    )]
+    max_index_age: Option<Duration>,
+
     /// Upgrade all packages to the version in the lockfile.
-    #[clap(long)]
+    #[clap(long, env = "CARGO_UPGRADE_TO_LOCKFILE", hide_env = true)]
     to_lockfile: bool,
 
-    /// Crates to exclude and not upgrade.
-    #[clap(long)]
+    /// Only upgrade within current requirements, skipping Cargo.lock
+    #[clap(
+        long,
+        conflicts_with_all = &["to-lockfile", "check-majors"],
+        env = "CARGO_UPGRADE_COMPATIBLE_ONLY",
+        hide_env = true
+    )]
+    compatible_only: bool,
+
+    /// Also upgrade requirements that already allow the latest version, like `1.2` to `1.4`
+    #[clap(
+        long,
+        conflicts_with_all = &["compatible-only", "to-lockfile"],
+        env = "CARGO_UPGRADE_COMPATIBLE",
+        hide_env = true
+    )]
+    compatible: bool,
+
+    /// Upgrade the `rev` or `tag` of git dependencies, or skip them
+    #[clap(
+        long,
+        value_name = "MODE",
+        arg_enum,
+        default_value = "skip",
+        env = "CARGO_UPGRADE_GIT_DEPS",
+        hide_env = true
+    )]
+    git_deps: GitDepsArg,
+
+    /// Only upgrade to versions published before this date
+    #[clap(
+        long,
+        value_name = "DATE",
+        parse(try_from_str = parse_date),
+        conflicts_with_all = &["offline", "to-lockfile"],
+        env = "CARGO_UPGRADE_MAX_DATE",
+        hide_env = true
+    )]
+    max_date: Option<String>,
+
+    /// How to pick the version to upgrade to
+    #[clap(
+        long,
+        value_name = "STRATEGY",
+        arg_enum,
+        conflicts_with_all = &["compatible-only", "to-lockfile"],
+        env = "CARGO_UPGRADE_STRATEGY",
+        hide_env = true
+    )]
+    strategy: Option<StrategyArg>,
+
+    /// Skip versions needing a newer Rust than the package's `rust-version`
+    #[clap(
+        long,
+        conflicts_with = "to-lockfile",
+        env = "CARGO_UPGRADE_RESPECT_MSRV",
+        hide_env = true
+    )]
+    respect_msrv: bool,
+
+    /// Replace wildcard requirements with the locked version
+    #[clap(long, env = "CARGO_UPGRADE_FIX_WILDCARDS", hide_env = true)]
+    fix_wildcards: bool,
+
+    /// Rename deprecated keys, like `default_features`
+    #[clap(long, env = "CARGO_UPGRADE_FIX_DEPRECATIONS", hide_env = true)]
+    fix_deprecations: bool,
+
+    /// Spell out inherited `default-features = false` in members
+    #[clap(long, env = "CARGO_UPGRADE_EXPLICIT_DEFAULT_FEATURES", hide_env = true)]
+    explicit_default_features: bool,
+
+    /// Rewrite requirements in the workspace's configured style
+    #[clap(long, env = "CARGO_UPGRADE_NORMALIZE_REQS", hide_env = true)]
+    normalize_reqs: bool,
+
+    /// Crates to exclude and not upgrade, by name or glob like `tokio-*`, or `<PACKAGE>:<CRATE>`
+    /// to exclude only from one package.
+    #[clap(
+        long,
+        env = "CARGO_UPGRADE_EXCLUDE",
+        hide_env = true,
+        value_delimiter = ','
+    )]
     exclude: Vec<String>,
 
+    /// File listing crates to exclude, one name or glob per line.
+    #[clap(
+        long,
+        value_name = "FILE",
+        parse(from_os_str),
+        env = "CARGO_UPGRADE_EXCLUDE_FROM",
+        hide_env = true,
+        value_delimiter = ','
+    )]
+    exclude_from: Vec<PathBuf>,
+
+    /// Update excluded crates in Cargo.lock within their requirements
+    #[clap(
+        long,
+        conflicts_with_all = &["compatible-only", "locked", "frozen", "published-crate"],
+        env = "CARGO_UPGRADE_UPDATE_LOCK_FOR_EXCLUDED",
+        hide_env = true
+    )]
+    update_lock_for_excluded: bool,
+
+    /// Save the `--exclude` patterns in the workspace's config
+    #[clap(
+        long,
+        requires = "exclude",
+        env = "CARGO_UPGRADE_REMEMBER",
+        hide_env = true
+    )]
+    remember: bool,
+
+    /// Why the remembered exclusions are held back
+    #[clap(
+        long,
+        value_name = "TEXT",
+        requires = "remember",
+        env = "CARGO_UPGRADE_REASON",
+        hide_env = true
+    )]
+    reason: Option<String>,
+
+    /// Day the remembered exclusions expire on
+    #[clap(
+        long,
+        value_name = "DATE",
+        parse(try_from_str = parse_date),
+        requires = "remember",
+        env = "CARGO_UPGRADE_UNTIL",
+        hide_env = true
+    )]
+    until: Option<String>,
+
     /// Require `Cargo.toml` to be up to date
-    #[clap(long)]
+    #[clap(long, env = "CARGO_UPGRADE_LOCKED", hide_env = true)]
     locked: bool,
 
+    /// Require `Cargo.lock` and cache are up to date
+    #[clap(long, env = "CARGO_UPGRADE_FROZEN", hide_env = true)]
+    frozen: bool,
+
+    /// Continue past failing dependencies, reporting them at the end
+    #[clap(long, env = "CARGO_UPGRADE_IGNORE_ERRORS", hide_env = true)]
+    ignore_errors: bool,
+
+    /// Print where the time of the run went
+    #[clap(long, env = "CARGO_UPGRADE_TIMINGS", hide_env = true)]
+    timings: bool,
+
     /// Use verbose output
-    #[clap(short, long)]
+    #[clap(short, long, env = "CARGO_UPGRADE_VERBOSE", hide_env = true)]
     verbose: bool,
 
     /// Unstable (nightly-only) flags
-    #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
-    unstable_features: Vec<UnstableOptions>,
+    #[clap(short = 'Z', value_name = "FLAG", global = true)]
+    unstable_features: Vec<UnstableFlag>,
+
+    /// Whether this run is one of several, over workspaces or manifests
+    #[clap(skip)]
+    one_of_several: bool,
 }
 
 impl UpgradeArgs {
@@ -94,15 +482,210 @@ impl UpgradeArgs {
     }
 
     fn workspace(&self) -> bool {
-        self.all || self.workspace
+        self.all || self.workspace || !self.members.is_empty()
+    }
+
+    /// The `--pinned` levels that apply to `package`
+    fn pinned_levels<'a>(&'a self, package: &'a str) -> impl Iterator<Item = PinnedArg> + 'a {
+        self.pinned
+            .iter()
+            .filter(move |spec| spec.applies_to(package))
+            .map(|spec| spec.level)
+    }
+
+    /// Whether `--pinned` upgrades exact requirements, like `=1.2.3`, in `package`
+    fn upgrades_exact(&self, package: &str) -> bool {
+        self.pinned_levels(package)
+            .any(|level| matches!(level, PinnedArg::Exact | PinnedArg::All))
+    }
+
+    /// Whether `--pinned` upgrades range requirements, like `<2` or `~1.2`, in `package`
+    fn upgrades_ranges(&self, package: &str) -> bool {
+        self.pinned_levels(package)
+            .any(|level| matches!(level, PinnedArg::Ranges | PinnedArg::All))
+    }
+
+    fn cargo_flags(&self) -> CargoFlags {
+        CargoFlags {
+            offline: self.offline,
+            // `cargo` must not update `Cargo.lock` behind `--read-only`'s back
+            locked: self.locked || self.read_only,
+            frozen: self.frozen,
+        }
+    }
+
+    fn offline(&self) -> bool {
+        self.cargo_flags().is_offline()
+    }
+
+    /// Whether `--locked` or `--frozen` forbid changing the requirements
+    fn locked(&self) -> bool {
+        self.locked || self.frozen
+    }
+
+    /// Update `registry`'s index, unless the cached one was asked for or is recent enough
+    fn update_index(&self, registry: &url::Url) -> CargoResult<()> {
+        if self.offline() || self.skip_index_update {
+            return Ok(());
+        }
+        if let Some(max_index_age) = self.max_index_age {
+            if let Some(age) = registry_index_age(registry)? {
+                if age <= max_index_age {
+                    return Ok(());
+                }
+            }
+        }
+        update_registry_index(registry, false)
+    }
+
+    fn manifest_path(&self) -> Option<&Path> {
+        self.published_crate
+            .as_deref()
+            .or_else(|| self.manifest_path.first().map(PathBuf::as_path))
+    }
+
+    /// How versions are picked, from `--strategy` or the flags it stands in for
+    fn version_strategy(&self) -> CargoResult<Box<dyn VersionStrategy>> {
+        let strategy: Box<dyn VersionStrategy> = match (self.strategy, &self.max_date) {
+            (None | Some(StrategyArg::MaxByDate), Some(max_date)) => {
+                Box::new(PublishedBefore::new(max_date, self.compatible_only))
+            }
+            (Some(StrategyArg::MaxByDate), None) => {
+                anyhow::bail!("`--strategy max-by-date` requires `--max-date`")
+            }
+            (Some(_), Some(_)) => {
+                anyhow::bail!("`--max-date` only applies to `--strategy max-by-date`")
+            }
+            (None, None) if self.compatible_only => Box::new(LatestCompatibleVersion),
+            (None | Some(StrategyArg::Latest), None) => Box::new(LatestVersion),
+            (Some(StrategyArg::LatestCompatible), None) => Box::new(LatestCompatibleVersion),
+            (Some(StrategyArg::Minimal), None) => Box::new(MinimalVersion),
+        };
+        Ok(strategy)
+    }
+
+    /// Whether versions are only picked within the current requirements, leaving `Cargo.lock`
+    fn compatible_only(&self) -> bool {
+        self.compatible_only
+            || matches!(
+                self.strategy,
+                Some(StrategyArg::LatestCompatible | StrategyArg::Minimal)
+            )
+    }
+
+    /// Published crates are audited and majors are only checked, never modified
+    fn dry_run(&self) -> bool {
+        self.dry_run.is_some()
+            || self.published_crate.is_some()
+            || self.check_majors
+            || self.read_only
+    }
+
+    /// Exclusion patterns from `--exclude` and `--exclude-from`
+    fn excludes(&self) -> CargoResult<Vec<String>> {
+        let mut excludes = self.exclude.clone();
+        for path in &self.exclude_from {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            excludes.extend(parse_list_file(&content));
+        }
+        Ok(excludes)
+    }
+
+    /// Manifests from `--manifest-path` and `--manifest-list`, whose paths are relative to it
+    fn manifest_paths(&self) -> CargoResult<Vec<PathBuf>> {
+        let mut manifest_paths = self.manifest_path.clone();
+        if let Some(path) = &self.manifest_list {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let dir = path.parent().unwrap_or_else(|| Path::new(""));
+            manifest_paths.extend(parse_list_file(&content).map(|line| dir.join(line)));
+        }
+        Ok(manifest_paths)
     }
 
     fn resolve_targets(&self) -> CargoResult<Vec<cargo_metadata::Package>> {
-        resolve_manifests(
-            self.manifest_path.as_deref(),
+        if let Some(published_crate) = self.published_crate.as_deref() {
+            // Published manifests are normalized and detached from their workspace
+            return Ok(vec![manifest_package(published_crate)?]);
+        }
+
+        // An explicit manifest lets a package outside any workspace skip `cargo metadata`, so
+        // checking a fresh clone of a library doesn't wait on it
+        let manifest_path = match self.manifest_path.first() {
+            Some(manifest_path) => Some(manifest_path.clone()),
+            None if self.compatible_only() && !self.workspace() => Some(find(None)?),
+            None => None,
+        };
+        let mut targets = resolve_manifests(
+            manifest_path.as_deref(),
             self.workspace(),
             self.pkgid.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
-        )
+            self.cargo_flags(),
+        )?;
+        targets.retain(|p| !self.exclude_package.contains(&p.name));
+        let targets = filter_members(targets, &self.members)?;
+        if self.test_support {
+            self.with_test_support(targets)
+        } else {
+            Ok(targets)
+        }
+    }
+
+    /// Add the path crates that `targets` dev-depend on, like test-support crates, and in turn
+    /// the ones those dev-depend on, whether or not they were selected
+    fn with_test_support(
+        &self,
+        mut targets: Vec<cargo_metadata::Package>,
+    ) -> CargoResult<Vec<cargo_metadata::Package>> {
+        let members = workspace_members(
+            self.manifest_path.first().map(PathBuf::as_path),
+            self.cargo_flags(),
+        )?;
+        let mut known = targets
+            .iter()
+            .map(|package| dunce::canonicalize(&package.manifest_path))
+            .collect::<Result<BTreeSet<_>, _>>()?;
+        let mut pending = targets.clone();
+        while let Some(package) = pending.pop() {
+            for dep in &package.dependencies {
+                let dir = match &dep.path {
+                    Some(dir) if dep.kind == cargo_metadata::DependencyKind::Development => dir,
+                    _ => continue,
+                };
+                let manifest_path = dunce::canonicalize(dir.join("Cargo.toml"))?;
+                if self.exclude_package.contains(&dep.name) || !known.insert(manifest_path.clone())
+                {
+                    continue;
+                }
+                // Only members come with their dependencies, to follow in turn
+                let support = match members
+                    .iter()
+                    .find(|member| member.manifest_path.as_std_path() == manifest_path)
+                {
+                    Some(member) => member.clone(),
+                    None => manifest_package(&manifest_path)?,
+                };
+                targets.push(support.clone());
+                pending.push(support);
+            }
+        }
+        Ok(targets)
+    }
+
+    /// Propagate `err`, or with `--ignore-errors`, record it for the end of the run
+    fn ignore_error(
+        &self,
+        failures: &mut Vec<String>,
+        subject: &str,
+        err: anyhow::Error,
+    ) -> CargoResult<()> {
+        if !self.ignore_errors {
+            return Err(err);
+        }
+        shell_warn(&format!("skipping {}: {:#}", subject, err))?;
+        failures.push(format!("  {}: {:#}", subject, err));
+        Ok(())
     }
 
     fn verbose<F>(&self, mut callback: F) -> CargoResult<()>
@@ -117,286 +700,1932 @@ impl UpgradeArgs {
     }
 }
 
+/// Options for `-Z`, see `-Z help`
+const UNSTABLE_OPTIONS: &[UnstableOption] = &[];
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
-enum UnstableOptions {}
+enum OutputFormat {
+    /// Only the tables, on stderr
+    Human,
+    /// The report of every dependency as JSON, on stdout, in place of the tables
+    Json,
+    /// Renovate's package files as JSON, on stdout
+    Renovate,
+    /// A standalone page with sortable tables, on stdout
+    Html,
+}
 
-/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
-/// messages.
-fn exec(args: UpgradeArgs) -> CargoResult<()> {
-    if args.all {
-        deprecated_message("The flag `--all` has been deprecated in favor of `--workspace`")?;
-    }
+/// What the rows of the tables are grouped by
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum GroupBy {
+    /// A table of dependencies for each package
+    Package,
+    /// One row for each dependency, with the requirements of every package on it
+    Dep,
+}
 
-    if !args.offline && !args.to_lockfile {
-        let url = registry_url(&find(args.manifest_path.as_deref())?, None)?;
-        update_registry_index(&url, false)?;
-    }
+/// Which [`VersionStrategy`] `--strategy` picks versions with
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum StrategyArg {
+    /// The newest version
+    Latest,
+    /// The newest version the current requirement allows, like `--compatible-only`
+    LatestCompatible,
+    /// The oldest version the current requirement allows
+    Minimal,
+    /// The newest version published before `--max-date`
+    MaxByDate,
+}
 
-    let manifests = args.resolve_targets()?;
-    let locked = load_lockfile(&manifests, args.locked, args.offline).unwrap_or_default();
+/// Which pinned requirements `--pinned` upgrades
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum PinnedArg {
+    /// Exact requirements, like `=1.2.3`
+    Exact,
+    /// Upper bounds, wildcards and tilde requirements, like `<2`, `1.*` or `~1.2`
+    Ranges,
+    /// Both, and renamed dependencies
+    All,
+}
 
-    let selected_dependencies = args
-        .dependency
-        .iter()
-        .map(|name| {
-            let spec = CrateSpec::resolve(name)?;
-            Ok((spec.name, spec.version_req))
-        })
-        .collect::<CargoResult<IndexMap<_, _>>>()?;
-    let mut processed_keys = BTreeSet::new();
+/// A `--pinned` level, optionally scoped to the packages matching a glob, like `my-cli:exact`
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PinnedSpec {
+    package: Option<String>,
+    level: PinnedArg,
+}
 
-    let mut updated_registries = BTreeSet::new();
-    let mut any_crate_modified = false;
-    let mut compatible_present = false;
-    let mut pinned_present = false;
-    for package in &manifests {
-        let mut manifest = LocalManifest::try_new(package.manifest_path.as_std_path())?;
-        let mut crate_modified = false;
-        let mut table = Vec::new();
-        let manifest_path = manifest.path.clone();
-        shell_status("Checking", &format!("{}'s dependencies", package.name))?;
-        for dep_table in manifest.get_dependency_tables_mut() {
-            for (dep_key, dep_item) in dep_table.iter_mut() {
-                let dep_key = dep_key.get();
-                processed_keys.insert(dep_key.to_owned());
-                if !selected_dependencies.is_empty() && !selected_dependencies.contains_key(dep_key)
-                {
-                    args.verbose(|| {
-                        shell_warn(&format!("ignoring {}, excluded by user", dep_key))
-                    })?;
-                    continue;
-                }
-                if args.exclude.contains(&dep_key.to_owned()) {
-                    args.verbose(|| {
-                        shell_warn(&format!("ignoring {}, excluded by user", dep_key))
-                    })?;
-                    continue;
-                }
-                let dependency = match Dependency::from_toml(&manifest_path, dep_key, dep_item) {
-                    Ok(dependency) => dependency,
-                    Err(err) => {
-                        shell_warn(&format!("ignoring {}, unsupported entry: {}", dep_key, err))?;
-                        continue;
-                    }
-                };
-                let old_version_req = match dependency.version() {
-                    Some(version_req) => version_req.to_owned(),
-                    None => {
-                        args.verbose(|| {
-                            let source = dependency
-                                .source()
-                                .map(|s| s.to_string())
-                                .unwrap_or_else(|| "unknown".to_owned());
-                            shell_warn(&format!(
-                                "ignoring {}, source is {}",
-                                dependency.toml_key(),
-                                source,
-                            ))
-                        })?;
-                        continue;
-                    }
-                };
+impl PinnedSpec {
+    fn applies_to(&self, package: &str) -> bool {
+        self.package
+            .as_deref()
+            .map_or(true, |pattern| crate_glob_match(pattern, package))
+    }
+}
 
-                let mut reason = None;
-                if !args.pinned {
-                    if dependency.rename.is_some() {
-                        reason.get_or_insert(Reason::Pinned);
-                        pinned_present = true;
-                    }
+impl std::str::FromStr for PinnedSpec {
+    type Err = String;
 
-                    if is_pinned_req(&old_version_req) {
-                        reason.get_or_insert(Reason::Pinned);
-                        pinned_present = true;
-                    }
-                }
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (package, level) = match s.split_once(':') {
+            Some((package, level)) => (Some(package.to_owned()), level),
+            None => (None, s),
+        };
+        let level = <PinnedArg as clap::ArgEnum>::from_str(level, true)?;
+        Ok(Self { package, level })
+    }
+}
 
-                let locked_version =
-                    find_locked_version(&dependency.name, &old_version_req, &locked);
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum KindArg {
+    Normal,
+    Dev,
+    Build,
+}
 
-                let latest_version = if dependency
-                    .source
-                    .as_ref()
-                    .and_then(|s| s.as_registry())
-                    .is_some()
-                {
-                    // Update indices for any alternative registries, unless
-                    // we're offline.
-                    let registry_url = dependency
-                        .registry()
-                        .map(|registry| registry_url(&manifest_path, Some(registry)))
-                        .transpose()?;
-                    if !args.offline {
-                        if let Some(registry_url) = &registry_url {
-                            if updated_registries.insert(registry_url.to_owned()) {
-                                update_registry_index(registry_url, false)?;
-                            }
-                        }
-                    }
-                    let is_prerelease = old_version_req.contains('-');
-                    let latest_version = get_latest_dependency(
-                        &dependency.name,
-                        is_prerelease,
-                        &manifest_path,
-                        registry_url.as_ref(),
-                    )
-                    .map(|d| {
-                        d.version()
-                            .expect("registry packages always have a version")
-                            .to_owned()
-                    });
-                    latest_version.ok()
-                } else {
-                    None
-                };
+impl From<KindArg> for DepKind {
+    fn from(kind: KindArg) -> Self {
+        match kind {
+            KindArg::Normal => DepKind::Normal,
+            KindArg::Dev => DepKind::Development,
+            KindArg::Build => DepKind::Build,
+        }
+    }
+}
 
-                let new_version_req = if reason.is_some() {
-                    old_version_req.clone()
-                } else if let Some(Some(new_version_req)) =
-                    selected_dependencies.get(dependency.toml_key())
-                {
-                    new_version_req.to_owned()
-                } else {
-                    let new_version_req = if args.to_lockfile {
-                        if let Some(locked_version) = &locked_version {
-                            let new_version_req = locked_version.clone();
-                            let new_version: semver::Version = locked_version.parse()?;
-                            match cargo_edit::upgrade_requirement(&old_version_req, &new_version) {
-                                Ok(Some(version_req)) => Some(version_req),
-                                Err(_) => Some(new_version_req),
-                                _ => None,
-                            }
-                        } else {
-                            None
-                        }
-                    } else if let Some(latest_version) = &latest_version {
-                        let mut new_version_req = latest_version.clone();
-                        let new_version: semver::Version = latest_version.parse()?;
-                        match cargo_edit::upgrade_requirement(&old_version_req, &new_version) {
-                            Ok(Some(version_req)) => {
-                                new_version_req = version_req;
-                            }
-                            Err(_) => {}
-                            _ => {
-                                new_version_req = old_version_req.clone();
-                            }
-                        }
-                        if new_version_req == old_version_req {
-                            None
-                        } else if old_version_compatible(&old_version_req, latest_version) {
-                            reason.get_or_insert(Reason::Compatible);
-                            compatible_present = true;
-                            None
-                        } else {
-                            Some(new_version_req)
-                        }
-                    } else {
-                        None
-                    };
-                    new_version_req.unwrap_or_else(|| old_version_req.clone())
+/// Kind of requirement change, for `--only`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum OnlyArg {
+    /// To a version the old requirement doesn't allow
+    Breaking,
+    /// Within the versions the old requirement allows
+    Compatible,
+    /// Of a pinned requirement or renamed dependency, with `--pinned`
+    Pinned,
+}
+
+impl OnlyArg {
+    fn classify(old_version_req: &str, new_version_req: &str, pinned: bool) -> Self {
+        if pinned {
+            Self::Pinned
+        } else if is_breaking_change(old_version_req, new_version_req) {
+            Self::Breaking
+        } else {
+            Self::Compatible
+        }
+    }
+}
+
+/// Which git dependencies `--git-deps` upgrades
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum GitDepsArg {
+    /// Those pinned with a `rev`, to the head of the default branch
+    Rev,
+    /// Those pinned with a `tag`, to the newest semver tag
+    Tag,
+    /// None of them
+    Skip,
+}
+
+/// A git dependency's `tag` or `rev`, and the newest one it can move to
+struct GitRef {
+    key: &'static str,
+    current: String,
+    latest: String,
+}
+
+impl GitRef {
+    /// Look up where `mode` moves `git`, if it has the field `mode` upgrades
+    fn lookup(mode: GitDepsArg, git: &GitSource) -> CargoResult<Option<Self>> {
+        match (mode, &git.tag, &git.rev) {
+            (GitDepsArg::Tag, Some(tag), _) => {
+                let current = match git_tag_version(tag) {
+                    Some(current) => current,
+                    None => anyhow::bail!("tag `{}` isn't a version to upgrade from", tag),
                 };
-                if new_version_req == old_version_req {
-                    reason.get_or_insert(Reason::Unchanged);
-                }
-                if new_version_req != old_version_req {
-                    set_dep_version(dep_item, &new_version_req)?;
-                    crate_modified = true;
-                    any_crate_modified = true;
+                let latest = get_latest_git_tag(&git.git, !current.pre.is_empty())?
+                    .filter(|latest| git_tag_version(latest).map_or(false, |v| current < v))
+                    .unwrap_or_else(|| tag.clone());
+                Ok(Some(Self {
+                    key: "tag",
+                    current: tag.clone(),
+                    latest,
+                }))
+            }
+            (GitDepsArg::Rev, _, Some(rev)) => {
+                if !rev.chars().all(|c| c.is_ascii_hexdigit()) {
+                    anyhow::bail!("rev `{}` isn't a commit hash to upgrade from", rev);
                 }
-                table.push(Dep {
-                    name: dependency.toml_key().to_owned(),
-                    old_version_req,
-                    locked_version,
-                    latest_version,
-                    new_version_req,
-                    reason,
-                });
+                let head = get_latest_git_rev(&git.git)?;
+                // Abbreviated hashes stay abbreviated
+                let latest = if head.starts_with(rev.as_str()) {
+                    rev.clone()
+                } else {
+                    head[..rev.len().clamp(7, head.len())].to_owned()
+                };
+                Ok(Some(Self {
+                    key: "rev",
+                    current: rev.clone(),
+                    latest,
+                }))
             }
+            _ => Ok(None),
         }
-        if !table.is_empty() {
-            print_upgrade(table, args.verbose)?;
+    }
+
+    /// The kind of change moving to `latest` is, for `--only`
+    fn classify(&self) -> OnlyArg {
+        match (
+            git_tag_version(&self.current),
+            git_tag_version(&self.latest),
+        ) {
+            (Some(current), Some(latest)) => {
+                OnlyArg::classify(&current.to_string(), &latest.to_string(), false)
+            }
+            // Any commit can break the API
+            _ => OnlyArg::Breaking,
         }
-        if !args.dry_run && !args.locked && crate_modified {
-            manifest.write()?;
+    }
+}
+
+/// Registry of dependencies without an explicit `registry`
+const CRATES_IO: &str = "crates-io";
+
+/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
+/// messages.
+fn exec(mut args: UpgradeArgs) -> CargoResult<()> {
+    UnstableFlags::new(&args.unstable_features, UNSTABLE_OPTIONS)?;
+    if args.all {
+        deprecated_message("The flag `--all` has been deprecated in favor of `--workspace`")?;
+    }
+
+    catch_interrupts()?;
+    if args.read_only {
+        enforce_read_only();
+    }
+
+    if let Some(dir) = args.recursive_dirs.clone() {
+        return upgrade_recursive(args, &dir);
+    }
+    let mut manifest_paths = args.manifest_paths()?;
+    if args.include_nested {
+        manifest_paths = with_nested_crates(manifest_paths, args.cargo_flags())?;
+    }
+    if 1 < manifest_paths.len() {
+        if args.output_format != OutputFormat::Human {
+            anyhow::bail!("`--output-format` can only report on one manifest at a time");
         }
+        let cwd = dunce::canonicalize(std::env::current_dir()?)?;
+        let targets = manifest_paths
+            .into_iter()
+            .map(|path| {
+                let relative = path.strip_prefix(&cwd).unwrap_or(&path);
+                (relative.display().to_string(), path)
+            })
+            .collect();
+        return upgrade_each(args, "manifest", targets);
     }
+    args.manifest_path = manifest_paths;
+    upgrade(&args, &mut BTreeSet::new())
+}
 
-    if any_crate_modified {
-        if args.locked {
-            anyhow::bail!("cannot upgrade due to `--locked`");
-        } else {
-            load_lockfile(&manifests, args.locked, args.offline)?;
+/// Follow each manifest with the companion crates of its workspace, defaulting to the manifest of
+/// the current directory
+fn with_nested_crates(
+    manifest_paths: Vec<PathBuf>,
+    flags: CargoFlags,
+) -> CargoResult<Vec<PathBuf>> {
+    let manifest_paths = if manifest_paths.is_empty() {
+        vec![dunce::canonicalize(find(None)?)?]
+    } else {
+        manifest_paths
+    };
+    let mut all = Vec::new();
+    for manifest_path in manifest_paths {
+        let nested = find_nested_crates(&manifest_path, flags)?;
+        all.push(manifest_path);
+        for path in nested {
+            if !all.contains(&path) {
+                all.push(path);
+            }
         }
     }
+    Ok(all)
+}
 
-    let unused = selected_dependencies
-        .keys()
-        .filter(|k| !processed_keys.contains(k.as_str()))
+/// Upgrade each workspace beneath `dir` in turn, carrying on past the ones that fail
+fn upgrade_recursive(mut args: UpgradeArgs, dir: &Path) -> CargoResult<()> {
+    let roots = find_workspace_roots(dir)?;
+    if roots.is_empty() {
+        anyhow::bail!("no workspaces found in {}", dir.display());
+    }
+    let base = dunce::canonicalize(dir)?;
+
+    args.workspace = true;
+    let targets = roots
+        .into_iter()
+        .map(|root| {
+            let relative = root.strip_prefix(&base).unwrap_or(&root);
+            (relative.display().to_string(), root)
+        })
+        .collect();
+    upgrade_each(args, "workspace", targets)
+}
+
+/// Upgrade the manifest of each `(label, path)` target in turn, carrying on past the ones that
+/// fail, where `kind` names what the targets are in the report
+fn upgrade_each(
+    mut args: UpgradeArgs,
+    kind: &str,
+    targets: Vec<(String, PathBuf)>,
+) -> CargoResult<()> {
+    args.one_of_several = true;
+    let mut failures = Vec::new();
+    let mut processed_keys = BTreeSet::new();
+    // Targets whose manifests were already written, for when Ctrl-C stops the rest
+    let mut written = Vec::new();
+    for (label, path) in &targets {
+        if let Err(err) = check_interrupted() {
+            if written.is_empty() {
+                return Err(err);
+            }
+            anyhow::bail!(
+                "Interrupted after upgrading {}, the remaining {}s were not modified",
+                written.join(", "),
+                kind
+            );
+        }
+        shell_status("Upgrading", &format!("{} {}", kind, label))?;
+        args.manifest_path = vec![path.clone()];
+        match upgrade(&args, &mut processed_keys) {
+            Ok(()) if !args.dry_run() && !args.locked() => written.push(label.as_str()),
+            Ok(()) => {}
+            Err(err) => {
+                shell_warn(&format!("skipping {}: {:#}", label, err))?;
+                failures.push(format!("  {}: {:#}", label, err));
+            }
+        }
+    }
+
+    shell_note(&format!(
+        "upgraded {} of {} {}s",
+        targets.len() - failures.len(),
+        targets.len(),
+        kind
+    ))?;
+    let selected = args
+        .dependency
+        .iter()
+        .map(|name| Ok(CrateSpec::resolve(name)?.name))
+        .collect::<CargoResult<Vec<_>>>()?;
+    check_selected_exist(selected.iter(), &processed_keys)?;
+    match failures.len() {
+        0 => Ok(()),
+        1 => anyhow::bail!("1 {} failed:\n{}", kind, failures.join("\n")),
+        n => anyhow::bail!("{} {}s failed:\n{}", n, kind, failures.join("\n")),
+    }
+}
+
+/// Fail on dependencies that were asked for but not found in any manifest, under either spelling
+/// of `-` and `_`
+fn check_selected_exist<'s>(
+    selected: impl Iterator<Item = &'s String>,
+    processed_keys: &BTreeSet<String>,
+) -> CargoResult<()> {
+    let unused = selected
+        .filter(|k| !processed_keys.contains(&normalize_crate_name(k)))
         .map(|k| k.as_str())
         .collect::<Vec<_>>();
     match unused.len() {
-        0 => {}
+        0 => Ok(()),
         1 => anyhow::bail!("dependency {} doesn't exist", unused.join(", ")),
         _ => anyhow::bail!("dependencies {} don't exist", unused.join(", ")),
     }
+}
+
+/// Upgrade the dependencies of the packages `args` selects, recording which were found in
+/// `processed_keys`
+fn upgrade(args: &UpgradeArgs, processed_keys: &mut BTreeSet<String>) -> CargoResult<()> {
+    let strategy = args.version_strategy()?;
+    if args.only.contains(&OnlyArg::Pinned) && args.pinned.is_empty() {
+        anyhow::bail!(
+            "`--only pinned` requires `--pinned`, or pinned requirements aren't upgraded"
+        );
+    }
+    let mut timings = Timings::new();
+    if !args.to_lockfile {
+        let url = registry_url(&find(args.manifest_path())?, None)?;
+        timed(&mut timings.index_update, || args.update_index(&url))?;
+    }
+
+    let manifests = timed(&mut timings.metadata, || args.resolve_targets())?;
+    // Extracted `.crate`s have no workspace, and so no `Cargo.lock` to read or update
+    let detached = manifests
+        .iter()
+        .map(|p| p.manifest_path.as_std_path())
+        .filter(|path| args.published_crate.is_none() && is_published_manifest(path))
+        .collect::<Vec<_>>();
+    for manifest_path in &detached {
+        shell_warn(&format!(
+            "{} is from a published `.crate`, so it is upgraded without a `Cargo.lock`; use \
+            `--published-crate` to only audit it",
+            manifest_path.display()
+        ))?;
+    }
+    let without_lockfile = args.published_crate.is_some() || !detached.is_empty();
+    let locked = if without_lockfile || args.compatible_only() {
+        Lockfile::default()
+    } else {
+        timed(&mut timings.metadata, || {
+            load_lockfile(&manifests, args.cargo_flags())
+        })
+        .unwrap_or_default()
+    };
+
+    let mut excludes = args.excludes()?;
+    let kinds = args
+        .kind
+        .iter()
+        .map(|k| DepKind::from(*k))
+        .collect::<Vec<_>>();
+    let target = args
+        .target
+        .as_deref()
+        .map(|triple| -> CargoResult<_> { Ok((triple, target_cfgs(triple)?)) })
+        .transpose()?;
+    let selected_dependencies = args
+        .dependency
+        .iter()
+        .map(|name| {
+            let spec = CrateSpec::resolve(name)?;
+            Ok((spec.name, spec.version_req))
+        })
+        .collect::<CargoResult<IndexMap<_, _>>>()?;
+    // Like the registry, `foo-bar` selects `foo_bar` and the other way around
+    let selected = |key: &str| {
+        let key = normalize_crate_name(key);
+        selected_dependencies
+            .iter()
+            .find(|(name, _)| normalize_crate_name(name) == key)
+            .map(|(_, req)| req)
+    };
 
-    if pinned_present {
+    // Reported once the run is over, where they can't scroll away among the tables
+    let mut warnings = Vec::new();
+    let today = today();
+    for exclude in remembered_excludes(&manifests)? {
+        match exclude.until {
+            Some(until) if until <= today => {
+                let reason = exclude
+                    .reason
+                    .map(|reason| format!(" ({})", reason))
+                    .unwrap_or_default();
+                warnings.push(WarningReport::new(format!(
+                    "the exclusion of {} expired on {}{} and no longer applies",
+                    exclude.pattern, until, reason
+                )));
+            }
+            _ => excludes.push(exclude.pattern),
+        }
+    }
+    let req_style = if args.normalize_reqs {
+        Some(req_style(&manifests)?)
+    } else {
+        None
+    };
+    let defaults_off = defaults_off_in_workspace(&manifests)?;
+    let root_dir = manifests
+        .first()
+        .map(|package| {
+            let manifest_path = package.manifest_path.as_std_path();
+            let root = find_workspace_root(manifest_path).unwrap_or_else(|_| manifest_path.into());
+            root.parent()
+                .expect("manifest is always in a directory")
+                .to_owned()
+        })
+        .unwrap_or_default();
+    let mut run = Run {
+        args,
+        strategy,
+        locked,
+        req_style,
+        defaults_off,
+        root_dir,
+        timings,
+        updated_registries: BTreeSet::new(),
+        any_crate_modified: false,
+        compatible_present: false,
+        pinned_present: false,
+        implicit_defaults_present: false,
+        deprecations_present: false,
+        tilde_present: false,
+        wildcard_present: false,
+        failures: Vec::new(),
+        majors: Vec::new(),
+        lock_updates: BTreeSet::new(),
+        registry_lookups: BTreeMap::new(),
+        failed_registries: BTreeMap::new(),
+        warnings,
+        renovate: renovate::Report::default(),
+        report: UpgradeReport::new(),
+        grouped: Vec::new(),
+    };
+    let mut transaction = ManifestTransaction::new();
+    // Recorded once `--interactive` settled which changes to write
+    let mut pending = Vec::new();
+    for package in &manifests {
+        let load_started = Instant::now();
+        let manifest = transaction.load(package.manifest_path.as_std_path());
+        run.timings.manifest_io += load_started.elapsed();
+        let manifest = match manifest {
+            Ok(manifest) => manifest,
+            Err(err) => {
+                args.ignore_error(&mut run.failures, &package.name, err)?;
+                continue;
+            }
+        };
+        shell_status("Checking", &format!("{}'s dependencies", package.name))?;
+        // Dependencies in `dev_dependencies` are only upgraded once it's renamed
+        if args.fix_deprecations {
+            for (key, new) in manifest.fix_deprecated_keys() {
+                shell_status("Renaming", &format!("`{}` to `{}`", key, new))?;
+            }
+        }
+        for (key, new) in manifest.deprecated_keys() {
+            let message = if args.fix_deprecations {
+                format!(
+                    "{} has both `{}` and `{}`, which cargo can only use one of",
+                    package.name, key, new
+                )
+            } else {
+                run.deprecations_present = true;
+                format!(
+                    "{}'s `{}` is deprecated in favor of `{}`",
+                    package.name, key, new
+                )
+            };
+            run.warnings
+                .push(WarningReport::new(message).set_package(Some(package.name.clone())));
+        }
+        let context = match PackageContext::new(args, package, manifest) {
+            Ok(context) => context,
+            Err(err) => {
+                args.ignore_error(&mut run.failures, &package.name, err)?;
+                continue;
+            }
+        };
+        let manifest_keys = manifest
+            .get_sections()
+            .into_iter()
+            .flat_map(|(_, table)| {
+                table
+                    .as_table_like()
+                    .into_iter()
+                    .flat_map(|table| table.iter().map(|(key, _)| key.to_owned()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<BTreeSet<_>>();
+        let mut deps = Vec::new();
+        for (section, dep_table) in manifest.get_dependency_sections_mut() {
+            let kind = section.kind();
+            if !kinds.is_empty() && !kinds.contains(&kind) {
+                continue;
+            }
+            if let Some((triple, cfgs)) = &target {
+                match section.matches_target(triple, cfgs) {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(err) => {
+                        args.ignore_error(&mut run.failures, &package.name, err)?;
+                        continue;
+                    }
+                }
+            }
+            for (dep_key, dep_item) in dep_table.iter_mut() {
+                check_interrupted()?;
+                let dep_key = dep_key.get();
+                let package_name = dep_item
+                    .get("package")
+                    .and_then(|p| p.as_str())
+                    .filter(|p| *p != dep_key)
+                    .map(|p| p.to_owned());
+                // Renamed dependencies can also be selected by their package, unless that is
+                // ambiguous with another dependency's key
+                let alias = package_name
+                    .as_deref()
+                    .filter(|p| !manifest_keys.contains(*p));
+                processed_keys.insert(normalize_crate_name(dep_key));
+                if let Some(alias) = alias {
+                    processed_keys.insert(normalize_crate_name(alias));
+                }
+                let selection = selected(dep_key).or_else(|| alias.and_then(selected));
+                if !selected_dependencies.is_empty() && selection.is_none() {
+                    args.verbose(|| {
+                        shell_warn(&format!("ignoring {}, excluded by user", dep_key))
+                    })?;
+                    continue;
+                }
+                let excluded = excludes.iter().any(|pattern| {
+                    // `my-cli:clap` only excludes clap from the `my-cli` package
+                    let pattern = match pattern.split_once(':') {
+                        Some((scope, pattern)) if crate_glob_match(scope, &package.name) => pattern,
+                        Some(_) => return false,
+                        None => pattern.as_str(),
+                    };
+                    crate_glob_match(pattern, dep_key)
+                        || package_name
+                            .as_deref()
+                            .map(|name| crate_glob_match(pattern, name))
+                            .unwrap_or(false)
+                }) || context.policy.ignores(dep_key, package_name.as_deref());
+                let planned = if excluded {
+                    args.verbose(|| {
+                        shell_warn(&format!("ignoring {}, excluded by user", dep_key))
+                    })?;
+                    if !args.update_lock_for_excluded {
+                        continue;
+                    }
+                    run.plan_excluded(&context, &section, dep_key, dep_item)
+                } else {
+                    run.plan_dependency(&context, &section, dep_key, dep_item, selection)
+                };
+                match planned {
+                    Ok(Some(dep)) => deps.push(dep),
+                    Ok(None) => {}
+                    Err(err) => args.ignore_error(&mut run.failures, dep_key, err)?,
+                }
+            }
+        }
+        if args.interactive {
+            pending.push((context, deps));
+        } else {
+            run.record(&mut transaction, &context, deps)?;
+        }
+    }
+
+    if args.interactive {
+        let mut planned = pending
+            .iter_mut()
+            .flat_map(|(context, deps)| {
+                let package = context.package.name.as_str();
+                deps.iter_mut()
+                    .filter(|dep| dep.change.is_some())
+                    .map(move |dep| (package, dep))
+            })
+            .collect::<Vec<_>>();
+        if !planned.is_empty() {
+            let keep = choose_upgrades(&planned)?;
+            for ((_, dep), _) in planned.iter_mut().zip(keep).filter(|(_, keep)| !keep) {
+                dep.decline();
+            }
+        }
+        for (context, deps) in pending {
+            run.record(&mut transaction, &context, deps)?;
+        }
+    }
+
+    if !run.grouped.is_empty() {
+        print_by_dependency(std::mem::take(&mut run.grouped), args.verbose)?;
+    }
+
+    for warning in &run.warnings {
+        shell_warn(&warning.message)?;
+    }
+
+    if args.remember {
+        if let Some(package) = manifests.first() {
+            let manifest_path = package.manifest_path.as_std_path();
+            let root =
+                find_workspace_root(manifest_path).unwrap_or_else(|_| manifest_path.to_owned());
+            remember_excludes(args, transaction.load(&root)?)?;
+        }
+    }
+
+    // Only write once every package was checked, so a failure or interrupt can't leave the
+    // workspace half-upgraded
+    if let Some(format) = args.dry_run {
+        transaction.report_dry_run(format.into())?;
+    } else if !args.dry_run() && !args.locked() {
+        for warning in transaction.future_keys_warnings() {
+            shell_warn(&warning)?;
+        }
+        if let Err(err) = timed(&mut run.timings.manifest_io, || transaction.commit()) {
+            args.ignore_error(&mut run.failures, "manifests", err)?;
+        }
+    }
+
+    if run.any_crate_modified {
+        if args.locked() {
+            let flag = if args.frozen { "--frozen" } else { "--locked" };
+            anyhow::bail!("cannot upgrade due to `{}`", flag);
+        } else if !args.dry_run() && !args.compatible_only() && !without_lockfile {
+            let updated = timed(&mut run.timings.metadata, || {
+                update_lockfile(&manifests, args.cargo_flags())
+            });
+            match updated {
+                Ok(()) if args.check_duplicates => {
+                    if let Err(err) = report_duplicates(&run.locked, &manifests) {
+                        args.ignore_error(&mut run.failures, "Cargo.lock", err)?;
+                    }
+                }
+                Ok(()) => {}
+                Err(err) => args.ignore_error(&mut run.failures, "Cargo.lock", err)?,
+            }
+        }
+    }
+
+    if !run.lock_updates.is_empty() && !args.dry_run() {
+        let updated = timed(&mut run.timings.metadata, || {
+            update_locked_excluded(&manifests, &run.lock_updates, args.cargo_flags())
+        });
+        if let Err(err) = updated {
+            args.ignore_error(&mut run.failures, "Cargo.lock", err)?;
+        }
+    }
+
+    // Across several workspaces, a dependency only has to exist in one of them
+    if !args.one_of_several {
+        check_selected_exist(selected_dependencies.keys(), processed_keys)?;
+    }
+
+    // Only worth breaking down when alternative registries are involved
+    if 1 < run.registry_lookups.len() + run.failed_registries.len() {
+        let lookups = run
+            .registry_lookups
+            .iter()
+            .map(|(registry, count)| format!("{} from `{}`", count, registry))
+            .collect::<Vec<_>>();
+        shell_note(&format!("looked up dependencies {}", lookups.join(", ")))?;
+    }
+
+    if args.timings {
+        run.timings.print()?;
+        run.renovate.timings = Some(run.timings.report());
+        run.report.timings = Some(run.timings.report());
+    }
+
+    let Run {
+        mut report,
+        renovate,
+        warnings,
+        root_dir,
+        majors,
+        mut failures,
+        failed_registries,
+        compatible_present,
+        pinned_present,
+        implicit_defaults_present,
+        deprecations_present,
+        tilde_present,
+        wildcard_present,
+        ..
+    } = run;
+    report.warnings = warnings;
+    match args.output_format {
+        OutputFormat::Human => {}
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Renovate => println!("{}", serde_json::to_string_pretty(&renovate)?),
+        OutputFormat::Html => print!("{}", html::render(&report, &root_dir)?),
+    }
+    if args.summary {
+        write_summary(&manifests, &report, &root_dir, args.cargo_flags())?;
+    }
+
+    // Hooks are free to write, so they don't run at all when read-only
+    if let Some(package) = manifests.first().filter(|_| !is_read_only()) {
+        let manifest_path = package.manifest_path.as_std_path();
+        let notify = NotifyHooks::for_manifest(manifest_path)?;
+        if !notify.is_empty() {
+            notify.notify(&report, manifest_path)?;
+        }
+    }
+
+    if args.check_majors {
+        for major in &majors {
+            println!("{}", major);
+        }
+        match majors.len() {
+            0 => shell_note("no dependencies have a new major version")?,
+            1 => anyhow::bail!("1 dependency has a new major version"),
+            n => anyhow::bail!("{} dependencies have a new major version", n),
+        }
+    }
+
+    if pinned_present && !args.check_majors {
         shell_note("Re-run with `--pinned` to upgrade pinned version requirements")?;
     }
+    if tilde_present {
+        shell_note("Re-run with `--pinned` to upgrade tilde requirements to new minor versions")?;
+    }
     if compatible_present {
         shell_note("Re-run with `--to-lockfile` to upgrade compatible version requirements")?;
     }
+    if wildcard_present {
+        shell_note(
+            "Re-run with `--fix-wildcards` to replace wildcard requirements with the locked or \
+            latest version",
+        )?;
+    }
+    if deprecations_present {
+        shell_note("Re-run with `--fix-deprecations` to rename deprecated keys")?;
+    }
+    if implicit_defaults_present {
+        shell_note(
+            "Re-run with `--explicit-default-features` to write `default-features = false` in \
+            the members",
+        )?;
+    }
 
-    if args.dry_run {
-        shell_warn("aborting upgrade due to dry run")?;
+    if args.published_crate.is_some() {
+        shell_note("published crates are only audited, no changes were written")?;
+    } else if args.read_only {
+        shell_note("running read-only, no changes were written")?;
     }
 
-    Ok(())
+    // Dependencies from other registries were still upgraded, but the run isn't a success
+    failures.extend(
+        failed_registries
+            .iter()
+            .map(|(registry, err)| format!("  registry `{}`: {:#}", registry, err)),
+    );
+    match failures.len() {
+        0 => Ok(()),
+        1 => anyhow::bail!("1 error was ignored:\n{}", failures.join("\n")),
+        n => anyhow::bail!("{} errors were ignored:\n{}", n, failures.join("\n")),
+    }
 }
 
-fn load_lockfile(
-    targets: &[cargo_metadata::Package],
-    locked: bool,
-    offline: bool,
-) -> CargoResult<Vec<cargo_metadata::Package>> {
+/// What a run of `upgrade` found so far, across the packages
+struct Run<'a> {
+    args: &'a UpgradeArgs,
+    strategy: Box<dyn VersionStrategy>,
+    locked: Lockfile,
+    req_style: Option<ReqStyle>,
+    defaults_off: BTreeSet<String>,
+    root_dir: PathBuf,
+    timings: Timings,
+    updated_registries: BTreeSet<String>,
+    any_crate_modified: bool,
+    compatible_present: bool,
+    pinned_present: bool,
+    implicit_defaults_present: bool,
+    deprecations_present: bool,
+    tilde_present: bool,
+    wildcard_present: bool,
+    failures: Vec<String>,
+    majors: Vec<String>,
+    /// Excluded crates to update in `Cargo.lock`, by name and locked version
+    lock_updates: BTreeSet<(String, String)>,
+    registry_lookups: BTreeMap<String, usize>,
+    failed_registries: BTreeMap<String, anyhow::Error>,
+    /// Reported once the run is over, where they can't scroll away among the tables
+    warnings: Vec<WarningReport>,
+    renovate: renovate::Report,
+    report: UpgradeReport,
+    /// Printed together once every package was checked, with `--group-by dep`
+    grouped: Vec<(String, Vec<Dep>)>,
+}
+
+/// What planning a package's dependencies needs to know about it
+struct PackageContext<'p> {
+    package: &'p cargo_metadata::Package,
+    manifest_path: PathBuf,
+    public_deps: Option<BTreeSet<String>>,
+    constraints: BTreeMap<String, VersionReq>,
+    policy: UpgradePolicy,
+    rust_version: Option<semver::Version>,
+}
+
+impl<'p> PackageContext<'p> {
+    fn new(
+        args: &UpgradeArgs,
+        package: &'p cargo_metadata::Package,
+        manifest: &LocalManifest,
+    ) -> CargoResult<Self> {
+        let rust_version = if args.respect_msrv {
+            package_rust_version(manifest)?
+        } else {
+            None
+        };
+        Ok(Self {
+            package,
+            manifest_path: manifest.path.clone(),
+            public_deps: public_deps(package),
+            constraints: constraints(package)?,
+            policy: upgrade_policy(package)?,
+            rust_version,
+        })
+    }
+}
+
+/// A dependency's row in the tables and reports, with the change to write for it
+struct PlannedDep {
+    section: DepTable,
+    dep_key: String,
+    /// Name of the crate in the registry
+    name: String,
+    change: Option<Change>,
+    /// Whether the package's dependents see it, which makes breaking changes theirs too
+    public: bool,
+    row: Dep,
+    report: DependencyReport,
+    renovate: Option<renovate::Dependency>,
+}
+
+impl PlannedDep {
+    /// Keep the old requirement, as the user turned the change down
+    fn decline(&mut self) {
+        self.change = None;
+        self.row.new_version_req = self.row.old_version_req.clone();
+        self.row.reason = Some(Reason::Declined);
+        self.report.new_req = self.report.old_req.clone();
+        self.report.reason = Some(ReportReason::Declined);
+        if let Some(renovate) = &mut self.renovate {
+            renovate.updates.clear();
+        }
+    }
+}
+
+/// What gets written to a dependency's entry
+enum Change {
+    Version(String),
+    GitRef {
+        key: &'static str,
+        latest: String,
+    },
+    /// Spell out the `default-features = false` a workspace dependency inherits
+    DefaultFeatures,
+}
+
+impl Run<'_> {
+    /// Plan an excluded dependency, which keeps its requirement while compatible fixes can
+    /// still be locked
+    fn plan_excluded(
+        &mut self,
+        context: &PackageContext<'_>,
+        section: &DepTable,
+        dep_key: &str,
+        dep_item: &toml_edit::Item,
+    ) -> CargoResult<Option<PlannedDep>> {
+        let args = self.args;
+        let manifest_path = &context.manifest_path;
+        let dependency = Dependency::from_toml(manifest_path, dep_key, dep_item)?;
+        let from_registry = dependency
+            .source
+            .as_ref()
+            .and_then(|s| s.as_registry())
+            .is_some();
+        let old_version_req = match dependency.version() {
+            Some(version_req) if from_registry => version_req.to_owned(),
+            _ => return Ok(None),
+        };
+        let locked_version = match find_locked_version(
+            context.package,
+            &dependency.name,
+            &old_version_req,
+            &self.locked,
+        ) {
+            Some(locked_version) => locked_version,
+            None => return Ok(None),
+        };
+        let registry_url = dependency
+            .registry()
+            .map(|registry| -> CargoResult<_> {
+                let registry_url = registry_url(manifest_path, Some(registry))?;
+                if self.updated_registries.insert(registry_url.clone()) {
+                    timed(&mut self.timings.index_update, || {
+                        args.update_index(&registry_url)
+                    })?;
+                }
+                Ok(registry_url)
+            })
+            .transpose()?;
+        let compatible = get_compatible_dependency(
+            &dependency.name,
+            &VersionReq::parse(&old_version_req)?,
+            manifest_path,
+            registry_url.as_ref(),
+        )?;
+        let compatible = without_build_metadata(
+            compatible
+                .version()
+                .expect("registry packages always have a version"),
+        );
+        if compatible.parse::<semver::Version>()? <= locked_version.parse::<semver::Version>()? {
+            return Ok(None);
+        }
+        self.lock_updates
+            .insert((dependency.name.clone(), locked_version.clone()));
+        Ok(Some(PlannedDep {
+            section: section.clone(),
+            dep_key: dep_key.to_owned(),
+            name: dependency.name.clone(),
+            change: None,
+            public: false,
+            report: DependencyReport::new(
+                dependency.toml_key(),
+                &old_version_req,
+                &old_version_req,
+            )
+            .set_locked(Some(locked_version.clone()))
+            .set_latest(Some(compatible.clone()))
+            .set_reason(Some(ReportReason::Excluded)),
+            row: Dep {
+                name: dependency.toml_key().to_owned(),
+                old_version_req: old_version_req.clone(),
+                locked_version: Some(locked_version),
+                latest_version: Some(compatible),
+                new_version_req: old_version_req,
+                reason: Some(Reason::Excluded),
+            },
+            renovate: None,
+        }))
+    }
+
+    /// Plan the new requirement of a dependency, without writing it yet
+    fn plan_dependency(
+        &mut self,
+        context: &PackageContext<'_>,
+        section: &DepTable,
+        dep_key: &str,
+        dep_item: &toml_edit::Item,
+        selection: Option<&Option<String>>,
+    ) -> CargoResult<Option<PlannedDep>> {
+        let args = self.args;
+        let package = context.package;
+        let manifest_path = &context.manifest_path;
+        let dependency = match Dependency::from_toml(manifest_path, dep_key, dep_item) {
+            Ok(dependency) => dependency,
+            Err(err) => {
+                self.warnings.push(
+                    WarningReport::new(format!("ignoring {}, unsupported entry: {}", dep_key, err))
+                        .set_package(Some(package.name.clone()))
+                        .set_dependency(Some(dep_key.to_owned())),
+                );
+                return Ok(None);
+            }
+        };
+        if dependency.source().and_then(|s| s.as_workspace()).is_some()
+            && self.defaults_off.contains(dep_key)
+            && dep_item.get("features").is_some()
+            && dep_item.get("default-features").is_none()
+            && dep_item.get("default_features").is_none()
+        {
+            if args.explicit_default_features {
+                let old_req = "workspace = true";
+                let new_req = "default-features = false";
+                return Ok(Some(PlannedDep {
+                    section: section.clone(),
+                    dep_key: dep_key.to_owned(),
+                    name: dependency.name.clone(),
+                    change: Some(Change::DefaultFeatures),
+                    public: false,
+                    report: DependencyReport::new(dependency.toml_key(), old_req, new_req),
+                    row: Dep {
+                        name: dependency.toml_key().to_owned(),
+                        old_version_req: old_req.to_owned(),
+                        locked_version: None,
+                        latest_version: None,
+                        new_version_req: new_req.to_owned(),
+                        reason: None,
+                    },
+                    renovate: None,
+                }));
+            } else {
+                self.warnings.push(
+                    WarningReport::new(format!(
+                        "{} adds features to {}, which can re-enable the default features \
+                        `[workspace.dependencies]` turns off",
+                        package.name, dep_key
+                    ))
+                    .set_package(Some(package.name.clone()))
+                    .set_dependency(Some(dep_key.to_owned())),
+                );
+                self.implicit_defaults_present = true;
+            }
+        }
+        let git = dependency
+            .source()
+            .and_then(|s| s.as_git())
+            .filter(|_| args.git_deps != GitDepsArg::Skip && !args.check_majors);
+        let git_ref = match git {
+            Some(_) if args.offline() => {
+                args.verbose(|| {
+                    shell_warn(&format!(
+                        "ignoring {}, git repositories can't be checked offline",
+                        dependency.toml_key()
+                    ))
+                })?;
+                return Ok(None);
+            }
+            Some(git) => match GitRef::lookup(args.git_deps, git) {
+                Ok(git_ref) => git_ref,
+                Err(err) => {
+                    self.warnings.push(
+                        WarningReport::new(format!(
+                            "not upgrading {}: {:#}",
+                            dependency.toml_key(),
+                            err
+                        ))
+                        .set_package(Some(package.name.clone()))
+                        .set_dependency(Some(dependency.toml_key().to_owned())),
+                    );
+                    return Ok(None);
+                }
+            },
+            None => None,
+        };
+        if let Some(git_ref) = git_ref {
+            let mut new_ref = git_ref.latest.clone();
+            let mut reason = None;
+            if git_ref.latest == git_ref.current {
+                reason = Some(Reason::Unchanged);
+            } else if !args.only.is_empty() && !args.only.contains(&git_ref.classify()) {
+                reason = Some(Reason::Filtered);
+                new_ref = git_ref.current.clone();
+            }
+            let change = (new_ref != git_ref.current).then(|| Change::GitRef {
+                key: git_ref.key,
+                latest: new_ref.clone(),
+            });
+            return Ok(Some(PlannedDep {
+                section: section.clone(),
+                dep_key: dep_key.to_owned(),
+                name: dependency.name.clone(),
+                change,
+                public: false,
+                report: DependencyReport::new(dependency.toml_key(), &git_ref.current, &new_ref)
+                    .set_latest(Some(git_ref.latest.clone()))
+                    .set_reason(reason.map(Reason::to_report)),
+                row: Dep {
+                    name: dependency.toml_key().to_owned(),
+                    old_version_req: git_ref.current,
+                    locked_version: None,
+                    latest_version: Some(git_ref.latest),
+                    new_version_req: new_ref,
+                    reason,
+                },
+                renovate: None,
+            }));
+        }
+        let old_version_req = match dependency.version() {
+            Some(version_req) => version_req.to_owned(),
+            None => {
+                args.verbose(|| {
+                    let source = dependency
+                        .source()
+                        .map(|s| s.to_string())
+                        .unwrap_or_else(|| "unknown".to_owned());
+                    shell_warn(&format!(
+                        "ignoring {}, source is {}",
+                        dependency.toml_key(),
+                        source,
+                    ))
+                })?;
+                return Ok(None);
+            }
+        };
+
+        // crates.io rejects these on publish
+        let is_wildcard = is_wildcard_req(&old_version_req);
+        if is_wildcard && !args.fix_wildcards {
+            self.warnings.push(
+                WarningReport::new(format!(
+                    "{} in {} has the wildcard requirement `{}`, which crates.io rejects on \
+                    publish",
+                    dependency.toml_key(),
+                    package.name,
+                    old_version_req
+                ))
+                .set_package(Some(package.name.clone()))
+                .set_dependency(Some(dependency.toml_key().to_owned())),
+            );
+            self.wildcard_present = true;
+        }
+        let fix_wildcard = is_wildcard && args.fix_wildcards;
+
+        let mut reason = None;
+        // Fixed wildcards are replaced whether or not they're pinned
+        if !fix_wildcard {
+            let upgrades_renamed = args
+                .pinned_levels(&package.name)
+                .any(|level| level == PinnedArg::All);
+            let held_as_pinned = dependency.rename.is_some()
+                || context
+                    .policy
+                    .pins(dependency.toml_key(), Some(&dependency.name));
+            if held_as_pinned && !upgrades_renamed {
+                reason.get_or_insert(Reason::Pinned);
+                self.pinned_present = true;
+            }
+            let held_by_pin = if is_exact_req(&old_version_req) {
+                !args.upgrades_exact(&package.name)
+            } else {
+                is_pinned_req(&old_version_req) && !args.upgrades_ranges(&package.name)
+            };
+            if held_by_pin {
+                reason.get_or_insert(Reason::Pinned);
+                self.pinned_present = true;
+            }
+        }
+
+        let locked_version =
+            find_locked_version(package, &dependency.name, &old_version_req, &self.locked);
+
+        let constraint = context
+            .constraints
+            .get(dependency.toml_key())
+            .or_else(|| context.constraints.get(&dependency.name));
+        let from_registry = dependency
+            .source
+            .as_ref()
+            .and_then(|s| s.as_registry())
+            .is_some();
+        let (latest_version, allowed_version, msrv_version) = if from_registry {
+            let registry_name = dependency.registry().unwrap_or(CRATES_IO);
+            if self.failed_registries.contains_key(registry_name) {
+                args.verbose(|| {
+                    shell_warn(&format!(
+                        "ignoring {}, registry `{}` is unavailable",
+                        dependency.toml_key(),
+                        registry_name
+                    ))
+                })?;
+                return Ok(None);
+            }
+            // Update indices for any alternative registries, unless
+            // we're offline or using the cached indices. A broken
+            // alternative registry only skips the dependencies from it.
+            let registry_url = dependency
+                .registry()
+                .map(|registry| -> CargoResult<_> {
+                    let registry_url = registry_url(manifest_path, Some(registry))?;
+                    if self.updated_registries.insert(registry_url.clone()) {
+                        timed(&mut self.timings.index_update, || {
+                            args.update_index(&registry_url)
+                        })?;
+                    }
+                    Ok(registry_url)
+                })
+                .transpose();
+            let registry_url = match registry_url {
+                Ok(registry_url) => registry_url,
+                Err(err) => {
+                    self.warnings.push(WarningReport::new(format!(
+                        "skipping dependencies from registry `{}`: {:#}",
+                        registry_name, err
+                    )));
+                    self.failed_registries.insert(registry_name.to_owned(), err);
+                    return Ok(None);
+                }
+            };
+            if let Some(registry_url) = &registry_url {
+                args.verbose(|| {
+                    shell_status(
+                        "Querying",
+                        &format!(
+                            "{} from registry `{}` ({})",
+                            dependency.toml_key(),
+                            registry_name,
+                            registry_url
+                        ),
+                    )
+                })?;
+            }
+            *self
+                .registry_lookups
+                .entry(registry_name.to_owned())
+                .or_default() += 1;
+            let lookup_started = Instant::now();
+            let is_prerelease = old_version_req.contains('-');
+            let latest_version = VersionReq::parse(&old_version_req)
+                .map_err(Into::into)
+                .and_then(|version_req| {
+                    self.strategy.select(&VersionQuery {
+                        crate_name: &dependency.name,
+                        version_req: &version_req,
+                        allow_prerelease: is_prerelease,
+                        manifest_path,
+                        registry: registry_url.as_ref(),
+                    })
+                })
+                .map(|d| {
+                    d.version()
+                        .expect("registry packages always have a version")
+                        .to_owned()
+                });
+            let latest_version = match latest_version {
+                Ok(latest_version) => Some(latest_version),
+                Err(err) => {
+                    self.warnings.push(
+                        WarningReport::new(format!(
+                            "not upgrading {}: {:#}",
+                            dependency.toml_key(),
+                            err
+                        ))
+                        .set_package(Some(package.name.clone()))
+                        .set_dependency(Some(dependency.toml_key().to_owned())),
+                    );
+                    None
+                }
+            };
+            // The newest version the constraint allows, when it rules out the latest
+            let allowed_version = match (constraint, &latest_version) {
+                (Some(constraint), Some(latest)) if !constraint.matches(&latest.parse()?) => {
+                    let version_req = VersionReq::parse(&old_version_req)?;
+                    let query = VersionQuery {
+                        crate_name: &dependency.name,
+                        version_req: &version_req,
+                        allow_prerelease: is_prerelease,
+                        manifest_path,
+                        registry: registry_url.as_ref(),
+                    };
+                    let allowed = self
+                        .strategy
+                        .select_within(&query, constraint)
+                        .ok()
+                        .map(|d| {
+                            d.version()
+                                .expect("registry packages always have a version")
+                                .to_owned()
+                        });
+                    Some(allowed)
+                }
+                _ => None,
+            };
+            // The newest version the package's `rust-version` builds, when the one
+            // picked needs a newer Rust
+            let picked = allowed_version
+                .clone()
+                .unwrap_or_else(|| latest_version.clone());
+            let msrv_version = match (&context.rust_version, picked) {
+                (Some(rust_version), Some(picked)) => {
+                    let needs = get_rust_version(
+                        &dependency.name,
+                        &picked.parse()?,
+                        manifest_path,
+                        registry_url.as_ref(),
+                    )?;
+                    if needs.map_or(false, |needs| needs > *rust_version) {
+                        let bound = match constraint {
+                            Some(constraint) => Some(constraint.clone()),
+                            None if args.compatible_only() => {
+                                Some(VersionReq::parse(&old_version_req)?)
+                            }
+                            None => None,
+                        };
+                        let supported = get_dependency_for_rust_version(
+                            &dependency.name,
+                            bound.as_ref(),
+                            is_prerelease,
+                            rust_version,
+                            manifest_path,
+                            registry_url.as_ref(),
+                        )
+                        .ok()
+                        .map(|d| {
+                            d.version()
+                                .expect("registry packages always have a version")
+                                .to_owned()
+                        });
+                        Some(supported)
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            };
+            *self
+                .timings
+                .lookups
+                .entry(registry_name.to_owned())
+                .or_default() += lookup_started.elapsed();
+            (latest_version, allowed_version, msrv_version)
+        } else {
+            (None, None, None)
+        };
+
+        // Newer minors are out of a tilde requirement's range, and it already matches
+        // newer patches
+        let tilde_held_back = !args.upgrades_ranges(&package.name)
+            && is_tilde_req(&old_version_req)
+            && latest_version.as_deref().map_or(false, |latest| {
+                !old_version_compatible(&old_version_req, latest)
+            });
+
+        if args.check_majors {
+            // Compatible drift is left to regular upgrades
+            if let Some(latest_version) = &latest_version {
+                if !old_version_compatible(&old_version_req, latest_version) {
+                    self.majors.push(format!(
+                        "{} {} -> {}",
+                        dependency.toml_key(),
+                        old_version_req,
+                        latest_version
+                    ));
+                }
+            }
+            return Ok(None);
+        }
+
+        let new_version_req = if fix_wildcard {
+            locked_version
+                .as_ref()
+                .or(latest_version.as_ref())
+                .map(|version| without_build_metadata(version))
+                .unwrap_or_else(|| old_version_req.clone())
+        } else if reason.is_some() {
+            old_version_req.clone()
+        } else if let Some(Some(new_version_req)) = selection {
+            new_version_req.to_owned()
+        } else {
+            let new_version_req = if args.to_lockfile {
+                if let Some(locked_version) = &locked_version {
+                    let new_version_req = locked_version.clone();
+                    let new_version: semver::Version = locked_version.parse()?;
+                    match cargo_edit::upgrade_requirement(&old_version_req, &new_version) {
+                        Ok(Some(version_req)) => Some(version_req),
+                        Err(_) => Some(new_version_req),
+                        _ => None,
+                    }
+                } else {
+                    None
+                }
+            } else if tilde_held_back {
+                None
+            } else if let Some(latest_version) = msrv_version
+                .as_ref()
+                .or(allowed_version.as_ref())
+                .unwrap_or(&latest_version)
+            {
+                let mut new_version_req = without_build_metadata(latest_version);
+                let new_version: semver::Version = latest_version.parse()?;
+                match cargo_edit::upgrade_requirement(&old_version_req, &new_version) {
+                    Ok(Some(version_req)) => {
+                        new_version_req = version_req;
+                    }
+                    Err(_) => {}
+                    _ => {
+                        new_version_req = old_version_req.clone();
+                    }
+                }
+                if new_version_req == old_version_req {
+                    None
+                } else if !args.compatible_only()
+                    && !args.compatible
+                    && old_version_compatible(&old_version_req, latest_version)
+                {
+                    reason.get_or_insert(Reason::Compatible);
+                    self.compatible_present = true;
+                    None
+                } else {
+                    Some(new_version_req)
+                }
+            } else {
+                None
+            };
+            new_version_req.unwrap_or_else(|| old_version_req.clone())
+        };
+        if tilde_held_back && new_version_req == old_version_req {
+            reason.get_or_insert(Reason::TildeConstrained);
+            self.tilde_present = true;
+        }
+        if msrv_version.is_some() {
+            reason.get_or_insert(Reason::MsrvIncompatible);
+        }
+        if allowed_version.is_some() {
+            reason.get_or_insert(Reason::Constrained);
+        }
+        let mut new_version_req = match self.req_style {
+            Some(style) => {
+                normalize_requirement(&new_version_req, style)?.unwrap_or(new_version_req)
+            }
+            None => new_version_req,
+        };
+        if !args.only.is_empty() && new_version_req != old_version_req {
+            let pinned = dependency.rename.is_some()
+                || is_exact_req(&old_version_req)
+                || is_pinned_req(&old_version_req);
+            let kind = OnlyArg::classify(&old_version_req, &new_version_req, pinned);
+            if !args.only.contains(&kind) {
+                reason = Some(Reason::Filtered);
+                new_version_req = old_version_req.clone();
+            }
+        }
+        if new_version_req == old_version_req {
+            reason.get_or_insert(Reason::Unchanged);
+        }
+        let public = context.public_deps.as_ref().map_or(false, |public_deps| {
+            public_deps.contains(dependency.toml_key())
+                || dep_item
+                    .get("public")
+                    .and_then(|p| p.as_bool())
+                    .unwrap_or(false)
+        });
+        let renovate = from_registry.then(|| {
+            let mut dep = renovate::Dependency::new(
+                dependency.toml_key(),
+                &dependency.name,
+                section,
+                &old_version_req,
+            );
+            dep.locked_version = locked_version.clone();
+            if new_version_req != old_version_req {
+                // The newest version that the new requirement picks
+                let new_version = VersionReq::parse(&new_version_req)
+                    .ok()
+                    .and_then(|req| {
+                        [
+                            latest_version.as_ref(),
+                            allowed_version.as_ref().and_then(|v| v.as_ref()),
+                            locked_version.as_ref(),
+                        ]
+                        .into_iter()
+                        .flatten()
+                        .find(|v| v.parse().map_or(false, |v| req.matches(&v)))
+                    })
+                    .map(|v| without_build_metadata(v));
+                dep.updates.push(renovate::Update {
+                    update_type: renovate::UpdateType::new(
+                        is_breaking_change(&old_version_req, &new_version_req),
+                        locked_version.as_deref(),
+                        new_version.as_deref(),
+                    ),
+                    new_value: new_version_req.clone(),
+                    new_version,
+                });
+            }
+            dep
+        });
+        let change =
+            (new_version_req != old_version_req).then(|| Change::Version(new_version_req.clone()));
+        Ok(Some(PlannedDep {
+            section: section.clone(),
+            dep_key: dep_key.to_owned(),
+            name: dependency.name.clone(),
+            change,
+            public,
+            report: DependencyReport::new(
+                dependency.toml_key(),
+                &old_version_req,
+                &new_version_req,
+            )
+            .set_locked(locked_version.clone())
+            .set_latest(latest_version.clone())
+            .set_reason(reason.map(Reason::to_report)),
+            row: Dep {
+                name: dependency.toml_key().to_owned(),
+                old_version_req,
+                locked_version,
+                latest_version,
+                new_version_req,
+                reason,
+            },
+            renovate,
+        }))
+    }
+
+    /// Write the changes planned for a package, and add its dependencies to the tables and
+    /// reports
+    fn record(
+        &mut self,
+        transaction: &mut ManifestTransaction,
+        context: &PackageContext<'_>,
+        deps: Vec<PlannedDep>,
+    ) -> CargoResult<()> {
+        let package = context.package;
+        let manifest = transaction.load(&context.manifest_path)?;
+        let mut table = Vec::new();
+        let mut upgraded = Vec::new();
+        let mut package_file = renovate::PackageFile {
+            package_file: package_file(&self.root_dir, &context.manifest_path),
+            deps: Vec::new(),
+        };
+        let mut package_report = PackageReport::new(&package.name, &context.manifest_path);
+        for dep in deps {
+            if let Some(change) = &dep.change {
+                let dep_item = find_dep_item(manifest, &dep.section, &dep.dep_key)
+                    .with_context(|| format!("{} is missing from {}", dep.dep_key, package.name))?;
+                match change {
+                    Change::Version(new_version_req) => {
+                        set_dep_version(dep_item, new_version_req)?;
+                        upgraded.push((dep.name.clone(), new_version_req.clone()));
+                    }
+                    Change::GitRef { key, latest } => set_dep_git_ref(dep_item, key, latest)?,
+                    Change::DefaultFeatures => {
+                        shell_status(
+                            "Updating",
+                            &format!(
+                                "{} in {} to spell out `default-features = false`",
+                                dep.dep_key, package.name
+                            ),
+                        )?;
+                        if let Some(table) = dep_item.as_table_like_mut() {
+                            table.insert("default-features", toml_edit::value(false));
+                        }
+                    }
+                }
+                self.any_crate_modified = true;
+                if dep.public
+                    && is_breaking_change(&dep.row.old_version_req, &dep.row.new_version_req)
+                {
+                    self.warnings.push(
+                        WarningReport::new(format!(
+                            "{} is a public dependency of {}, upgrading it from {} to {} is a \
+                            breaking change for its dependents",
+                            dep.row.name,
+                            package.name,
+                            dep.row.old_version_req,
+                            dep.row.new_version_req
+                        ))
+                        .set_package(Some(package.name.clone()))
+                        .set_dependency(Some(dep.row.name.clone())),
+                    );
+                }
+            }
+            package_file.deps.extend(dep.renovate);
+            package_report.dependencies.push(dep.report);
+            table.push(dep.row);
+        }
+        self.warnings
+            .extend(legacy_warnings(manifest, &package.name, &upgraded));
+        if !table.is_empty() && self.args.output_format != OutputFormat::Json {
+            match self.args.group_by {
+                GroupBy::Package => print_upgrade(table, self.args.verbose)?,
+                GroupBy::Dep => self.grouped.push((package.name.clone(), table)),
+            }
+        }
+        if !package_file.deps.is_empty() {
+            self.renovate.cargo.push(package_file);
+        }
+        self.report.packages.push(package_report);
+        Ok(())
+    }
+}
+
+/// Where the time of a run went, for `--timings`
+#[derive(Debug)]
+struct Timings {
+    started: Instant,
+    index_update: Duration,
+    metadata: Duration,
+    lookups: BTreeMap<String, Duration>,
+    manifest_io: Duration,
+}
+
+impl Timings {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            index_update: Duration::ZERO,
+            metadata: Duration::ZERO,
+            lookups: BTreeMap::new(),
+            manifest_io: Duration::ZERO,
+        }
+    }
+
+    fn report(&self) -> TimingsReport {
+        let ms = |duration: &Duration| duration.as_millis() as u64;
+        let mut report = TimingsReport::default();
+        report.index_update_ms = ms(&self.index_update);
+        report.metadata_ms = ms(&self.metadata);
+        report.lookups_ms = self
+            .lookups
+            .iter()
+            .map(|(registry, duration)| (registry.clone(), ms(duration)))
+            .collect();
+        report.manifest_io_ms = ms(&self.manifest_io);
+        report.total_ms = ms(&self.started.elapsed());
+        report
+    }
+
+    fn print(&self) -> CargoResult<()> {
+        let lookups = self.lookups.values().sum::<Duration>();
+        let mut lookups = format!("{:.2}s", lookups.as_secs_f64());
+        if 1 < self.lookups.len() {
+            let registries = self
+                .lookups
+                .iter()
+                .map(|(registry, duration)| {
+                    format!("`{}` {:.2}s", registry, duration.as_secs_f64())
+                })
+                .collect::<Vec<_>>();
+            lookups.push_str(&format!(" ({})", registries.join(", ")));
+        }
+        shell_note(&format!(
+            "timings\n  index update  {:.2}s\n  metadata      {:.2}s\n  lookups       {}\n  \
+            manifest IO   {:.2}s\n  total         {:.2}s",
+            self.index_update.as_secs_f64(),
+            self.metadata.as_secs_f64(),
+            lookups,
+            self.manifest_io.as_secs_f64(),
+            self.started.elapsed().as_secs_f64(),
+        ))
+    }
+}
+
+/// Run `f`, adding the time it took to `total`
+fn timed<T>(total: &mut Duration, f: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = f();
+    *total += started.elapsed();
+    result
+}
+
+fn load_lockfile(targets: &[cargo_metadata::Package], flags: CargoFlags) -> CargoResult<Lockfile> {
     // Get locked dependencies. For workspaces with multiple Cargo.toml
-    // files, there is only a single lockfile, so it suffices to get
-    // metadata for any one of Cargo.toml files.
+    // files, there is only a single lockfile, so it suffices to look it
+    // up for any one of Cargo.toml files.
+    let package = targets
+        .get(0)
+        .ok_or_else(|| anyhow::format_err!("Invalid cargo config"))?;
+    let manifest_path = package.manifest_path.as_std_path();
+    let lockfile_path = lockfile_path(package);
+    // Spawning cargo dominates the runtime, so only have it refresh a lockfile the manifests
+    // changed since
+    if !lockfile_is_stale(&lockfile_path, targets) {
+        if let Ok(lockfile) = Lockfile::find(manifest_path) {
+            return Ok(lockfile);
+        }
+    }
+    match update_lockfile(targets, flags) {
+        Ok(()) => Lockfile::find(manifest_path),
+        Err(err) => match Lockfile::find(manifest_path) {
+            // Workspaces cargo can't resolve, or a lockfile `--locked` keeps as it is, are still
+            // read as they were last locked
+            Ok(lockfile) => {
+                shell_warn(&format!(
+                    "cargo couldn't update {}, reading it as it is: {:#}",
+                    lockfile_path.display(),
+                    err
+                ))?;
+                Ok(lockfile)
+            }
+            Err(_) => Err(err),
+        },
+    }
+}
+
+/// Whether `Cargo.lock` is missing, or older than the workspace root's or any target's manifest
+fn lockfile_is_stale(lockfile_path: &Path, targets: &[cargo_metadata::Package]) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let locked = match modified(lockfile_path) {
+        Some(locked) => locked,
+        None => return true,
+    };
+    let root_manifest = lockfile_path.with_file_name("Cargo.toml");
+    std::iter::once(root_manifest.as_path())
+        .chain(targets.iter().map(|p| p.manifest_path.as_std_path()))
+        .filter(|path| path.is_file())
+        .any(|path| modified(path).map_or(true, |m| m > locked))
+}
+
+fn update_lockfile(targets: &[cargo_metadata::Package], flags: CargoFlags) -> CargoResult<()> {
+    // Resolving the workspace is enough for cargo to update the lockfile; features don't affect
+    // its contents.
     let package = targets
         .get(0)
         .ok_or_else(|| anyhow::format_err!("Invalid cargo config"))?;
+    ensure_writable(&lockfile_path(package))?;
     let mut cmd = cargo_metadata::MetadataCommand::new();
     cmd.manifest_path(package.manifest_path.clone());
-    cmd.features(cargo_metadata::CargoOpt::AllFeatures);
-    let mut other = Vec::new();
-    if locked {
-        other.push("--locked".to_owned());
+    flags.apply(&mut cmd);
+    cmd.exec()?;
+    Ok(())
+}
+
+/// Where `cargo` keeps the `Cargo.lock` of the workspace `package` is in, whether or not it exists
+/// yet
+fn lockfile_path(package: &cargo_metadata::Package) -> PathBuf {
+    let manifest_path = package.manifest_path.as_std_path();
+    let root = find_workspace_root(manifest_path).unwrap_or_else(|_| manifest_path.to_owned());
+    root.with_file_name("Cargo.lock")
+}
+
+/// Write `report` into the target directory of the workspace `targets` are in, as JSON and Markdown
+fn write_summary(
+    targets: &[cargo_metadata::Package],
+    report: &UpgradeReport,
+    root_dir: &Path,
+    flags: CargoFlags,
+) -> CargoResult<()> {
+    let package = match targets.first() {
+        Some(package) => package,
+        None => return Ok(()),
+    };
+    // Only `cargo` knows about `CARGO_TARGET_DIR` and `build.target-dir` in its config
+    let mut cmd = cargo_metadata::MetadataCommand::new();
+    cmd.no_deps();
+    cmd.manifest_path(package.manifest_path.clone());
+    flags.apply(&mut cmd);
+    let target_dir = cmd.exec()?.target_directory.into_std_path_buf();
+    std::fs::create_dir_all(&target_dir)
+        .with_context(|| format!("Failed to create {}", target_dir.display()))?;
+    let json_path = target_dir.join("cargo-upgrade-report.json");
+    let markdown_path = target_dir.join("cargo-upgrade-report.md");
+    for (path, contents) in [
+        (&json_path, serde_json::to_string_pretty(report)? + "\n"),
+        (&markdown_path, summary::render(report, root_dir)?),
+    ] {
+        ensure_writable(path)?;
+        std::fs::write(path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
     }
-    if offline {
-        other.push("--offline".to_owned());
+    shell_status(
+        "Summarized",
+        &format!("upgrades in {}", markdown_path.display()),
+    )?;
+    Ok(())
+}
+
+/// Let the user toggle which of the `planned` changes to write, returning whether each is kept
+///
+/// The checklist is reprinted after each line of input, until an empty line (or the end of stdin)
+/// confirms it.
+fn choose_upgrades(planned: &[(&str, &mut PlannedDep)]) -> CargoResult<Vec<bool>> {
+    let mut keep = vec![true; planned.len()];
+    let width = planned.len().to_string().len();
+    let stdin = std::io::stdin();
+    loop {
+        shell_status(
+            "Selecting",
+            "upgrades to write by number (`a` for all, `n` for none, Enter to confirm)",
+        )?;
+        for (i, ((package, dep), keep)) in planned.iter().zip(&keep).enumerate() {
+            shell_write_stderr(
+                &format!(
+                    "  [{}] {:>width$} {}: {} {} -> {}\n",
+                    if *keep { 'x' } else { ' ' },
+                    i + 1,
+                    package,
+                    dep.row.name,
+                    dep.row.old_version_req,
+                    dep.row.new_version_req,
+                    width = width
+                ),
+                &ColorSpec::new(),
+            )?;
+        }
+        shell_write_stderr("> ", &ColorSpec::new())?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        for token in line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|t| !t.is_empty())
+        {
+            match token {
+                "a" => keep.iter_mut().for_each(|k| *k = true),
+                "n" => keep.iter_mut().for_each(|k| *k = false),
+                _ => match token.parse::<usize>() {
+                    Ok(n) if (1..=keep.len()).contains(&n) => keep[n - 1] = !keep[n - 1],
+                    _ => shell_warn(&format!("ignoring `{}`, not an upgrade's number", token))?,
+                },
+            }
+        }
     }
-    cmd.other_options(other);
+    Ok(keep)
+}
 
-    let result = cmd.exec()?;
+/// The entry of `dep_key` in the manifest's `section`
+fn find_dep_item<'m>(
+    manifest: &'m mut LocalManifest,
+    section: &DepTable,
+    dep_key: &str,
+) -> Option<&'m mut toml_edit::Item> {
+    manifest
+        .get_dependency_sections_mut()
+        .find(|(s, _)| s == section)
+        .and_then(|(_, table)| table.get_mut(dep_key))
+}
 
-    let locked = result.packages;
+/// Update the excluded crates in `Cargo.lock` to the newest versions their requirements allow,
+/// like `cargo update -p`
+fn update_locked_excluded(
+    targets: &[cargo_metadata::Package],
+    updates: &BTreeSet<(String, String)>,
+    flags: CargoFlags,
+) -> CargoResult<()> {
+    let package = targets
+        .get(0)
+        .ok_or_else(|| anyhow::format_err!("Invalid cargo config"))?;
+    ensure_writable(&lockfile_path(package))?;
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let mut cmd = std::process::Command::new(cargo);
+    cmd.arg("update")
+        .arg("--manifest-path")
+        .arg(package.manifest_path.as_std_path());
+    for (name, version) in updates {
+        cmd.arg("--package").arg(format!("{}:{}", name, version));
+    }
+    if flags.frozen {
+        cmd.arg("--frozen");
+    } else {
+        if flags.locked {
+            cmd.arg("--locked");
+        }
+        if flags.offline {
+            cmd.arg("--offline");
+        }
+    }
+    let status = cmd.status().context("Failed to run `cargo update`")?;
+    if !status.success() {
+        anyhow::bail!("`cargo update` failed for the excluded crates");
+    }
+    Ok(())
+}
 
-    Ok(locked)
+/// Warn about crates locked more often than `before` the upgrade, and what uses each version
+fn report_duplicates(before: &Lockfile, targets: &[cargo_metadata::Package]) -> CargoResult<()> {
+    let after = match targets.first() {
+        Some(package) => Lockfile::find(package.manifest_path.as_std_path())?,
+        None => return Ok(()),
+    };
+    let before = before.duplicates();
+    for (name, packages) in after.duplicates() {
+        if packages.len() <= before.get(name).map_or(1, |packages| packages.len()) {
+            continue;
+        }
+        let versions = packages
+            .iter()
+            .map(|package| {
+                let mut version = package.version.clone();
+                version.build = semver::BuildMetadata::EMPTY;
+                let dependents = after
+                    .dependents(package)
+                    .map(|dependent| dependent.name.as_str())
+                    .collect::<Vec<_>>();
+                format!("{} (used by {})", version, dependents.join(", "))
+            })
+            .collect::<Vec<_>>();
+        shell_warn(&format!(
+            "upgrading left {} versions of {}: {}",
+            packages.len(),
+            name,
+            versions.join(", ")
+        ))?;
+    }
+    Ok(())
 }
 
+/// The version of the dependency `package` is locked to, for the "locked" column
 fn find_locked_version(
+    package: &cargo_metadata::Package,
     dep_name: &str,
     old_version: &str,
-    locked: &[cargo_metadata::Package],
+    locked: &Lockfile,
 ) -> Option<String> {
     let req = semver::VersionReq::parse(old_version).ok()?;
-    for p in locked {
-        if dep_name == p.name && req.matches(&p.version) {
-            let mut v = p.version.clone();
-            v.build = semver::BuildMetadata::EMPTY;
-            return Some(v.to_string());
-        }
-    }
-    None
+    let p = locked.find_used_version(&package.name, &package.version, dep_name, &req)?;
+    let mut v = p.version.clone();
+    v.build = semver::BuildMetadata::EMPTY;
+    Some(v.to_string())
 }
 
 fn old_version_compatible(old_version_req: &str, new_version: &str) -> bool {
@@ -414,6 +2643,211 @@ fn old_version_compatible(old_version_req: &str, new_version: &str) -> bool {
     old_version_req.matches(&new_version)
 }
 
+/// Dependencies a published library exposes in its API, or `None` for other packages
+///
+/// These are the dependencies listed in `public-deps` under `[package.metadata.cargo-upgrade]`, in
+/// addition to any marked `public = true`.
+fn public_deps(package: &cargo_metadata::Package) -> Option<BTreeSet<String>> {
+    let is_lib = package.targets.iter().any(|target| {
+        target
+            .kind
+            .iter()
+            .any(|kind| matches!(kind.as_str(), "lib" | "rlib" | "dylib"))
+    });
+    // `publish = false` shows up as an empty list of registries
+    let is_published = package
+        .publish
+        .as_ref()
+        .map(|registries| !registries.is_empty())
+        .unwrap_or(true);
+    if !is_lib || !is_published {
+        return None;
+    }
+    let public_deps = package
+        .metadata
+        .pointer("/cargo-upgrade/public-deps")
+        .and_then(|deps| deps.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|dep| dep.as_str())
+        .map(|dep| dep.to_owned())
+        .collect();
+    Some(public_deps)
+}
+
+/// Upper bounds on dependencies' upgrades, from `[package.metadata.cargo-edit.constraints]`
+fn constraints(package: &cargo_metadata::Package) -> CargoResult<BTreeMap<String, VersionReq>> {
+    let config = match package.metadata.pointer("/cargo-edit/constraints") {
+        Some(config) => config
+            .as_object()
+            .with_context(|| "`constraints` must be a table")?,
+        None => return Ok(BTreeMap::new()),
+    };
+    config
+        .iter()
+        .map(|(name, constraint)| {
+            let constraint = constraint
+                .as_str()
+                .with_context(|| format!("`constraints.{}` must be a version requirement", name))?;
+            let constraint = VersionReq::parse(constraint)
+                .with_context(|| format!("Invalid `constraints.{}`", name))?;
+            Ok((name.clone(), constraint))
+        })
+        .collect()
+}
+
+/// Per-package upgrade policy, from `ignore` and `pin` under `[package.metadata.cargo-upgrade]`
+struct UpgradePolicy {
+    /// Dependencies never to upgrade, like `--exclude`
+    ignore: Vec<String>,
+    /// Dependencies to hold like pinned requirements, unless upgrading with `--pinned all`
+    pin: Vec<String>,
+}
+
+impl UpgradePolicy {
+    fn ignores(&self, dep_key: &str, package_name: Option<&str>) -> bool {
+        Self::matches(&self.ignore, dep_key, package_name)
+    }
+
+    fn pins(&self, dep_key: &str, package_name: Option<&str>) -> bool {
+        Self::matches(&self.pin, dep_key, package_name)
+    }
+
+    fn matches(patterns: &[String], dep_key: &str, package_name: Option<&str>) -> bool {
+        patterns.iter().any(|pattern| {
+            crate_glob_match(pattern, dep_key)
+                || package_name
+                    .map(|name| crate_glob_match(pattern, name))
+                    .unwrap_or(false)
+        })
+    }
+}
+
+fn upgrade_policy(package: &cargo_metadata::Package) -> CargoResult<UpgradePolicy> {
+    let list = |key: &str| -> CargoResult<Vec<String>> {
+        let config = match package.metadata.pointer(&format!("/cargo-upgrade/{}", key)) {
+            Some(config) => config,
+            None => return Ok(Vec::new()),
+        };
+        config
+            .as_array()
+            .and_then(|patterns| {
+                patterns
+                    .iter()
+                    .map(|pattern| pattern.as_str().map(|p| p.to_owned()))
+                    .collect()
+            })
+            .with_context(|| format!("`{}` must be an array of crate names", key))
+    };
+    Ok(UpgradePolicy {
+        ignore: list("ignore")?,
+        pin: list("pin")?,
+    })
+}
+
+/// Point out constructs from old manifests that upgrading skips or that no longer work together
+/// with the `upgraded` dependencies' new requirements
+fn legacy_warnings(
+    manifest: &LocalManifest,
+    package: &str,
+    upgraded: &[(String, String)],
+) -> Vec<WarningReport> {
+    let mut warnings = Vec::new();
+    let mut warn = |message: String| {
+        warnings.push(WarningReport::new(message).set_package(Some(package.to_owned())));
+    };
+
+    if manifest.package_key() == "project" {
+        warn(format!(
+            "{} uses `[project]`, which Cargo now calls `[package]`",
+            package
+        ));
+    }
+
+    let targets = manifest
+        .data
+        .get("target")
+        .and_then(|t| t.as_table_like())
+        .into_iter()
+        .flat_map(|t| t.iter())
+        .map(|(target, table)| (Some(target), table));
+    for (target, table) in std::iter::once((None, manifest.data.as_item())).chain(targets) {
+        for (legacy, current) in [
+            ("dev_dependencies", "dev-dependencies"),
+            ("build_dependencies", "build-dependencies"),
+        ] {
+            if table.get(legacy).is_none() {
+                continue;
+            }
+            let (legacy, current) = match target {
+                Some(target) => (
+                    format!("target.{}.{}", target, legacy),
+                    format!("target.{}.{}", target, current),
+                ),
+                None => (legacy.to_owned(), current.to_owned()),
+            };
+            warn(format!(
+                "{}'s `[{}]` weren't upgraded, rename them to `[{}]`",
+                package, legacy, current
+            ));
+        }
+    }
+
+    let replaced = manifest
+        .data
+        .get("replace")
+        .and_then(|r| r.as_table_like())
+        .into_iter()
+        .flat_map(|r| r.iter());
+    for (spec, _) in replaced {
+        // Specs look like `name:version`, possibly after a source URL and `#`
+        let (name, version) = match spec.rsplit('#').next().and_then(|s| s.split_once(':')) {
+            Some((name, version)) => (name, version),
+            None => continue,
+        };
+        let version = match semver::Version::parse(version) {
+            Ok(version) => version,
+            Err(_) => continue,
+        };
+        for (dep, new_version_req) in upgraded.iter().filter(|(dep, _)| dep == name) {
+            let still_matches = VersionReq::parse(new_version_req)
+                .map(|req| req.matches(&version))
+                .unwrap_or(true);
+            if !still_matches {
+                warn(format!(
+                    "`[replace]` of {} no longer applies to {} {}, consider `[patch]` instead",
+                    spec, dep, new_version_req
+                ));
+            }
+        }
+    }
+
+    warnings
+}
+
+/// Whether the new requirement allows none of the versions the old one did, so dependents using
+/// the dependency's types would have to upgrade it too
+fn is_breaking_change(old_version_req: &str, new_version_req: &str) -> bool {
+    let (old_version_req, new_version_req) = match (
+        VersionReq::parse(old_version_req),
+        VersionReq::parse(new_version_req),
+    ) {
+        (Ok(old), Ok(new)) => (old, new),
+        _ => return false,
+    };
+    let lowest = match new_version_req.comparators.first() {
+        Some(comparator) => semver::Version {
+            major: comparator.major,
+            minor: comparator.minor.unwrap_or(0),
+            patch: comparator.patch.unwrap_or(0),
+            pre: comparator.pre.clone(),
+            build: semver::BuildMetadata::EMPTY,
+        },
+        None => return false,
+    };
+    !old_version_req.matches(&lowest)
+}
+
 fn is_pinned_req(old_version_req: &str) -> bool {
     if let Ok(version_req) = VersionReq::parse(old_version_req) {
         version_req.comparators.iter().any(|comparator| {
@@ -427,6 +2861,266 @@ fn is_pinned_req(old_version_req: &str) -> bool {
     }
 }
 
+/// `*` and `1.*`, but not `>=1`
+fn is_wildcard_req(old_version_req: &str) -> bool {
+    VersionReq::parse(old_version_req)
+        .map(|version_req| {
+            version_req.comparators.is_empty()
+                || version_req
+                    .comparators
+                    .iter()
+                    .any(|comparator| comparator.op == Op::Wildcard)
+        })
+        .unwrap_or(false)
+}
+
+fn is_exact_req(old_version_req: &str) -> bool {
+    VersionReq::parse(old_version_req)
+        .map(|version_req| {
+            version_req
+                .comparators
+                .iter()
+                .any(|comparator| comparator.op == Op::Exact)
+        })
+        .unwrap_or(false)
+}
+
+fn is_tilde_req(old_version_req: &str) -> bool {
+    VersionReq::parse(old_version_req)
+        .map(|version_req| {
+            !version_req.comparators.is_empty()
+                && version_req
+                    .comparators
+                    .iter()
+                    .all(|comparator| comparator.op == Op::Tilde)
+        })
+        .unwrap_or(false)
+}
+
+/// Cargo ignores build metadata in requirements, so leave it out of new ones
+fn without_build_metadata(version: &str) -> String {
+    version.split('+').next().unwrap_or(version).to_owned()
+}
+
+/// Requirement style configured for the workspace of `manifests`
+fn req_style(manifests: &[cargo_metadata::Package]) -> CargoResult<ReqStyle> {
+    match manifests.first() {
+        Some(package) => ReqStyle::for_manifest(package.manifest_path.as_std_path()),
+        None => Ok(ReqStyle::default()),
+    }
+}
+
+/// An exclusion saved with `--remember`
+struct RememberedExclude {
+    pattern: String,
+    reason: Option<String>,
+    /// Day the exclusion stops applying on, like `2024-01-01`
+    until: Option<String>,
+}
+
+/// Exclusions from `exclude` under `[workspace.metadata.cargo-upgrade]`, or
+/// `[package.metadata.cargo-upgrade]` outside of a workspace
+fn remembered_excludes(
+    manifests: &[cargo_metadata::Package],
+) -> CargoResult<Vec<RememberedExclude>> {
+    let package = match manifests.first() {
+        Some(package) => package,
+        None => return Ok(Vec::new()),
+    };
+    let manifest_path = package.manifest_path.as_std_path();
+    let root = find_workspace_root(manifest_path).unwrap_or_else(|_| manifest_path.to_owned());
+    let root = LocalManifest::try_new(&root)?;
+    let config = ["workspace", "package"].iter().find_map(|table| {
+        root.data
+            .get(table)?
+            .get("metadata")?
+            .get("cargo-upgrade")?
+            .get("exclude")
+    });
+    let config = match config {
+        Some(config) => config
+            .as_table_like()
+            .with_context(|| "`exclude` must be a table")?,
+        None => return Ok(Vec::new()),
+    };
+    let mut excludes = Vec::new();
+    for (pattern, value) in config.iter() {
+        let value = value
+            .as_table_like()
+            .with_context(|| format!("`exclude.{}` must be a table", pattern))?;
+        let field = |key: &str| -> CargoResult<Option<String>> {
+            value
+                .get(key)
+                .map(|v| {
+                    v.as_str()
+                        .map(|v| v.to_owned())
+                        .with_context(|| format!("`exclude.{}.{}` must be a string", pattern, key))
+                })
+                .transpose()
+        };
+        let until = field("until")?
+            .map(|until| parse_date(&until))
+            .transpose()
+            .with_context(|| format!("Invalid `exclude.{}.until`", pattern))?;
+        excludes.push(RememberedExclude {
+            pattern: pattern.to_owned(),
+            reason: field("reason")?,
+            until,
+        });
+    }
+    Ok(excludes)
+}
+
+/// Save the `--exclude` patterns where [`remembered_excludes`] reads them
+fn remember_excludes(args: &UpgradeArgs, root: &mut LocalManifest) -> CargoResult<()> {
+    let owner = if root.data.contains_key("workspace") {
+        "workspace"
+    } else {
+        "package"
+    };
+    let mut table: &mut dyn toml_edit::TableLike = root.data.as_table_mut();
+    for key in [owner, "metadata", "cargo-upgrade"] {
+        let mut implicit = toml_edit::Table::new();
+        implicit.set_implicit(true);
+        table = table
+            .entry(key)
+            .or_insert(toml_edit::Item::Table(implicit))
+            .as_table_like_mut()
+            .with_context(|| format!("`{}` must be a table", key))?;
+    }
+    let excludes = table
+        .entry("exclude")
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+        .with_context(|| "`exclude` must be a table")?;
+    for pattern in &args.exclude {
+        let mut exclude = toml_edit::InlineTable::default();
+        if let Some(reason) = &args.reason {
+            exclude.insert("reason", reason.as_str().into());
+        }
+        if let Some(until) = &args.until {
+            exclude.insert("until", until.as_str().into());
+        }
+        shell_status("Remembering", &format!("the exclusion of {}", pattern))?;
+        excludes.insert(pattern, toml_edit::value(exclude));
+    }
+    Ok(())
+}
+
+/// Today's date in UTC, like `2024-01-01`
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    date_from_days((secs / 86_400) as i64)
+}
+
+/// The date `days` after 1970-01-01, from Howard Hinnant's `civil_from_days`
+fn date_from_days(days: i64) -> String {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Counted from March, so leap days come last
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Path of the manifest relative to the workspace root, like Renovate reports it
+pub fn package_file(root_dir: &Path, manifest_path: &Path) -> String {
+    let path = manifest_path
+        .strip_prefix(root_dir)
+        .unwrap_or(manifest_path);
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Dependencies in `[workspace.dependencies]` that turn off their default features
+fn defaults_off_in_workspace(
+    manifests: &[cargo_metadata::Package],
+) -> CargoResult<BTreeSet<String>> {
+    let package = match manifests.first() {
+        Some(package) => package,
+        None => return Ok(BTreeSet::new()),
+    };
+    let root = match find_workspace_root(package.manifest_path.as_std_path()) {
+        Ok(root) => LocalManifest::try_new(&root)?,
+        Err(_) => return Ok(BTreeSet::new()),
+    };
+    let dependencies = root
+        .data
+        .get("workspace")
+        .and_then(|workspace| workspace.get("dependencies"))
+        .and_then(|dependencies| dependencies.as_table_like());
+    let defaults_off = dependencies
+        .into_iter()
+        .flat_map(|dependencies| dependencies.iter())
+        .filter(|(_, dependency)| {
+            let default_features = dependency
+                .get("default-features")
+                .or_else(|| dependency.get("default_features"));
+            default_features.and_then(|d| d.as_bool()) == Some(false)
+        })
+        .map(|(name, _)| name.to_owned())
+        .collect();
+    Ok(defaults_off)
+}
+
+/// Parse a duration like `90s`, `30m`, `12h`, or `2d`
+fn parse_duration(value: &str) -> CargoResult<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount.parse().with_context(|| {
+        format!(
+            "Invalid duration `{}`, expected a number and unit like `2d`",
+            value
+        )
+    })?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => anyhow::bail!(
+            "Invalid duration unit `{}`, expected one of `s`, `m`, `h`, or `d`",
+            unit
+        ),
+    };
+    Ok(Duration::from_secs(amount * seconds))
+}
+
+/// Parse a date like `2024-01-01`
+fn parse_date(value: &str) -> CargoResult<String> {
+    let value = value.trim();
+    let is_date = value.len() == 10
+        && value.char_indices().all(|(i, c)| match i {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        })
+        && (1..=12).contains(&value[5..7].parse::<u32>()?)
+        && (1..=31).contains(&value[8..10].parse::<u32>()?);
+    if !is_date {
+        anyhow::bail!("Invalid date `{}`, expected one like `2024-01-01`", value);
+    }
+    Ok(value.to_owned())
+}
+
+/// Parse a list of exclusions or manifests, ignoring blank lines and `#` comments
+fn parse_list_file(content: &str) -> impl Iterator<Item = String> + '_ {
+    content
+        .lines()
+        .map(|line| line.split_once('#').map(|(l, _)| l).unwrap_or(line).trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_owned())
+}
+
 fn deprecated_message(message: &str) -> CargoResult<()> {
     let colorchoice = colorize_stderr();
     let mut output = StandardStream::stderr(colorchoice);
@@ -539,7 +3233,8 @@ impl Dep {
     }
 
     fn is_interesting(&self) -> bool {
-        if self.reason.is_none() {
+        // Only listed when `Cargo.lock` will pick up a newer version
+        if self.reason.is_none() || self.reason == Some(Reason::Excluded) {
             return true;
         }
 
@@ -560,6 +3255,12 @@ enum Reason {
     Unchanged,
     Compatible,
     Pinned,
+    Constrained,
+    TildeConstrained,
+    Excluded,
+    MsrvIncompatible,
+    Filtered,
+    Declined,
 }
 
 impl Reason {
@@ -568,6 +3269,12 @@ impl Reason {
             Self::Unchanged => "",
             Self::Compatible => "compatible",
             Self::Pinned => "pinned",
+            Self::Constrained => "constrained",
+            Self::TildeConstrained => "tilde-constrained",
+            Self::Excluded => "excluded",
+            Self::MsrvIncompatible => "msrv",
+            Self::Filtered => "filtered",
+            Self::Declined => "declined",
         }
     }
 
@@ -576,6 +3283,26 @@ impl Reason {
             Self::Unchanged => "unchanged",
             Self::Compatible => "compatible",
             Self::Pinned => "pinned",
+            Self::Constrained => "constrained",
+            Self::TildeConstrained => "tilde-constrained",
+            Self::Excluded => "excluded",
+            Self::MsrvIncompatible => "msrv-incompatible",
+            Self::Filtered => "filtered",
+            Self::Declined => "declined",
+        }
+    }
+
+    fn to_report(self) -> ReportReason {
+        match self {
+            Self::Unchanged => ReportReason::Unchanged,
+            Self::Compatible => ReportReason::Compatible,
+            Self::Pinned => ReportReason::Pinned,
+            Self::Constrained => ReportReason::Constrained,
+            Self::TildeConstrained => ReportReason::TildeConstrained,
+            Self::Excluded => ReportReason::Excluded,
+            Self::MsrvIncompatible => ReportReason::MsrvIncompatible,
+            Self::Filtered => ReportReason::Filtered,
+            Self::Declined => ReportReason::Declined,
         }
     }
 }
@@ -620,66 +3347,14 @@ fn print_upgrade(deps: Vec<Dep>, verbose: bool) -> CargoResult<()> {
                 width[5] = width[5].max(dep.short_reason().len());
             }
         }
-        for (i, dep) in interesting.iter().enumerate() {
-            let is_header = (0..=1).contains(&i);
-            let mut header_spec = ColorSpec::new();
-            header_spec.set_bold(true);
-
-            let spec = if is_header {
-                header_spec.clone()
-            } else {
-                ColorSpec::new()
-            };
-            write_cell(&dep.name, width[0], &spec)?;
-
-            shell_write_stderr(" ", &ColorSpec::new())?;
-            let spec = if is_header {
-                header_spec.clone()
-            } else {
-                dep.old_version_req_spec()
-            };
-            write_cell(&dep.old_version_req, width[1], &spec)?;
-
-            shell_write_stderr(" ", &ColorSpec::new())?;
-            let spec = if is_header {
-                header_spec.clone()
-            } else {
-                dep.locked_version_spec()
-            };
-            write_cell(dep.locked_version(), width[2], &spec)?;
-
-            shell_write_stderr(" ", &ColorSpec::new())?;
-            let spec = if is_header {
-                header_spec.clone()
-            } else {
-                ColorSpec::new()
-            };
-            write_cell(dep.latest_version(), width[3], &spec)?;
-
-            shell_write_stderr(" ", &ColorSpec::new())?;
-            let spec = if is_header {
-                header_spec.clone()
-            } else {
-                dep.new_version_req_spec()
-            };
-            write_cell(&dep.new_version_req, width[4], &spec)?;
-
-            if 0 < width[5] {
-                shell_write_stderr(" ", &ColorSpec::new())?;
-                let spec = if is_header {
-                    header_spec.clone()
-                } else {
-                    dep.reason_spec()
-                };
-                let reason = match i {
-                    0 => "note",
-                    1 => "====",
-                    _ => dep.short_reason(),
-                };
-                write_cell(reason, width[5], &spec)?;
-            }
-
-            shell_write_stderr("\n", &ColorSpec::new())?;
+        let fits = match term_width() {
+            Some(max) => shrink_to_fit(&mut width, max),
+            None => true,
+        };
+        if fits {
+            print_table(&interesting, &width)?;
+        } else {
+            print_records(&interesting[2..])?;
         }
     }
 
@@ -708,9 +3383,282 @@ fn print_upgrade(deps: Vec<Dep>, verbose: bool) -> CargoResult<()> {
     Ok(())
 }
 
+/// Print a row for each dependency, listing which packages require which versions of it
+fn print_by_dependency(tables: Vec<(String, Vec<Dep>)>, verbose: bool) -> CargoResult<()> {
+    let mut groups: IndexMap<String, Vec<(String, Dep)>> = IndexMap::new();
+    for (package, deps) in tables {
+        for dep in deps {
+            groups
+                .entry(dep.name.clone())
+                .or_default()
+                .push((package.clone(), dep));
+        }
+    }
+
+    let mut rows = vec![
+        (
+            [
+                "name".to_owned(),
+                "old reqs".to_owned(),
+                "latest".to_owned(),
+                "new reqs".to_owned(),
+            ],
+            true,
+        ),
+        (
+            [
+                "====".to_owned(),
+                "========".to_owned(),
+                "======".to_owned(),
+                "========".to_owned(),
+            ],
+            true,
+        ),
+    ];
+    let mut uninteresting = BTreeMap::new();
+    for (name, uses) in groups {
+        let mut old_reqs: IndexMap<&str, Vec<&str>> = IndexMap::new();
+        let mut new_reqs: Vec<&str> = Vec::new();
+        for (package, dep) in &uses {
+            old_reqs
+                .entry(dep.old_version_req.as_str())
+                .or_default()
+                .push(package.as_str());
+            if !new_reqs.contains(&dep.new_version_req.as_str()) {
+                new_reqs.push(dep.new_version_req.as_str());
+            }
+        }
+        let consistent = old_reqs.len() == 1;
+        if !verbose && consistent && !uses.iter().any(|(_, dep)| dep.is_interesting()) {
+            uninteresting
+                .entry(uses[0].1.long_reason())
+                .or_insert_with(BTreeSet::new)
+                .insert(name);
+            continue;
+        }
+        let old_reqs = old_reqs
+            .iter()
+            .map(|(req, packages)| format!("{} ({})", req, packages.join(", ")))
+            .collect::<Vec<_>>();
+        let latest = uses[0].1.latest_version().to_owned();
+        rows.push((
+            [name, old_reqs.join("; "), latest, new_reqs.join(", ")],
+            consistent,
+        ));
+    }
+
+    if 2 < rows.len() {
+        let mut width = [0; 4];
+        for (cells, _) in &rows {
+            for (width, cell) in width.iter_mut().zip(cells) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let mut header_spec = ColorSpec::new();
+        header_spec.set_bold(true);
+        // Packages disagreeing on a dependency is what this view is for
+        let mut inconsistent_spec = ColorSpec::new();
+        inconsistent_spec.set_fg(Some(Color::Yellow));
+        let plain_spec = ColorSpec::new();
+        for (i, (cells, consistent)) in rows.iter().enumerate() {
+            for (column, cell) in cells.iter().enumerate() {
+                if 0 < column {
+                    shell_write_stderr(" ", &plain_spec)?;
+                }
+                let spec = if i < 2 {
+                    &header_spec
+                } else if column == 1 && !consistent {
+                    &inconsistent_spec
+                } else {
+                    &plain_spec
+                };
+                write_cell(cell, width[column], spec)?;
+            }
+            shell_write_stderr("\n", &plain_spec)?;
+        }
+    }
+
+    if !uninteresting.is_empty() {
+        let mut note = "Re-run with `--verbose` to show all dependencies".to_owned();
+        for (reason, deps) in uninteresting {
+            use std::fmt::Write;
+            write!(
+                &mut note,
+                "\n  {}: {}",
+                reason,
+                deps.into_iter().collect::<Vec<_>>().join(", ")
+            )?;
+        }
+        shell_note(&note)?;
+    }
+
+    Ok(())
+}
+
+/// Print the dependencies as a table, below their header rows
+fn print_table(deps: &[Dep], width: &[usize; 6]) -> CargoResult<()> {
+    for (i, dep) in deps.iter().enumerate() {
+        let is_header = (0..=1).contains(&i);
+        let mut header_spec = ColorSpec::new();
+        header_spec.set_bold(true);
+
+        let spec = if is_header {
+            header_spec.clone()
+        } else {
+            ColorSpec::new()
+        };
+        write_cell(&dep.name, width[0], &spec)?;
+
+        shell_write_stderr(" ", &ColorSpec::new())?;
+        let spec = if is_header {
+            header_spec.clone()
+        } else {
+            dep.old_version_req_spec()
+        };
+        write_cell(&dep.old_version_req, width[1], &spec)?;
+
+        shell_write_stderr(" ", &ColorSpec::new())?;
+        let spec = if is_header {
+            header_spec.clone()
+        } else {
+            dep.locked_version_spec()
+        };
+        write_cell(dep.locked_version(), width[2], &spec)?;
+
+        shell_write_stderr(" ", &ColorSpec::new())?;
+        let spec = if is_header {
+            header_spec.clone()
+        } else {
+            ColorSpec::new()
+        };
+        write_cell(dep.latest_version(), width[3], &spec)?;
+
+        shell_write_stderr(" ", &ColorSpec::new())?;
+        let spec = if is_header {
+            header_spec.clone()
+        } else {
+            dep.new_version_req_spec()
+        };
+        write_cell(&dep.new_version_req, width[4], &spec)?;
+
+        if 0 < width[5] {
+            shell_write_stderr(" ", &ColorSpec::new())?;
+            let spec = if is_header {
+                header_spec.clone()
+            } else {
+                dep.reason_spec()
+            };
+            let reason = match i {
+                0 => "note",
+                1 => "====",
+                _ => dep.short_reason(),
+            };
+            write_cell(reason, width[5], &spec)?;
+        }
+
+        shell_write_stderr("\n", &ColorSpec::new())?;
+    }
+    Ok(())
+}
+
+/// Print each dependency as a block of labeled fields, for terminals too narrow for the table
+fn print_records(deps: &[Dep]) -> CargoResult<()> {
+    let mut label_spec = ColorSpec::new();
+    label_spec.set_bold(true);
+    for (i, dep) in deps.iter().enumerate() {
+        if 0 < i {
+            shell_write_stderr("\n", &ColorSpec::new())?;
+        }
+        let mut fields = vec![
+            ("name", dep.name.as_str(), ColorSpec::new()),
+            (
+                "old req",
+                dep.old_version_req.as_str(),
+                dep.old_version_req_spec(),
+            ),
+            ("locked", dep.locked_version(), dep.locked_version_spec()),
+            ("latest", dep.latest_version(), ColorSpec::new()),
+            (
+                "new req",
+                dep.new_version_req.as_str(),
+                dep.new_version_req_spec(),
+            ),
+        ];
+        if !dep.short_reason().is_empty() {
+            fields.push(("note", dep.short_reason(), dep.reason_spec()));
+        }
+        for (label, value, spec) in fields {
+            write_cell(label, RECORD_LABEL_WIDTH, &label_spec)?;
+            shell_write_stderr(" ", &ColorSpec::new())?;
+            shell_write_stderr(value, &spec)?;
+            shell_write_stderr("\n", &ColorSpec::new())?;
+        }
+    }
+    Ok(())
+}
+
+/// Width of the labels in [`print_records`]
+const RECORD_LABEL_WIDTH: usize = 7;
+
+/// Columns that can be truncated to fit the table in the terminal: name, old req and new req
+const SHRINKABLE_COLUMNS: [usize; 3] = [0, 1, 4];
+
+/// Narrowest a column gets before the table gives way to [`print_records`]
+const MIN_COLUMN_WIDTH: usize = 10;
+
+/// Width of the terminal that stderr is attached to, if any
+///
+/// `COLUMNS` takes precedence, so the layout can be chosen when the terminal can't be queried.
+fn term_width() -> Option<usize> {
+    if let Some(columns) = std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()) {
+        return Some(columns);
+    }
+    #[cfg(unix)]
+    let size = {
+        use std::os::unix::io::AsRawFd;
+        terminal_size::terminal_size_using_fd(std::io::stderr().as_raw_fd())
+    };
+    #[cfg(not(unix))]
+    let size = terminal_size::terminal_size();
+    size.map(|(terminal_size::Width(width), _)| usize::from(width))
+}
+
+/// Narrow the widest of the shrinkable columns until the table fits in `max`
+///
+/// Returns whether it fits without narrowing any column below [`MIN_COLUMN_WIDTH`].
+fn shrink_to_fit(width: &mut [usize; 6], max: usize) -> bool {
+    loop {
+        let columns = width.iter().filter(|w| 0 < **w).count();
+        let total = width.iter().sum::<usize>() + columns.saturating_sub(1);
+        if total <= max {
+            return true;
+        }
+        match SHRINKABLE_COLUMNS
+            .iter()
+            .copied()
+            .filter(|i| MIN_COLUMN_WIDTH < width[*i])
+            .max_by_key(|i| width[*i])
+        {
+            Some(i) => width[i] -= 1,
+            None => return false,
+        }
+    }
+}
+
+/// Pad `content` to `width`, truncating it with an ellipsis when it is too long
 fn write_cell(content: &str, width: usize, spec: &ColorSpec) -> CargoResult<()> {
+    let len = content.chars().count();
+    if width < len {
+        let truncated = content
+            .chars()
+            .take(width.saturating_sub(1))
+            .chain(std::iter::once('…'))
+            .collect::<String>();
+        shell_write_stderr(&truncated, spec)?;
+        return Ok(());
+    }
     shell_write_stderr(content, spec)?;
-    for _ in 0..(width - content.len()) {
+    for _ in 0..(width - len) {
         shell_write_stderr(" ", &ColorSpec::new())?;
     }
     Ok(())
@@ -773,4 +3721,106 @@ mod test {
         let req = "3";
         assert!(!is_pinned_req(req));
     }
+
+    #[test]
+    fn star_is_wildcard_req() {
+        assert!(is_wildcard_req("*"));
+        assert!(is_wildcard_req("3.*"));
+        assert!(!is_wildcard_req(">=3"));
+    }
+
+    #[test]
+    fn only_exact_is_exact_req() {
+        assert!(is_exact_req("=3.1.0"));
+        assert!(!is_exact_req("<3.1"));
+        assert!(!is_exact_req("3.1.*"));
+    }
+
+    #[test]
+    fn exclude_file_skips_comments() {
+        let content = "\
+# held back until the next release
+serde
+  tokio-* # async stack
+
+";
+        let actual = parse_list_file(content).collect::<Vec<_>>();
+        assert_eq!(actual, ["serde", "tokio-*"]);
+    }
+
+    #[test]
+    fn durations_have_units() {
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(30 * 60));
+        assert_eq!(
+            parse_duration("2d").unwrap(),
+            Duration::from_secs(2 * 24 * 60 * 60)
+        );
+        assert!(parse_duration("2").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("2w").is_err());
+    }
+
+    #[test]
+    fn dates_are_days() {
+        assert_eq!(parse_date("2024-01-01").unwrap(), "2024-01-01");
+        assert!(parse_date("2024-1-1").is_err());
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("2024-01-01T00:00:00Z").is_err());
+    }
+
+    #[test]
+    fn days_since_epoch() {
+        assert_eq!(date_from_days(0), "1970-01-01");
+        assert_eq!(date_from_days(19_723), "2024-01-01");
+        assert_eq!(date_from_days(19_782), "2024-02-29");
+        assert_eq!(date_from_days(-1), "1969-12-31");
+    }
+
+    #[test]
+    fn major_bumps_are_breaking() {
+        assert!(is_breaking_change("1.0", "2.0"));
+        assert!(is_breaking_change("0.1", "0.2"));
+        assert!(!is_breaking_change("1.0", "1.5"));
+        assert!(!is_breaking_change("0.1.0", "0.1.3"));
+    }
+
+    #[test]
+    fn shrink_widest_columns_to_fit() {
+        let mut width = [30, 12, 6, 9, 12, 0];
+        assert!(shrink_to_fit(&mut width, 60));
+        assert_eq!(width, [17, 12, 6, 9, 12, 0]);
+
+        let mut width = [30, 12, 6, 9, 12, 0];
+        assert!(!shrink_to_fit(&mut width, 40));
+    }
+
+    #[test]
+    fn replace_left_behind_by_upgrade() {
+        let manifest = LocalManifest {
+            path: PathBuf::from("Cargo.toml"),
+            manifest: r#"
+[package]
+name = "foo"
+version = "0.1.0"
+
+[dependencies]
+serde = "1.0"
+
+[replace]
+"serde:0.9.0" = { path = "serde" }
+"#
+            .parse()
+            .unwrap(),
+        };
+        let upgraded = [("serde".to_owned(), "1.0".to_owned())];
+        let warnings = legacy_warnings(&manifest, "foo", &upgraded);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].message,
+            "`[replace]` of serde:0.9.0 no longer applies to serde 1.0, consider `[patch]` instead"
+        );
+        let upgraded = [("serde".to_owned(), "0.9".to_owned())];
+        assert!(legacy_warnings(&manifest, "foo", &upgraded).is_empty());
+    }
 }
@@ -0,0 +1,217 @@
+//! Report the edits a `--dry-run` kept from being written.
+use std::io::Write;
+use std::path::Path;
+
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
+
+use super::errors::*;
+use super::transaction::ManifestTransaction;
+use super::util::{colorize_stdout, shell_warn};
+
+/// How `--dry-run` reports the edits it didn't write
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DryRun {
+    /// Only the command's usual status output
+    Summary,
+    /// A unified diff of every edited manifest
+    Diff,
+    /// The contents of every edited manifest before and after, as JSON
+    Json,
+}
+
+/// Manifest edits, as reported by `--dry-run=json`
+#[derive(Debug, Serialize)]
+struct ManifestChange<'t> {
+    manifest_path: &'t Path,
+    before: &'t str,
+    after: String,
+}
+
+impl ManifestTransaction {
+    /// Print the staged edits to stdout in place of committing them, then warn that nothing was
+    /// written
+    pub fn report_dry_run(&self, format: DryRun) -> CargoResult<()> {
+        match format {
+            DryRun::Summary => {}
+            DryRun::Diff => {
+                let cwd = std::env::current_dir()?;
+                let mut output = StandardStream::stdout(colorize_stdout());
+                for (path, before, after) in self.changes() {
+                    let path = path.strip_prefix(&cwd).unwrap_or(path);
+                    let path = path.display().to_string().replace('\\', "/");
+                    write_diff(&mut output, &unified_diff(&path, before, &after))?;
+                }
+            }
+            DryRun::Json => {
+                let changes = self
+                    .changes()
+                    .map(|(manifest_path, before, after)| ManifestChange {
+                        manifest_path,
+                        before,
+                        after,
+                    })
+                    .collect::<Vec<_>>();
+                println!("{}", serde_json::to_string_pretty(&changes)?);
+            }
+        }
+        shell_warn("aborting due to dry run")
+    }
+}
+
+/// Lines of unchanged context around each hunk
+const CONTEXT: usize = 3;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Line<'s> {
+    Same(&'s str),
+    Removed(&'s str),
+    Added(&'s str),
+}
+
+impl Line<'_> {
+    fn in_old(&self) -> bool {
+        !matches!(self, Line::Added(_))
+    }
+
+    fn in_new(&self) -> bool {
+        !matches!(self, Line::Removed(_))
+    }
+}
+
+/// Unified diff between two versions of the manifest at `path`
+fn unified_diff(path: &str, before: &str, after: &str) -> String {
+    let old = before.lines().collect::<Vec<_>>();
+    let new = after.lines().collect::<Vec<_>>();
+    let lines = diff_lines(&old, &new);
+
+    // Changes close enough for their context to touch share a hunk
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (i, _) in lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| !matches!(l, Line::Same(_)))
+    {
+        let start = i.saturating_sub(CONTEXT);
+        let end = (i + CONTEXT + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut diff = format!("--- a/{}\n+++ b/{}\n", path, path);
+    for (start, end) in hunks {
+        let old_start = lines[..start].iter().filter(|l| l.in_old()).count();
+        let new_start = lines[..start].iter().filter(|l| l.in_new()).count();
+        let hunk = &lines[start..end];
+        let old_len = hunk.iter().filter(|l| l.in_old()).count();
+        let new_len = hunk.iter().filter(|l| l.in_new()).count();
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_len),
+            hunk_range(new_start, new_len)
+        ));
+        for line in hunk {
+            let (prefix, line) = match line {
+                Line::Same(line) => (' ', line),
+                Line::Removed(line) => ('-', line),
+                Line::Added(line) => ('+', line),
+            };
+            diff.push(prefix);
+            diff.push_str(line);
+            diff.push('\n');
+        }
+    }
+    diff
+}
+
+/// Write `diff` colored like `git diff`, when `output` supports it
+fn write_diff(output: &mut impl WriteColor, diff: &str) -> CargoResult<()> {
+    for line in diff.lines() {
+        let mut spec = ColorSpec::new();
+        if line.starts_with("--- ") || line.starts_with("+++ ") {
+            spec.set_bold(true);
+        } else if line.starts_with("@@") {
+            spec.set_fg(Some(Color::Cyan));
+        } else if line.starts_with('-') {
+            spec.set_fg(Some(Color::Red));
+        } else if line.starts_with('+') {
+            spec.set_fg(Some(Color::Green));
+        }
+        output.set_color(&spec)?;
+        write!(output, "{}", line)?;
+        output.reset()?;
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+fn hunk_range(start: usize, len: usize) -> String {
+    // Line numbers are 1-based, except that an empty range names the line before it
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+/// Line-by-line edit script from `old` to `new`, from their longest common subsequence
+fn diff_lines<'s>(old: &[&'s str], new: &[&'s str]) -> Vec<Line<'s>> {
+    // `common[i][j]` is the length of the longest common subsequence of `old[i..]` and `new[j..]`
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn diff_has_context_and_ranges() {
+        let before = "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n\n[dependencies]\na = \"1\"\nb = \"1\"\nc = \"1\"\nd = \"1\"\ne = \"1\"\n";
+        let after = "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n\n[dependencies]\na = \"2\"\nb = \"1\"\nc = \"1\"\nd = \"1\"\n";
+        assert_eq!(
+            unified_diff("Cargo.toml", before, after),
+            "--- a/Cargo.toml\n+++ b/Cargo.toml\n@@ -3,8 +3,7 @@\n version = \"0.1.0\"\n \n [dependencies]\n-a = \"1\"\n+a = \"2\"\n b = \"1\"\n c = \"1\"\n d = \"1\"\n-e = \"1\"\n"
+        );
+    }
+
+    #[test]
+    fn diff_colors_changed_lines() {
+        let diff = "--- a/Cargo.toml\n+++ b/Cargo.toml\n@@ -1 +1 @@\n-a = \"1\"\n+a = \"2\"\n";
+        let mut plain = termcolor::Buffer::no_color();
+        write_diff(&mut plain, diff).unwrap();
+        assert_eq!(String::from_utf8(plain.into_inner()).unwrap(), diff);
+
+        let mut colored = termcolor::Buffer::ansi();
+        write_diff(&mut colored, diff).unwrap();
+        let colored = String::from_utf8(colored.into_inner()).unwrap();
+        assert!(colored.contains("\x1b[31m-a = \"1\""), "{:?}", colored);
+        assert!(colored.contains("\x1b[32m+a = \"2\""), "{:?}", colored);
+    }
+}
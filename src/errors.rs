@@ -69,6 +69,30 @@ pub(crate) fn no_crate_err(name: impl Display) -> Error {
     anyhow::format_err!("The crate `{}` could not be found in registry index.", name)
 }
 
+pub(crate) fn no_compatible_version_err(name: impl Display, version_req: impl Display) -> Error {
+    anyhow::format_err!(
+        "No available versions of `{}` match `{}`.",
+        name,
+        version_req
+    )
+}
+
+pub(crate) fn no_version_before_err(name: impl Display, date: impl Display) -> Error {
+    anyhow::format_err!(
+        "No available versions of `{}` were published before {}.",
+        name,
+        date
+    )
+}
+
+pub(crate) fn no_version_for_rust_err(name: impl Display, rust_version: impl Display) -> Error {
+    anyhow::format_err!(
+        "No available versions of `{}` support Rust {}.",
+        name,
+        rust_version
+    )
+}
+
 pub(crate) fn non_existent_table_err(table: impl Display) -> Error {
     anyhow::format_err!("The table `{}` could not be found.", table)
 }
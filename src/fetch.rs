@@ -6,8 +6,9 @@ use std::time::Duration;
 use url::Url;
 
 use super::errors::*;
-use super::registry::registry_url;
+use super::registry::{registry_url, CRATES_IO_INDEX};
 use super::shell_status;
+use super::version::parse_rust_version;
 use super::Dependency;
 use super::RegistrySource;
 use super::VersionExt;
@@ -161,6 +162,365 @@ fn gen_fuzzy_crate_names(crate_name: String) -> CargoResult<Vec<String>> {
     Ok(result)
 }
 
+/// Query the newest version matching a requirement from a registry index
+///
+/// Unlike [`get_latest_dependency`], this won't look past the versions `version_req` allows, for
+/// checking what an upgrade within the requirement would pick.
+pub fn get_compatible_dependency(
+    crate_name: &str,
+    version_req: &semver::VersionReq,
+    manifest_path: &Path,
+    registry: Option<&Url>,
+) -> CargoResult<Dependency> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        // The simulated registry only has the latest version of each crate
+        let latest = get_latest_dependency(crate_name, false, manifest_path, registry)?;
+        let version: semver::Version = latest
+            .version()
+            .expect("registry packages always have a version")
+            .parse()?;
+        return if version_req.matches(&version) {
+            Ok(latest)
+        } else {
+            Err(no_compatible_version_err(crate_name, version_req))
+        };
+    }
+
+    if crate_name.is_empty() {
+        anyhow::bail!("Found empty crate name");
+    }
+
+    let registry = match registry {
+        Some(url) => url.clone(),
+        None => registry_url(manifest_path, None)?,
+    };
+
+    let crate_versions = fuzzy_query_registry_index(crate_name, &registry)?;
+    read_compatible_version(&crate_versions, version_req)
+        .ok_or_else(|| no_compatible_version_err(crate_name, version_req))
+}
+
+/// Query the oldest version matching a requirement from a registry index
+///
+/// Like [`get_compatible_dependency`], but for the lowest version `version_req` allows, the one
+/// `-Z minimal-versions` would resolve to.
+pub fn get_minimal_dependency(
+    crate_name: &str,
+    version_req: &semver::VersionReq,
+    manifest_path: &Path,
+    registry: Option<&Url>,
+) -> CargoResult<Dependency> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        // With a single version of each crate, the oldest is also the newest
+        return get_compatible_dependency(crate_name, version_req, manifest_path, registry);
+    }
+
+    if crate_name.is_empty() {
+        anyhow::bail!("Found empty crate name");
+    }
+
+    let registry = match registry {
+        Some(url) => url.clone(),
+        None => registry_url(manifest_path, None)?,
+    };
+
+    let crate_versions = fuzzy_query_registry_index(crate_name, &registry)?;
+    read_minimal_version(&crate_versions, version_req)
+        .ok_or_else(|| no_compatible_version_err(crate_name, version_req))
+}
+
+/// Query the newest version published before `max_date`, a `YYYY-MM-DD` day in UTC
+///
+/// Registry indexes don't record when versions were published, so this asks the crates.io API and
+/// fails for crates from other registries.  When `version_req` is given, only versions it matches
+/// are considered, like with [`get_compatible_dependency`].
+pub fn get_dependency_published_before(
+    crate_name: &str,
+    version_req: Option<&semver::VersionReq>,
+    flag_allow_prerelease: bool,
+    max_date: &str,
+    manifest_path: &Path,
+    registry: Option<&Url>,
+) -> CargoResult<Dependency> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        // The simulated registry published the latest version of each crate on 2020-01-01
+        if max_date <= "2020-01-01" {
+            return Err(no_version_before_err(crate_name, max_date));
+        }
+        return match version_req {
+            Some(version_req) => {
+                get_compatible_dependency(crate_name, version_req, manifest_path, registry)
+            }
+            None => {
+                get_latest_dependency(crate_name, flag_allow_prerelease, manifest_path, registry)
+            }
+        };
+    }
+
+    if let Some(registry) = registry.filter(|r| r.as_str() != CRATES_IO_INDEX) {
+        anyhow::bail!(
+            "Publish dates of `{}` are unknown: only crates.io provides them, and it is from {}",
+            crate_name,
+            registry
+        );
+    }
+
+    let url = format!("https://crates.io/api/v1/crates/{}/versions", crate_name);
+    let mut agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(30))
+        .user_agent(concat!("cargo-edit/", env!("CARGO_PKG_VERSION")));
+    if let Some(proxy) = env_proxy::for_url_str(&url).to_string() {
+        agent = agent.proxy(ureq::Proxy::new(proxy)?);
+    }
+    let response: ApiVersions = agent
+        .build()
+        .get(&url)
+        .call()
+        .with_context(|| format!("Failed to look up when `{}` was published", crate_name))?
+        .into_json()?;
+
+    let version = response
+        .versions
+        .into_iter()
+        .filter(|v| !v.yanked)
+        // RFC 3339 timestamps in UTC, so the date is a prefix
+        .filter(|v| v.created_at.get(..10).map_or(false, |date| date < max_date))
+        .filter_map(|v| v.num.parse::<semver::Version>().ok())
+        .filter(|v| match version_req {
+            Some(version_req) => version_req.matches(v),
+            None => flag_allow_prerelease || v.pre.is_empty(),
+        })
+        .max()
+        .ok_or_else(|| no_version_before_err(crate_name, max_date))?;
+    Ok(Dependency::new(crate_name).set_source(RegistrySource::new(&version.to_string())))
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiVersions {
+    versions: Vec<ApiVersion>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiVersion {
+    num: String,
+    created_at: String,
+    yanked: bool,
+}
+
+/// Query the newest version that builds with Rust `rust_version`
+///
+/// Versions that don't declare a `rust-version` are taken to build with any compiler.  When
+/// `version_req` is given, only versions it matches are considered, like with
+/// [`get_compatible_dependency`].
+pub fn get_dependency_for_rust_version(
+    crate_name: &str,
+    version_req: Option<&semver::VersionReq>,
+    flag_allow_prerelease: bool,
+    rust_version: &semver::Version,
+    manifest_path: &Path,
+    registry: Option<&Url>,
+) -> CargoResult<Dependency> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        // The simulated registry's latest versions need Rust 1.70, and 20.0.0 builds with any
+        if *rust_version >= semver::Version::new(1, 70, 0) {
+            return match version_req {
+                Some(version_req) => {
+                    get_compatible_dependency(crate_name, version_req, manifest_path, registry)
+                }
+                None => get_latest_dependency(
+                    crate_name,
+                    flag_allow_prerelease,
+                    manifest_path,
+                    registry,
+                ),
+            };
+        }
+        let version = semver::Version::new(20, 0, 0);
+        if version_req.map_or(false, |version_req| !version_req.matches(&version)) {
+            return Err(no_version_for_rust_err(crate_name, rust_version));
+        }
+        return Ok(Dependency::new(crate_name)
+            .set_source(RegistrySource::new(&format!("{}+{}", version, crate_name))));
+    }
+
+    let registry = match registry {
+        Some(url) => url.clone(),
+        None => registry_url(manifest_path, None)?,
+    };
+
+    let version = read_index_rust_versions(crate_name, &registry)?
+        .into_iter()
+        .filter(|v| !v.yanked)
+        .filter(|v| {
+            v.rust_version
+                .as_deref()
+                .and_then(|r| parse_rust_version(r).ok())
+                .map_or(true, |r| r <= *rust_version)
+        })
+        .filter_map(|v| v.vers.parse::<semver::Version>().ok())
+        .filter(|v| match version_req {
+            Some(version_req) => version_req.matches(v),
+            None => flag_allow_prerelease || v.pre.is_empty(),
+        })
+        .max()
+        .ok_or_else(|| no_version_for_rust_err(crate_name, rust_version))?;
+    Ok(Dependency::new(crate_name).set_source(RegistrySource::new(&version.to_string())))
+}
+
+/// The `rust-version` that `version` of the crate declares in its registry index
+///
+/// Returns `None` for versions that don't declare one, or that aren't in the index.
+pub fn get_rust_version(
+    crate_name: &str,
+    version: &semver::Version,
+    manifest_path: &Path,
+    registry: Option<&Url>,
+) -> CargoResult<Option<semver::Version>> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        // The simulated registry's latest versions need Rust 1.70, and 20.0.0 builds with any
+        return Ok((version.major == 99999).then(|| semver::Version::new(1, 70, 0)));
+    }
+
+    let registry = match registry {
+        Some(url) => url.clone(),
+        None => registry_url(manifest_path, None)?,
+    };
+
+    let rust_version = read_index_rust_versions(crate_name, &registry)?
+        .into_iter()
+        .find(|v| v.vers.parse::<semver::Version>().ok().as_ref() == Some(version))
+        .and_then(|v| v.rust_version);
+    rust_version.as_deref().map(parse_rust_version).transpose()
+}
+
+/// A version's entry in a registry index, for the fields `crates_index` doesn't read
+#[derive(Debug, Deserialize)]
+struct IndexEntry {
+    vers: String,
+    #[serde(default)]
+    yanked: bool,
+    #[serde(default)]
+    rust_version: Option<String>,
+}
+
+/// Read the crate's entries straight from the local clone of the registry index
+fn read_index_rust_versions(crate_name: &str, registry: &Url) -> CargoResult<Vec<IndexEntry>> {
+    let (path, _) = crates_index::local_path_and_canonical_url(registry.as_str(), None)?;
+    let repo = git2::Repository::open(&path)
+        .with_context(|| format!("The '{}' index hasn't been downloaded", registry))?;
+    let head = repo
+        .refname_to_id("FETCH_HEAD")
+        .or_else(|_| repo.refname_to_id("refs/remotes/origin/HEAD"))?;
+    let tree = repo.find_commit(head)?.tree()?;
+    let entry = match tree.get_path(Path::new(&index_path(crate_name))) {
+        Ok(entry) => entry,
+        Err(err) if err.code() == git2::ErrorCode::NotFound => {
+            return Err(no_crate_err(crate_name));
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let blob = entry.to_object(&repo)?.peel_to_blob()?;
+    blob.content()
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| Ok(serde_json::from_slice(line)?))
+        .collect()
+}
+
+/// Where a crate's entries are in a registry index, like `se/rd/serde`
+fn index_path(crate_name: &str) -> String {
+    let name = crate_name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{}", name),
+        2 => format!("2/{}", name),
+        3 => format!("3/{}/{}", &name[..1], name),
+        _ => format!("{}/{}/{}", &name[..2], &name[2..4], name),
+    }
+}
+
+/// Whether `version` of the crate was already published to the registry, even if it was yanked
+///
+/// Versions differing only in their build metadata count as the same, like they do for
+/// `cargo publish`.  Crates that aren't in the registry have no published versions.  The index is
+/// updated first when `update_index` is set.
+pub fn is_version_published(
+    crate_name: &str,
+    version: &semver::Version,
+    registry: &Url,
+    update_index: bool,
+) -> CargoResult<bool> {
+    let version = semver::Version {
+        build: semver::BuildMetadata::EMPTY,
+        ..version.clone()
+    };
+    if env::var("CARGO_IS_TEST").is_ok() {
+        // The simulated registry has published exactly what it claims is the latest version
+        return Ok(version == semver::Version::new(99999, 0, 0));
+    }
+
+    if update_index {
+        update_registry_index(registry, false)?;
+    }
+
+    let index = crates_index::Index::from_url(registry.as_str())?;
+    let published = index
+        .crate_(crate_name)
+        .map(|krate| {
+            krate.versions().iter().any(|v| {
+                v.version()
+                    .parse::<semver::Version>()
+                    .map(|v| semver::Version {
+                        build: semver::BuildMetadata::EMPTY,
+                        ..v
+                    })
+                    .map_or(false, |v| v == version)
+            })
+        })
+        .unwrap_or(false);
+    Ok(published)
+}
+
+/// The highest version of the crate published to the registry, counting yanked and prerelease
+/// versions since those can't be published again
+///
+/// Crates that aren't in the registry have no published versions.  The index is updated first
+/// when `update_index` is set.
+pub fn latest_published_version(
+    crate_name: &str,
+    registry: &Url,
+    update_index: bool,
+) -> CargoResult<Option<semver::Version>> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        // The simulated registry has published exactly what it claims is the latest version
+        return Ok(Some(semver::Version::new(99999, 0, 0)));
+    }
+
+    if update_index {
+        update_registry_index(registry, false)?;
+    }
+
+    let index = crates_index::Index::from_url(registry.as_str())?;
+    let latest = index.crate_(crate_name).and_then(|krate| {
+        krate
+            .versions()
+            .iter()
+            .filter_map(|v| v.version().parse::<semver::Version>().ok())
+            .max()
+    });
+    Ok(latest)
+}
+
+/// Whether the registry's local index has an entry for the crate, without updating it
+pub fn is_crate_indexed(crate_name: &str, registry: &Url) -> CargoResult<bool> {
+    if env::var("CARGO_IS_TEST").is_ok() {
+        // Everything is in the simulated registry
+        return Ok(true);
+    }
+
+    let index = crates_index::Index::from_url(registry.as_str())?;
+    Ok(index.crate_(crate_name).is_some())
+}
+
 // Checks whether a version object is a stable release
 fn version_is_stable(version: &CrateVersion) -> bool {
     !version.version.is_prerelease()
@@ -191,6 +551,40 @@ fn read_latest_version(
         .set_available_features(latest.available_features.clone()))
 }
 
+/// Read the newest version allowed by `version_req`, which only matches prereleases it names
+fn read_compatible_version(
+    versions: &[CrateVersion],
+    version_req: &semver::VersionReq,
+) -> Option<Dependency> {
+    let compatible = versions
+        .iter()
+        .filter(|&v| !v.yanked)
+        .filter(|&v| version_req.matches(&v.version))
+        .max_by_key(|&v| v.version.clone())?;
+    Some(
+        Dependency::new(&compatible.name)
+            .set_source(RegistrySource::new(&compatible.version.to_string()))
+            .set_available_features(compatible.available_features.clone()),
+    )
+}
+
+/// Read the oldest version allowed by `version_req`
+fn read_minimal_version(
+    versions: &[CrateVersion],
+    version_req: &semver::VersionReq,
+) -> Option<Dependency> {
+    let minimal = versions
+        .iter()
+        .filter(|&v| !v.yanked)
+        .filter(|&v| version_req.matches(&v.version))
+        .min_by_key(|&v| v.version.clone())?;
+    Some(
+        Dependency::new(&minimal.name)
+            .set_source(RegistrySource::new(&minimal.version.to_string()))
+            .set_available_features(minimal.available_features.clone()),
+    )
+}
+
 fn registry_features(v: &crates_index::Version) -> BTreeMap<String, Vec<String>> {
     let mut features: BTreeMap<_, _> = v
         .features()
@@ -207,13 +601,36 @@ fn registry_features(v: &crates_index::Version) -> BTreeMap<String, Vec<String>>
 }
 
 /// update registry index for given project
+///
+/// Indexes that need authentication, like private registries over SSH, are fetched with the
+/// user's `ssh-agent` and git credential helpers.
 pub fn update_registry_index(registry: &Url, quiet: bool) -> CargoResult<()> {
-    let mut index = crates_index::Index::from_url(registry.as_str())?;
     if !quiet {
         shell_status("Updating", &format!("'{}' index", registry))?;
     }
 
-    while need_retry(index.update())? {
+    if registry.scheme() == "ssh" {
+        return fetch_private_index(registry);
+    }
+    let mut index = match crates_index::Index::from_url(registry.as_str()) {
+        Ok(index) => index,
+        Err(crates_index::Error::Git(err)) if is_auth_error(&err) => {
+            return fetch_private_index(registry);
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    loop {
+        match index.update() {
+            Err(crates_index::Error::Git(err)) if is_auth_error(&err) => {
+                return fetch_private_index(registry);
+            }
+            res => {
+                if !need_retry(res)? {
+                    break;
+                }
+            }
+        }
         shell_status("Blocking", "waiting for lock on registry index")?;
         std::thread::sleep(REGISTRY_BACKOFF);
     }
@@ -221,6 +638,148 @@ pub fn update_registry_index(registry: &Url, quiet: bool) -> CargoResult<()> {
     Ok(())
 }
 
+/// How long ago the registry index was last updated
+///
+/// Returns `None` when the index hasn't been downloaded yet.
+pub fn registry_index_age(registry: &Url) -> CargoResult<Option<Duration>> {
+    let (path, _) = crates_index::local_path_and_canonical_url(registry.as_str(), None)?;
+    let fetched = match std::fs::metadata(path.join("FETCH_HEAD")) {
+        Ok(metadata) => metadata.modified()?,
+        Err(_) => return Ok(None),
+    };
+    // Clock skew can put the fetch in the future; treat that as fresh
+    Ok(Some(fetched.elapsed().unwrap_or_default()))
+}
+
+/// Fetch a registry index with the user's git credentials
+///
+/// The index is fetched into the same place `crates_index` looks for it, with an `origin`
+/// remote, so later lookups reuse it instead of trying to clone it anonymously.
+fn fetch_private_index(registry: &Url) -> CargoResult<()> {
+    let (path, url) = crates_index::local_path_and_canonical_url(registry.as_str(), None)?;
+    let repo = match git2::Repository::open(&path) {
+        Ok(repo) => repo,
+        Err(_) => git2::Repository::init_opts(
+            &path,
+            git2::RepositoryInitOptions::new().external_template(false),
+        )?,
+    };
+    let mut remote = repo
+        .find_remote("origin")
+        .or_else(|_| repo.remote("origin", &url))?;
+
+    let config = git2::Config::open_default()?;
+    let mut attempts = CredentialAttempts::default();
+    let mut callbacks = git2::RemoteCallbacks::new();
+    callbacks.credentials(|url, username, allowed| attempts.next(&config, url, username, allowed));
+    let mut options = git2::FetchOptions::new();
+    options.remote_callbacks(callbacks);
+
+    remote
+        .fetch(&["HEAD:refs/remotes/origin/HEAD"], Some(&mut options), None)
+        .map_err(|err| {
+            if is_auth_error(&err) {
+                anyhow::format_err!(
+                    "failed to authenticate with '{}': {}\n\
+                    For SSH, make sure your key is loaded into `ssh-agent`; \
+                    for HTTPS, configure a git credential helper",
+                    registry,
+                    err.message()
+                )
+            } else {
+                err.into()
+            }
+        })
+}
+
+/// The newest tag of a git repository that is a semver version, like `v1.2.3` or `1.2.3`
+///
+/// Prerelease versions are only considered with `flag_allow_prerelease`.  Returns `None` when no
+/// tag is a version.
+pub fn get_latest_git_tag(url: &str, flag_allow_prerelease: bool) -> CargoResult<Option<String>> {
+    let latest = list_git_refs(url)?
+        .into_iter()
+        .filter_map(|(name, _)| name.strip_prefix("refs/tags/").map(str::to_owned))
+        // Annotated tags are listed again, peeled to their commit
+        .filter(|tag| !tag.ends_with("^{}"))
+        .filter_map(|tag| Some((git_tag_version(&tag)?, tag)))
+        .filter(|(version, _)| flag_allow_prerelease || version.pre.is_empty())
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, tag)| tag);
+    Ok(latest)
+}
+
+/// The commit at the head of a git repository's default branch
+pub fn get_latest_git_rev(url: &str) -> CargoResult<String> {
+    list_git_refs(url)?
+        .into_iter()
+        .find(|(name, _)| name == "HEAD")
+        .map(|(_, oid)| oid.to_string())
+        .with_context(|| format!("{} has no default branch", url))
+}
+
+/// The version a git tag names, with or without a leading `v`
+pub fn git_tag_version(tag: &str) -> Option<semver::Version> {
+    tag.strip_prefix('v').unwrap_or(tag).parse().ok()
+}
+
+/// The refs a git repository advertises, like `git ls-remote`, without fetching it
+fn list_git_refs(url: &str) -> CargoResult<Vec<(String, git2::Oid)>> {
+    let mut remote = git2::Remote::create_detached(url)?;
+    let config = git2::Config::open_default()?;
+    let mut attempts = CredentialAttempts::default();
+    let mut callbacks = git2::RemoteCallbacks::new();
+    callbacks.credentials(|url, username, allowed| attempts.next(&config, url, username, allowed));
+    let connection = remote
+        .connect_auth(git2::Direction::Fetch, Some(callbacks), None)
+        .with_context(|| format!("Failed to list the refs of {}", url))?;
+    let refs = connection
+        .list()?
+        .iter()
+        .map(|head| (head.name().to_owned(), head.oid()))
+        .collect();
+    Ok(refs)
+}
+
+fn is_auth_error(err: &git2::Error) -> bool {
+    err.code() == git2::ErrorCode::Auth || err.class() == git2::ErrorClass::Ssh
+}
+
+/// Credentials offered to git, each tried at most once so a rejected one isn't retried forever
+#[derive(Default, Debug)]
+struct CredentialAttempts {
+    ssh_agent: bool,
+    credential_helper: bool,
+    default: bool,
+}
+
+impl CredentialAttempts {
+    fn next(
+        &mut self,
+        config: &git2::Config,
+        url: &str,
+        username: Option<&str>,
+        allowed: git2::CredentialType,
+    ) -> Result<git2::Cred, git2::Error> {
+        if allowed.contains(git2::CredentialType::USERNAME) {
+            return git2::Cred::username(username.unwrap_or("git"));
+        }
+        if allowed.contains(git2::CredentialType::SSH_KEY) && !self.ssh_agent {
+            self.ssh_agent = true;
+            return git2::Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+        if allowed.contains(git2::CredentialType::USER_PASS_PLAINTEXT) && !self.credential_helper {
+            self.credential_helper = true;
+            return git2::Cred::credential_helper(config, url, username);
+        }
+        if allowed.contains(git2::CredentialType::DEFAULT) && !self.default {
+            self.default = true;
+            return git2::Cred::default();
+        }
+        Err(git2::Error::from_str("no more credentials to try"))
+    }
+}
+
 /// Time between retries for retrieving the registry.
 const REGISTRY_BACKOFF: Duration = Duration::from_secs(1);
 
@@ -239,6 +798,19 @@ fn need_retry(res: Result<(), crates_index::Error>) -> CargoResult<bool> {
     }
 }
 
+#[test]
+fn credentials_are_tried_once() {
+    let config = git2::Config::new().unwrap();
+    let mut attempts = CredentialAttempts::default();
+    let allowed = git2::CredentialType::SSH_KEY;
+    assert!(attempts
+        .next(&config, "ssh://git@example.com/index", None, allowed)
+        .is_ok());
+    assert!(attempts
+        .next(&config, "ssh://git@example.com/index", None, allowed)
+        .is_err());
+}
+
 #[test]
 fn test_gen_fuzzy_crate_names() {
     fn test_helper(input: &str, expect: &[&str]) {
@@ -261,6 +833,27 @@ fn test_gen_fuzzy_crate_names() {
     );
 }
 
+#[test]
+fn git_tag_versions() {
+    assert_eq!(
+        git_tag_version("v1.2.3"),
+        Some(semver::Version::new(1, 2, 3))
+    );
+    assert_eq!(
+        git_tag_version("1.2.3"),
+        Some(semver::Version::new(1, 2, 3))
+    );
+    assert_eq!(git_tag_version("release-1.2.3"), None);
+}
+
+#[test]
+fn index_paths() {
+    assert_eq!(index_path("a"), "1/a");
+    assert_eq!(index_path("cc"), "2/cc");
+    assert_eq!(index_path("syn"), "3/s/syn");
+    assert_eq!(index_path("Serde_json"), "se/rd/serde_json");
+}
+
 #[test]
 fn get_latest_stable_version() {
     let versions = vec![
@@ -354,3 +947,26 @@ fn get_no_latest_version_from_json_when_all_are_yanked() {
     ];
     assert!(read_latest_version(&versions, false).is_err());
 }
+
+#[test]
+fn get_compatible_version() {
+    let version = |version: &str, yanked| CrateVersion {
+        name: "foo".into(),
+        version: version.parse().unwrap(),
+        yanked,
+        available_features: BTreeMap::new(),
+    };
+    let versions = vec![
+        version("0.5.0", false),
+        version("0.5.3", false),
+        version("0.5.4", true),
+        version("0.6.0", false),
+    ];
+    let compatible = |req: &str| {
+        read_compatible_version(&versions, &req.parse().unwrap())
+            .map(|d| d.version().unwrap().to_owned())
+    };
+    assert_eq!(compatible("0.5").as_deref(), Some("0.5.3"));
+    assert_eq!(compatible("0.6").as_deref(), Some("0.6.0"));
+    assert_eq!(compatible("0.7"), None);
+}
@@ -0,0 +1,391 @@
+//! Run the user's commands around manifest writes and after runs.
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use super::errors::*;
+use super::manifest::{LocalManifest, Manifest};
+use super::metadata::find_workspace_root;
+use super::report::UpgradeReport;
+use super::util::shell_warn;
+
+/// Environment variable that opts into running the commands manifests configure
+pub(crate) const TRUST_HOOKS_ENV: &str = "CARGO_EDIT_TRUST_HOOKS";
+
+/// Set once configured commands were skipped for lack of [`TRUST_HOOKS_ENV`]
+static WARNED_UNTRUSTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the user opted into running commands from manifests, which a cloned repo controls
+fn hooks_trusted() -> bool {
+    matches!(
+        std::env::var(TRUST_HOOKS_ENV).as_deref(),
+        Ok("1") | Ok("true") | Ok("yes")
+    )
+}
+
+/// Whether to run the `keys` configured for `manifest_path`, warning when they aren't trusted
+fn trust(
+    config: &dyn toml_edit::TableLike,
+    keys: &[&str],
+    manifest_path: &Path,
+) -> CargoResult<bool> {
+    let configured = keys
+        .iter()
+        .filter(|key| config.contains_key(key))
+        .map(|key| format!("`{}`", key))
+        .collect::<Vec<_>>();
+    if configured.is_empty() || hooks_trusted() {
+        return Ok(true);
+    }
+    if !WARNED_UNTRUSTED.swap(true, Ordering::SeqCst) {
+        shell_warn(&format!(
+            "not running {} configured for {}; set {}=1 to trust the manifest's commands",
+            configured.join(" and "),
+            manifest_path.display(),
+            TRUST_HOOKS_ENV
+        ))?;
+    }
+    Ok(false)
+}
+
+/// Commands to run around writing a manifest, from `pre-write` and `post-write` under
+/// `[workspace.metadata.cargo-edit]`, or `[package.metadata.cargo-edit]` outside of a workspace
+///
+/// They only run with [`TRUST_HOOKS_ENV`] set, since any cloned repo can configure them.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct WriteHooks {
+    pre_write: Option<String>,
+    post_write: Option<String>,
+    /// What the write changes, as described to the hooks by [`WriteHooks::pre_write`]
+    changes: Option<WriteChanges>,
+}
+
+/// The package and dependency requirements a write changes, passed to hooks
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct WriteChanges {
+    package: Option<String>,
+    /// `CARGO_EDIT_CHANGES`, a JSON list of [`DependencyChange`]
+    dependencies: String,
+}
+
+/// A dependency whose requirement a write adds, changes or removes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct DependencyChange {
+    /// The table it's in, like `dependencies` or `target.'cfg(unix)'.dev-dependencies`
+    table: String,
+    name: String,
+    old_req: Option<String>,
+    new_req: Option<String>,
+}
+
+impl WriteHooks {
+    /// Read the hooks configured for the manifest at `manifest_path`
+    pub(crate) fn for_manifest(manifest_path: &Path) -> CargoResult<Self> {
+        let mut hooks = Self::default();
+        let config = match config(manifest_path)? {
+            Some(config) => config,
+            None => return Ok(hooks),
+        };
+        let config = config.as_table_like().expect("config is a table");
+        if !trust(config, &["pre-write", "post-write"], manifest_path)? {
+            return Ok(hooks);
+        }
+        for (key, value) in config.iter() {
+            let hook = match key {
+                "pre-write" => &mut hooks.pre_write,
+                "post-write" => &mut hooks.post_write,
+                _ => continue,
+            };
+            let command = value
+                .as_str()
+                .with_context(|| format!("`{}` must be a command", key))?;
+            *hook = Some(command.to_owned());
+        }
+        Ok(hooks)
+    }
+
+    /// Run the `pre-write` hook, with the new contents staged at `staged_path`
+    ///
+    /// The hook may rewrite the staged file; failing aborts the write.
+    pub(crate) fn pre_write(
+        &mut self,
+        manifest_path: &Path,
+        staged_path: &Path,
+    ) -> CargoResult<()> {
+        if self.pre_write.is_none() && self.post_write.is_none() {
+            return Ok(());
+        }
+        let old = std::fs::read_to_string(manifest_path).unwrap_or_default();
+        let new = std::fs::read_to_string(staged_path)?;
+        let changes = self.changes.insert(WriteChanges::new(&old, &new)?);
+        if let Some(hook) = &self.pre_write {
+            let mut cmd = hook_command(hook, "pre-write", manifest_path);
+            changes.apply(&mut cmd);
+            cmd.env("CARGO_EDIT_STAGED_PATH", staged_path);
+            run(cmd, hook, manifest_path)?;
+        }
+        Ok(())
+    }
+
+    /// Run the `post-write` hook, after the manifest was replaced
+    pub(crate) fn post_write(&self, manifest_path: &Path) -> CargoResult<()> {
+        if let Some(hook) = &self.post_write {
+            let mut cmd = hook_command(hook, "post-write", manifest_path);
+            if let Some(changes) = &self.changes {
+                changes.apply(&mut cmd);
+            }
+            if let Err(err) = run(cmd, hook, manifest_path) {
+                // The manifest is already written, so there is nothing left to abort
+                shell_warn(&format!("{:#}", err))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl WriteChanges {
+    /// Compare the dependency requirements of the `old` and `new` manifest contents
+    fn new(old: &str, new: &str) -> CargoResult<Self> {
+        let new_manifest: Manifest = new.parse()?;
+        let package = new_manifest
+            .data
+            .get("package")
+            .and_then(|p| p.get("name"))
+            .and_then(|n| n.as_str())
+            .map(|n| n.to_owned());
+        // A manifest that didn't exist or parse yet had no dependencies
+        let old = old
+            .parse::<Manifest>()
+            .map(|m| requirements(&m))
+            .unwrap_or_default();
+        let new = requirements(&new_manifest);
+        let mut keys = old.keys().chain(new.keys()).collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+        let dependencies = keys
+            .into_iter()
+            .filter(|key| old.get(*key) != new.get(*key))
+            .map(|(table, name)| DependencyChange {
+                table: table.clone(),
+                name: name.clone(),
+                old_req: old.get(&(table.clone(), name.clone())).cloned().flatten(),
+                new_req: new.get(&(table.clone(), name.clone())).cloned().flatten(),
+            })
+            .collect::<Vec<_>>();
+        Ok(Self {
+            package,
+            dependencies: serde_json::to_string(&dependencies)?,
+        })
+    }
+
+    fn apply(&self, cmd: &mut Command) {
+        if let Some(package) = &self.package {
+            cmd.env("CARGO_EDIT_PACKAGE", package);
+        }
+        cmd.env("CARGO_EDIT_CHANGES", &self.dependencies);
+    }
+}
+
+/// Every dependency's version requirement, by table and name
+fn requirements(manifest: &Manifest) -> BTreeMap<(String, String), Option<String>> {
+    let mut sections = manifest
+        .get_sections()
+        .into_iter()
+        .map(|(table, item)| {
+            let name = match table.target() {
+                Some(target) => format!("target.'{}'.{}", target, table.kind_table()),
+                None => table.kind_table().to_owned(),
+            };
+            (name, item)
+        })
+        .collect::<Vec<_>>();
+    if let Some(deps) = manifest
+        .data
+        .get("workspace")
+        .and_then(|w| w.get("dependencies"))
+    {
+        sections.push(("workspace.dependencies".to_owned(), deps.clone()));
+    }
+    let mut requirements = BTreeMap::new();
+    for (table, item) in sections {
+        for (name, dep) in item.as_table_like().into_iter().flat_map(|t| t.iter()) {
+            let req = dep
+                .as_str()
+                .or_else(|| dep.get("version")?.as_str())
+                .map(|r| r.to_owned());
+            requirements.insert((table.clone(), name.to_owned()), req);
+        }
+    }
+    requirements
+}
+
+/// Where to send the report of a completed run, from `notify-command` and `notify-webhook` under
+/// `[workspace.metadata.cargo-edit]`, or `[package.metadata.cargo-edit]` outside of a workspace
+///
+/// Like write hooks, they only run with [`TRUST_HOOKS_ENV`] set.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NotifyHooks {
+    command: Option<String>,
+    webhook: Option<String>,
+}
+
+impl NotifyHooks {
+    /// Read the notifications configured for the manifest at `manifest_path`
+    pub fn for_manifest(manifest_path: &Path) -> CargoResult<Self> {
+        let mut hooks = Self::default();
+        let config = match config(manifest_path)? {
+            Some(config) => config,
+            None => return Ok(hooks),
+        };
+        let config = config.as_table_like().expect("config is a table");
+        if !trust(config, &["notify-command", "notify-webhook"], manifest_path)? {
+            return Ok(hooks);
+        }
+        for (key, value) in config.iter() {
+            let (hook, kind) = match key {
+                "notify-command" => (&mut hooks.command, "command"),
+                "notify-webhook" => (&mut hooks.webhook, "URL"),
+                _ => continue,
+            };
+            let value = value
+                .as_str()
+                .with_context(|| format!("`{}` must be a {}", key, kind))?;
+            *hook = Some(value.to_owned());
+        }
+        Ok(hooks)
+    }
+
+    /// Whether there is anywhere to send reports to
+    pub fn is_empty(&self) -> bool {
+        self.command.is_none() && self.webhook.is_none()
+    }
+
+    /// Pipe the JSON `report` to the command and POST it to the webhook
+    ///
+    /// The run is already over, so failures are only warned about.
+    pub fn notify(&self, report: &UpgradeReport, manifest_path: &Path) -> CargoResult<()> {
+        let json = serde_json::to_string(report)?;
+        if let Some(command) = &self.command {
+            if let Err(err) = notify_command(command, &json, manifest_path) {
+                shell_warn(&format!("{:#}", err))?;
+            }
+        }
+        if let Some(webhook) = &self.webhook {
+            if let Err(err) = notify_webhook(webhook, &json) {
+                shell_warn(&format!("{:#}", err))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn notify_command(command: &str, json: &str, manifest_path: &Path) -> CargoResult<()> {
+    let mut cmd = hook_command(command, "notify", manifest_path);
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run notification command `{}`", command))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    match stdin.write_all(json.as_bytes()) {
+        // Commands don't have to read the report
+        Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => {}
+        result => result?,
+    }
+    drop(stdin);
+    let status = child.wait()?;
+    if !status.success() {
+        anyhow::bail!("Notification command `{}` failed: {}", command, status);
+    }
+    Ok(())
+}
+
+fn notify_webhook(webhook: &str, json: &str) -> CargoResult<()> {
+    let mut agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(30))
+        .user_agent(concat!("cargo-edit/", env!("CARGO_PKG_VERSION")));
+    if let Some(proxy) = env_proxy::for_url_str(webhook).to_string() {
+        agent = agent.proxy(ureq::Proxy::new(proxy)?);
+    }
+    agent
+        .build()
+        .post(webhook)
+        .set("Content-Type", "application/json")
+        .send_string(json)
+        .with_context(|| format!("Failed to notify webhook {}", webhook))?;
+    Ok(())
+}
+
+/// `[workspace.metadata.cargo-edit]`, or `[package.metadata.cargo-edit]` outside of a workspace
+fn config(manifest_path: &Path) -> CargoResult<Option<toml_edit::Item>> {
+    let root = find_workspace_root(manifest_path).unwrap_or_else(|_| manifest_path.to_owned());
+    let root = LocalManifest::try_new(&root)?;
+    let config = ["workspace", "package"].iter().find_map(|table| {
+        root.data
+            .get(table)?
+            .get("metadata")?
+            .get("cargo-edit")
+            .filter(|config| config.is_table_like())
+    });
+    Ok(config.cloned())
+}
+
+/// Run `script` with the platform's shell
+pub fn shell_command(script: &str) -> Command {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(script);
+    cmd
+}
+
+fn hook_command(hook: &str, name: &str, manifest_path: &Path) -> Command {
+    let dir = manifest_path
+        .parent()
+        .expect("manifest is always in a directory");
+    let mut cmd = shell_command(hook);
+    cmd.current_dir(dir)
+        .env("CARGO_EDIT_HOOK", name)
+        .env("CARGO_EDIT_MANIFEST_PATH", manifest_path);
+    cmd
+}
+
+fn run(mut cmd: Command, hook: &str, manifest_path: &Path) -> CargoResult<()> {
+    let status = cmd
+        .status()
+        .with_context(|| format!("Failed to run hook `{}`", hook))?;
+    if !status.success() {
+        anyhow::bail!(
+            "Hook `{}` failed for {}: {}",
+            hook,
+            manifest_path.display(),
+            status
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn changes_list_requirements() {
+        let old = "[package]\nname = \"foo\"\n\n[dependencies]\na = \"1\"\nb = { version = \"2\" }\n\n[target.'cfg(unix)'.dev-dependencies]\nc = \"3\"\n";
+        let new = "[package]\nname = \"foo\"\n\n[dependencies]\na = \"1\"\nb = { version = \"2.1\" }\nd = \"4\"\n";
+        let changes = WriteChanges::new(old, new).unwrap();
+        assert_eq!(changes.package.as_deref(), Some("foo"));
+        assert_eq!(
+            changes.dependencies,
+            r#"[{"table":"dependencies","name":"b","old_req":"2","new_req":"2.1"},{"table":"dependencies","name":"d","old_req":null,"new_req":"4"},{"table":"target.'cfg(unix)'.dev-dependencies","name":"c","old_req":"3","new_req":null}]"#
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! Defer Ctrl-C so a run never stops with only some manifests written.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::errors::*;
+
+static INTERRUPTS: Interrupts = Interrupts::new();
+
+/// Exit code for a process stopped by `SIGINT`
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Install a Ctrl-C handler that defers interrupts to [`check_interrupted`].
+///
+/// A second Ctrl-C exits immediately, unless manifests are being written.
+pub fn catch_interrupts() -> CargoResult<()> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTS.interrupt() {
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    })
+    .context("Failed to install Ctrl-C handler")
+}
+
+/// Fail if the user pressed Ctrl-C since [`catch_interrupts`]
+pub fn check_interrupted() -> CargoResult<()> {
+    INTERRUPTS.check()
+}
+
+/// Hold off interrupts until the returned guard is dropped
+pub fn defer_interrupts() -> InterruptGuard {
+    INTERRUPTS.defer()
+}
+
+/// Interrupts are deferred while this is alive, see [`defer_interrupts`]
+#[derive(Debug)]
+pub struct InterruptGuard {
+    interrupts: &'static Interrupts,
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        self.interrupts.writing.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Whether Ctrl-C was pressed, and whether manifests are being written
+#[derive(Debug)]
+struct Interrupts {
+    interrupted: AtomicBool,
+    writing: AtomicBool,
+}
+
+impl Interrupts {
+    const fn new() -> Self {
+        Self {
+            interrupted: AtomicBool::new(false),
+            writing: AtomicBool::new(false),
+        }
+    }
+
+    /// Record a Ctrl-C, returning whether to exit right away
+    fn interrupt(&self) -> bool {
+        let repeated = self.interrupted.swap(true, Ordering::SeqCst);
+        repeated && !self.writing.load(Ordering::SeqCst)
+    }
+
+    fn check(&self) -> CargoResult<()> {
+        if self.interrupted.load(Ordering::SeqCst) {
+            anyhow::bail!("Interrupted, no manifests were modified");
+        }
+        Ok(())
+    }
+
+    fn defer(&'static self) -> InterruptGuard {
+        self.writing.store(true, Ordering::SeqCst);
+        InterruptGuard { interrupts: self }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn interrupts_wait_for_writes() {
+        let interrupts: &'static Interrupts = Box::leak(Box::new(Interrupts::new()));
+        assert!(interrupts.check().is_ok());
+
+        let guard = interrupts.defer();
+        assert!(!interrupts.interrupt());
+        assert!(!interrupts.interrupt());
+        assert!(interrupts.check().is_err());
+
+        drop(guard);
+        assert!(interrupts.interrupt());
+    }
+}
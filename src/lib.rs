@@ -18,26 +18,69 @@ extern crate serde_derive;
 
 mod crate_spec;
 mod dependency;
+mod dry_run;
 mod errors;
 mod fetch;
+mod hooks;
+mod interrupt;
+mod lockfile;
 mod manifest;
 mod metadata;
+mod read_only;
 mod registry;
+mod report;
+mod selection;
+mod strategy;
+mod target;
+mod transaction;
+mod unstable;
 mod util;
 mod version;
 
 pub use crate_spec::CrateSpec;
 pub use dependency::Dependency;
+pub use dependency::GitSource;
 pub use dependency::PathSource;
 pub use dependency::RegistrySource;
 pub use dependency::Source;
+pub use dry_run::DryRun;
 pub use errors::*;
-pub use fetch::{get_latest_dependency, update_registry_index};
-pub use manifest::{find, get_dep_version, set_dep_version, LocalManifest, Manifest};
-pub use metadata::{manifest_from_pkgid, resolve_manifests, workspace_members};
+pub use fetch::{
+    get_compatible_dependency, get_dependency_for_rust_version, get_dependency_published_before,
+    get_latest_dependency, get_latest_git_rev, get_latest_git_tag, get_minimal_dependency,
+    get_rust_version, git_tag_version, is_crate_indexed, is_version_published,
+    latest_published_version, registry_index_age, update_registry_index,
+};
+pub use hooks::{shell_command, NotifyHooks};
+pub use interrupt::{catch_interrupts, check_interrupted, defer_interrupts, InterruptGuard};
+pub use lockfile::{LockedPackage, Lockfile};
+pub use manifest::{
+    find, get_dep_version, set_dep_git_ref, set_dep_version, DepKind, DepTable, FeatureValue,
+    LocalManifest, Manifest,
+};
+pub use metadata::{
+    find_nested_crates, find_workspace_roots, is_published_manifest, manifest_from_pkgid,
+    manifest_package, workspace_members, CargoFlags,
+};
+pub use read_only::{enforce_read_only, ensure_writable, is_read_only};
 pub use registry::registry_url;
+pub use report::{
+    DependencyReport, PackageReport, ReportReason, TimingsReport, UpgradeReport, WarningReport,
+    REPORT_SCHEMA_VERSION,
+};
+pub use selection::{filter_members, find_workspace_root, resolve_manifests};
+pub use strategy::{
+    ConstrainedVersion, LatestCompatibleVersion, LatestVersion, MinimalVersion, PublishedBefore,
+    VersionQuery, VersionStrategy,
+};
+pub use target::{target_cfgs, Cfg, Platform};
+pub use transaction::ManifestTransaction;
+pub use unstable::{UnstableFlag, UnstableFlags, UnstableOption};
 pub use util::{
-    colorize_stderr, shell_note, shell_print, shell_status, shell_warn, shell_write_stderr, Color,
-    ColorChoice,
+    colorize_stderr, colorize_stdout, crate_glob_match, glob_match, normalize_crate_name,
+    shell_note, shell_print, shell_status, shell_warn, shell_write_stderr, Color, ColorChoice,
+};
+pub use version::{
+    normalize_requirement, package_rust_version, parse_rust_version, upgrade_requirement, ReqStyle,
+    VersionExt,
 };
-pub use version::{upgrade_requirement, VersionExt};
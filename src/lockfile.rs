@@ -0,0 +1,410 @@
+//! Direct `Cargo.lock` access, without running `cargo metadata`.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use super::errors::*;
+use super::selection::find_workspace_root;
+
+const LOCKFILE_FILENAME: &str = "Cargo.lock";
+
+/// A parsed `Cargo.lock`
+#[derive(Debug, Clone, Default)]
+pub struct Lockfile {
+    /// Path to the lockfile, if read from disk
+    pub path: Option<PathBuf>,
+    /// Lockfile format version (absent for v1)
+    pub version: Option<u32>,
+    /// Packages recorded in the lockfile
+    pub packages: Vec<LockedPackage>,
+}
+
+/// A package entry in `Cargo.lock`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockedPackage {
+    /// Package name
+    pub name: String,
+    /// Resolved version
+    pub version: semver::Version,
+    /// Source ID, `None` for path dependencies and workspace members
+    pub source: Option<String>,
+    /// Checksum of the downloaded `.crate`, when the source provides one
+    pub checksum: Option<String>,
+    /// Dependencies, as `name`, `name version` or `name version (source)`
+    pub dependencies: Vec<String>,
+}
+
+impl Lockfile {
+    /// Find the lockfile that applies to `manifest_path`.
+    ///
+    /// This is the `Cargo.lock` of the manifest's workspace root, even when `package.workspace`
+    /// points outside of the manifest's parents, or else the nearest one in the manifest's
+    /// directory or one of its parents.
+    pub fn find(manifest_path: &Path) -> CargoResult<Self> {
+        let path = find_lockfile_path(manifest_path)?;
+        Self::read(&path)
+    }
+
+    /// Read the lockfile at `path`
+    pub fn read(path: &Path) -> CargoResult<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut lockfile: Self = content
+            .parse()
+            .with_context(|| format!("Invalid lockfile {}", path.display()))?;
+        lockfile.path = Some(path.to_owned());
+        Ok(lockfile)
+    }
+
+    /// All locked packages with the given name
+    pub fn packages_named<'s>(&'s self, name: &'s str) -> impl Iterator<Item = &'s LockedPackage> {
+        self.packages.iter().filter(move |p| p.name == name)
+    }
+
+    /// Find the first locked version of `name` matching the requirement
+    pub fn find_version(&self, name: &str, req: &semver::VersionReq) -> Option<&LockedPackage> {
+        self.packages_named(name).find(|p| req.matches(&p.version))
+    }
+
+    /// Packages locked more than once, like `cargo tree --duplicates`, by name
+    pub fn duplicates(&self) -> BTreeMap<&str, Vec<&LockedPackage>> {
+        let mut by_name = BTreeMap::<_, Vec<_>>::new();
+        for package in &self.packages {
+            by_name
+                .entry(package.name.as_str())
+                .or_default()
+                .push(package);
+        }
+        by_name.retain(|_, packages| 1 < packages.len());
+        by_name
+    }
+
+    /// Find the version of `name` matching the requirement that the workspace member `dependent`
+    /// was resolved to use
+    ///
+    /// Several semver-incompatible versions can match a loose requirement, so this falls back to
+    /// [`Lockfile::find_version`] only when `dependent` isn't locked.
+    pub fn find_used_version(
+        &self,
+        dependent: &str,
+        dependent_version: &semver::Version,
+        name: &str,
+        req: &semver::VersionReq,
+    ) -> Option<&LockedPackage> {
+        let dependent = self.packages_named(dependent).find(|p| {
+            // Workspace members are the only packages without a source
+            p.source.is_none() && p.version == *dependent_version
+        });
+        match dependent {
+            Some(dependent) => dependent
+                .dependencies
+                .iter()
+                .filter_map(|dep| self.resolve_dependency(dep))
+                .find(|p| p.name == name && req.matches(&p.version)),
+            None => self.find_version(name, req),
+        }
+    }
+
+    /// Locked packages that depend on `package`
+    pub fn dependents<'s>(
+        &'s self,
+        package: &'s LockedPackage,
+    ) -> impl Iterator<Item = &'s LockedPackage> {
+        self.packages.iter().filter(move |p| {
+            p.dependencies
+                .iter()
+                .any(|dep| self.resolve_dependency(dep) == Some(package))
+        })
+    }
+
+    /// Find the package a `dependencies` entry refers to
+    fn resolve_dependency(&self, dependency: &str) -> Option<&LockedPackage> {
+        // Entries only name the version, and then the source, when they would be ambiguous
+        let mut parts = dependency.splitn(3, ' ');
+        let name = parts.next()?;
+        let version = parts.next();
+        let source = parts
+            .next()
+            .map(|source| source.trim_start_matches('(').trim_end_matches(')'));
+        self.packages_named(name).find(|p| {
+            version.map_or(true, |version| p.version.to_string() == version)
+                && source.map_or(true, |source| p.source.as_deref() == Some(source))
+        })
+    }
+}
+
+impl std::str::FromStr for Lockfile {
+    type Err = Error;
+
+    fn from_str(input: &str) -> CargoResult<Self> {
+        let raw = toml_edit::easy::from_str::<RawLockfile>(input)?;
+        let mut packages = raw
+            .package
+            .into_iter()
+            .map(|p| {
+                Ok(LockedPackage {
+                    version: p.version.parse().with_context(|| {
+                        format!("Invalid version `{}` for `{}`", p.version, p.name)
+                    })?,
+                    name: p.name,
+                    source: p.source,
+                    checksum: p.checksum,
+                    dependencies: p.dependencies,
+                })
+            })
+            .collect::<CargoResult<Vec<_>>>()?;
+
+        // v1 lockfiles store checksums in a separate table, keyed by
+        // `checksum <name> <version> (<source>)`
+        for (key, checksum) in raw.metadata {
+            let mut parts = key.splitn(3, ' ');
+            if parts.next() != Some("checksum") {
+                continue;
+            }
+            let (name, version, source) = match (parts.next(), parts.next()) {
+                (Some(name), Some(rest)) => {
+                    let (version, source) = rest.split_once(' ').unwrap_or((rest, ""));
+                    let source = source.trim_start_matches('(').trim_end_matches(')');
+                    (name, version, source)
+                }
+                _ => continue,
+            };
+            if checksum == "<none>" {
+                continue;
+            }
+            if let Some(package) = packages.iter_mut().find(|p| {
+                p.name == name
+                    && p.version.to_string() == version
+                    && p.source.as_deref() == Some(source)
+            }) {
+                package.checksum.get_or_insert(checksum);
+            }
+        }
+
+        Ok(Self {
+            path: None,
+            version: raw.version,
+            packages,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawLockfile {
+    version: Option<u32>,
+    #[serde(default)]
+    package: Vec<RawPackage>,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawPackage {
+    name: String,
+    version: String,
+    source: Option<String>,
+    checksum: Option<String>,
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+/// Find the Cargo.lock of the manifest's workspace, which is kept next to the workspace root.
+///
+/// Packages can point to a root outside of their parent directories with `package.workspace`, so
+/// the root is looked up first.  Otherwise, this is the nearest Cargo.lock next to the manifest or
+/// up the tree.
+pub(crate) fn find_lockfile_path(manifest_path: &Path) -> CargoResult<PathBuf> {
+    if let Ok(root) = find_workspace_root(manifest_path) {
+        let lockfile = root
+            .parent()
+            .expect("there must be a parent directory")
+            .join(LOCKFILE_FILENAME);
+        if lockfile.is_file() {
+            return Ok(lockfile);
+        }
+    }
+    let dir = manifest_path
+        .parent()
+        .expect("there must be a parent directory");
+    for path in dir.ancestors() {
+        let lockfile = path.join(LOCKFILE_FILENAME);
+        if lockfile.is_file() {
+            return Ok(lockfile);
+        }
+    }
+    anyhow::bail!("Unable to find Cargo.lock for {}", manifest_path.display());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_v3() {
+        let lockfile: Lockfile = r#"
+# This file is automatically @generated by Cargo.
+# It is not intended for manual editing.
+version = 3
+
+[[package]]
+name = "foo"
+version = "0.1.0"
+dependencies = [
+ "serde",
+]
+
+[[package]]
+name = "serde"
+version = "1.0.140"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "fc855a42c7967b7c369eb5860f7164ef1f6f81c20c7cc1141f2a604e18723b03"
+"#
+        .parse()
+        .unwrap();
+
+        assert_eq!(lockfile.version, Some(3));
+        assert_eq!(lockfile.packages.len(), 2);
+        let foo = lockfile.packages_named("foo").next().unwrap();
+        assert_eq!(foo.source, None);
+        assert_eq!(foo.dependencies, vec!["serde".to_owned()]);
+        let req = semver::VersionReq::parse("1.0").unwrap();
+        let serde = lockfile.find_version("serde", &req).unwrap();
+        assert_eq!(serde.version, semver::Version::new(1, 0, 140));
+        assert!(serde.checksum.is_some());
+    }
+
+    #[test]
+    fn found_next_to_pointed_root() {
+        use assert_fs::prelude::*;
+
+        let root = assert_fs::TempDir::new().unwrap();
+        root.child("ws/Cargo.toml")
+            .write_str("[workspace]\nmembers = [\"../pkg\"]\n")
+            .unwrap();
+        root.child("ws/Cargo.lock")
+            .write_str("version = 3\n")
+            .unwrap();
+        root.child("pkg/Cargo.toml")
+            .write_str("[package]\nname = \"pkg\"\nversion = \"0.1.0\"\nworkspace = \"../ws\"\n")
+            .unwrap();
+        let dir = dunce::canonicalize(root.path()).unwrap();
+
+        let path = find_lockfile_path(&dir.join("pkg/Cargo.toml")).unwrap();
+        assert_eq!(path, dir.join("ws/Cargo.lock"));
+    }
+
+    #[test]
+    fn parse_v1_checksums() {
+        let lockfile: Lockfile = r#"
+[[package]]
+name = "serde"
+version = "1.0.140"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[metadata]
+"checksum serde 1.0.140 (registry+https://github.com/rust-lang/crates.io-index)" = "fc855a42"
+"#
+        .parse()
+        .unwrap();
+
+        assert_eq!(lockfile.version, None);
+        let serde = lockfile.packages_named("serde").next().unwrap();
+        assert_eq!(serde.checksum.as_deref(), Some("fc855a42"));
+    }
+
+    #[test]
+    fn duplicates_and_dependents() {
+        let lockfile: Lockfile = r#"
+version = 3
+
+[[package]]
+name = "foo"
+version = "0.1.0"
+dependencies = [
+ "bar",
+ "serde 1.0.140",
+]
+
+[[package]]
+name = "bar"
+version = "0.1.0"
+dependencies = [
+ "serde 0.9.0",
+]
+
+[[package]]
+name = "serde"
+version = "0.9.0"
+
+[[package]]
+name = "serde"
+version = "1.0.140"
+"#
+        .parse()
+        .unwrap();
+
+        let duplicates = lockfile.duplicates();
+        assert_eq!(duplicates.keys().collect::<Vec<_>>(), vec![&"serde"]);
+        let dependents = |package| {
+            lockfile
+                .dependents(package)
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(dependents(duplicates["serde"][0]), vec!["bar"]);
+        assert_eq!(dependents(duplicates["serde"][1]), vec!["foo"]);
+        let bar = lockfile.packages_named("bar").next().unwrap();
+        assert_eq!(dependents(bar), vec!["foo"]);
+    }
+
+    #[test]
+    fn used_version() {
+        let lockfile: Lockfile = r#"
+version = 3
+
+[[package]]
+name = "foo"
+version = "0.1.0"
+dependencies = [
+ "serde 1.0.140",
+]
+
+[[package]]
+name = "serde"
+version = "1.0.100"
+
+[[package]]
+name = "serde"
+version = "1.0.140"
+"#
+        .parse()
+        .unwrap();
+
+        let req = semver::VersionReq::parse(">=1.0").unwrap();
+        let foo = semver::Version::new(0, 1, 0);
+        let used = lockfile
+            .find_used_version("foo", &foo, "serde", &req)
+            .unwrap();
+        assert_eq!(used.version, semver::Version::new(1, 0, 140));
+        let bar = semver::Version::new(0, 2, 0);
+        let first = lockfile
+            .find_used_version("bar", &bar, "serde", &req)
+            .unwrap();
+        assert_eq!(first.version, semver::Version::new(1, 0, 100));
+    }
+
+    #[test]
+    fn no_match_outside_req() {
+        let lockfile: Lockfile = r#"
+version = 3
+
+[[package]]
+name = "serde"
+version = "0.9.0"
+"#
+        .parse()
+        .unwrap();
+
+        let req = semver::VersionReq::parse("1.0").unwrap();
+        assert!(lockfile.find_version("serde", &req).is_none());
+    }
+}
@@ -1,4 +1,6 @@
+use std::collections::BTreeMap;
 use std::fs;
+use std::io::Read;
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
 use std::{env, str};
@@ -6,12 +8,18 @@ use std::{env, str};
 use semver::Version;
 
 use super::errors::*;
+use super::hooks::WriteHooks;
 use super::metadata::find_manifest_path;
+use super::read_only::ensure_writable;
 
+/// Kind of dependency, by the table it is declared in
 #[derive(PartialEq, Eq, Hash, Ord, PartialOrd, Clone, Debug, Copy)]
 pub enum DepKind {
+    /// `[dependencies]`
     Normal,
+    /// `[dev-dependencies]`
     Development,
+    /// `[build-dependencies]`
     Build,
 }
 
@@ -49,7 +57,40 @@ impl DepTable {
         self
     }
 
-    fn kind_table(&self) -> &str {
+    /// Type of dependency
+    pub fn kind(&self) -> DepKind {
+        self.kind
+    }
+
+    /// Platform for the dependency, if any
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    /// Platform for the dependency, if any, parsed as a target triple or `cfg(..)` expression
+    pub fn platform(&self) -> CargoResult<Option<cargo_platform::Platform>> {
+        self.target
+            .as_deref()
+            .map(|target| {
+                target
+                    .parse()
+                    .with_context(|| format!("Invalid target `{}`", target))
+            })
+            .transpose()
+    }
+
+    /// Whether the dependencies apply when building for the `target` triple
+    ///
+    /// `cfgs` are the target's configuration, see [`target_cfgs`][crate::target_cfgs].
+    pub fn matches_target(&self, target: &str, cfgs: &[cargo_platform::Cfg]) -> CargoResult<bool> {
+        Ok(self
+            .platform()?
+            .map(|platform| platform.matches(target, cfgs))
+            .unwrap_or(true))
+    }
+
+    /// Name of the table within its parent, like `dev-dependencies`
+    pub fn kind_table(&self) -> &str {
         match self.kind {
             DepKind::Normal => "dependencies",
             DepKind::Development => "dev-dependencies",
@@ -78,6 +119,28 @@ pub struct Manifest {
 }
 
 impl Manifest {
+    /// Top-level keys this version doesn't know, like tables from a newer cargo
+    ///
+    /// They are kept as they are, only the known sections are edited.
+    pub fn unknown_keys(&self) -> Vec<&str> {
+        self.data
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| !KNOWN_KEYS.contains(key))
+            .collect()
+    }
+
+    /// Unstable features the manifest opts into with `cargo-features`
+    pub fn cargo_features(&self) -> Vec<&str> {
+        self.data
+            .get("cargo-features")
+            .and_then(|f| f.as_array())
+            .into_iter()
+            .flat_map(|f| f.iter())
+            .filter_map(|f| f.as_str())
+            .collect()
+    }
+
     /// Get the specified table from the manifest.
     ///
     /// If there is no table at the specified path, then a non-existent table
@@ -91,7 +154,7 @@ impl Manifest {
 
     /// Get all sections in the manifest that exist and might contain dependencies.
     /// The returned items are always `Table` or `InlineTable`.
-    pub(crate) fn get_sections(&self) -> Vec<(DepTable, toml_edit::Item)> {
+    pub fn get_sections(&self) -> Vec<(DepTable, toml_edit::Item)> {
         let mut sections = Vec::new();
 
         for table in DepTable::KINDS {
@@ -182,6 +245,31 @@ impl std::fmt::Display for Manifest {
     }
 }
 
+/// Top-level keys of the manifest format, as of the cargo this version was written against
+const KNOWN_KEYS: &[&str] = &[
+    "cargo-features",
+    "package",
+    "project",
+    "lib",
+    "bin",
+    "example",
+    "test",
+    "bench",
+    "dependencies",
+    "dev-dependencies",
+    "dev_dependencies",
+    "build-dependencies",
+    "build_dependencies",
+    "target",
+    "features",
+    "badges",
+    "patch",
+    "replace",
+    "profile",
+    "workspace",
+    "lints",
+];
+
 /// A Cargo manifest that is available locally.
 #[derive(Debug)]
 pub struct LocalManifest {
@@ -227,8 +315,60 @@ impl LocalManifest {
         })
     }
 
-    /// Write changes back to the file
+    /// Construct a `LocalManifest` from contents piped to stdin, standing in for the manifest in
+    /// the current directory.
+    pub fn from_stdin() -> CargoResult<Self> {
+        let mut data = String::new();
+        std::io::stdin()
+            .read_to_string(&mut data)
+            .with_context(|| "Failed to read manifest contents from stdin")?;
+        let manifest = data.parse().context("Unable to parse Cargo.toml")?;
+        Ok(LocalManifest {
+            manifest,
+            path: env::current_dir()?.join("Cargo.toml"),
+        })
+    }
+
+    /// Warning about the keys and `cargo-features` this version doesn't know, if the manifest
+    /// uses any
+    ///
+    /// They are kept as they are, so commands only need to let the user know when writing it.
+    pub fn future_keys_warning(&self) -> Option<String> {
+        let mut uses = self
+            .unknown_keys()
+            .iter()
+            .map(|key| format!("`{}`", key))
+            .collect::<Vec<_>>();
+        uses.extend(
+            self.cargo_features()
+                .iter()
+                .map(|f| format!("`cargo-features = [\"{}\"]`", f)),
+        );
+        if uses.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "{} uses {}, which this version of cargo-edit doesn't know; they are kept as they are",
+            self.path.display(),
+            uses.join(", ")
+        ))
+    }
+
+    /// Write changes back to the file, running any configured write hooks
     pub fn write(&self) -> CargoResult<()> {
+        self.check_writable()?;
+        let mut hooks = WriteHooks::for_manifest(&self.path)?;
+        let temp_path = self.stage()?;
+        if let Err(err) = hooks.pre_write(&self.path, &temp_path) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(err);
+        }
+        commit_staged(&temp_path, &self.path)?;
+        hooks.post_write(&self.path)
+    }
+
+    /// Ensure the manifest is one we may write back
+    pub(crate) fn check_writable(&self) -> CargoResult<()> {
         if !self.manifest.data.contains_key("package")
             && !self.manifest.data.contains_key("project")
         {
@@ -245,11 +385,35 @@ impl LocalManifest {
                 );
             }
         }
+        Ok(())
+    }
 
+    /// Write the new contents next to the manifest, returning the temporary file's path.
+    ///
+    /// The manifest itself is only replaced by [`commit_staged`], so readers never see a
+    /// partially written file.
+    pub(crate) fn stage(&self) -> CargoResult<PathBuf> {
+        ensure_writable(&self.path)?;
         let s = self.manifest.data.to_string();
         let new_contents_bytes = s.as_bytes();
 
-        std::fs::write(&self.path, new_contents_bytes).context("Failed to write updated Cargo.toml")
+        // Stage next to the file a symlinked manifest points to, so replacing it keeps the link
+        let target = resolve_symlinks(&self.path);
+        let file_name = target
+            .file_name()
+            .expect("manifest paths end in a file name")
+            .to_string_lossy();
+        let temp_path = target.with_file_name(format!(".{}.tmp", file_name));
+        fs::write(&temp_path, new_contents_bytes).context("Failed to write updated Cargo.toml")?;
+        if let Ok(metadata) = fs::metadata(&target) {
+            if let Err(err) = fs::set_permissions(&temp_path, metadata.permissions()) {
+                let _ = fs::remove_file(&temp_path);
+                return Err(err).with_context(|| {
+                    format!("Failed to keep the permissions of {}", self.path.display())
+                });
+            }
+        }
+        Ok(temp_path)
     }
 
     /// Remove entry from a Cargo.toml.
@@ -272,51 +436,100 @@ impl LocalManifest {
     ///   assert!(!manifest.data.contains_key("dependencies"));
     /// ```
     pub fn remove_from_table(&mut self, table_path: &[String], name: &str) -> CargoResult<()> {
+        self.remove_from_table_keeping_empty(table_path, name)?;
+        self.prune_empty_table(table_path);
+        Ok(())
+    }
+
+    /// Remove entry from a Cargo.toml, leaving its table behind even when it ends up empty.
+    pub fn remove_from_table_keeping_empty(
+        &mut self,
+        table_path: &[String],
+        name: &str,
+    ) -> CargoResult<()> {
         let parent_table = self.get_table_mut(table_path)?;
+        let dep = parent_table
+            .get_mut(name)
+            .filter(|t| !t.is_none())
+            .ok_or_else(|| non_existent_dependency_err(name, table_path.join(".")))?;
+        *dep = toml_edit::Item::None;
+        Ok(())
+    }
 
-        {
-            let dep = parent_table
-                .get_mut(name)
-                .filter(|t| !t.is_none())
-                .ok_or_else(|| non_existent_dependency_err(name, table_path.join(".")))?;
-            // remove the dependency
-            *dep = toml_edit::Item::None;
+    /// Remove the table at `table_path` if it's empty, along with the `[target.<cfg>]` tables it
+    /// leaves empty and the blank lines it leaves at the end of the file
+    fn prune_empty_table(&mut self, table_path: &[String]) {
+        // `[workspace]` and the like mean something even when empty
+        let prunable = if table_path.first().map(String::as_str) == Some("target") {
+            1
+        } else {
+            table_path.len()
+        };
+        let mut pruned = false;
+        for depth in (prunable..=table_path.len()).rev() {
+            let table = table_path[..depth]
+                .iter()
+                .try_fold(self.data.as_item_mut(), |item, key| {
+                    item.get_mut(key.as_str())
+                });
+            match table {
+                Some(table) if table.as_table_like().map_or(false, |t| t.is_empty()) => {
+                    *table = toml_edit::Item::None;
+                    pruned = true;
+                }
+                _ => break,
+            }
         }
-
-        // remove table if empty
-        if parent_table.as_table_like().unwrap().is_empty() {
-            *parent_table = toml_edit::Item::None;
+        if pruned && self.data.trailing().trim().is_empty() {
+            self.data.set_trailing("");
         }
-
-        Ok(())
     }
 
     /// Allow mutating depedencies, wherever they live
     pub fn get_dependency_tables_mut<'r>(
         &'r mut self,
     ) -> impl Iterator<Item = &mut dyn toml_edit::TableLike> + 'r {
+        self.get_dependency_kind_tables_mut()
+            .map(|(_, table)| table)
+    }
+
+    /// Allow mutating depedencies, wherever they live, along with the kind of each table
+    pub fn get_dependency_kind_tables_mut<'r>(
+        &'r mut self,
+    ) -> impl Iterator<Item = (DepKind, &mut dyn toml_edit::TableLike)> + 'r {
+        self.get_dependency_sections_mut()
+            .map(|(section, table)| (section.kind(), table))
+    }
+
+    /// Allow mutating depedencies, wherever they live, along with the table each is declared in
+    pub fn get_dependency_sections_mut<'r>(
+        &'r mut self,
+    ) -> impl Iterator<Item = (DepTable, &mut dyn toml_edit::TableLike)> + 'r {
+        fn section_of(key: &str) -> Option<DepTable> {
+            DepTable::KINDS
+                .iter()
+                .find(|kind| kind.kind_table() == key)
+                .cloned()
+        }
+
         let root = self.data.as_table_mut();
         root.iter_mut().flat_map(|(k, v)| {
-            if DepTable::KINDS
-                .iter()
-                .any(|kind| kind.kind_table() == k.get())
-            {
-                v.as_table_like_mut().into_iter().collect::<Vec<_>>()
+            if let Some(section) = section_of(k.get()) {
+                v.as_table_like_mut()
+                    .map(|t| (section, t))
+                    .into_iter()
+                    .collect::<Vec<_>>()
             } else if k == "target" {
                 v.as_table_like_mut()
-                    .unwrap()
-                    .iter_mut()
-                    .flat_map(|(_, v)| {
-                        v.as_table_like_mut().into_iter().flat_map(|v| {
-                            v.iter_mut().filter_map(|(k, v)| {
-                                if DepTable::KINDS
-                                    .iter()
-                                    .any(|kind| kind.kind_table() == k.get())
-                                {
-                                    v.as_table_like_mut()
-                                } else {
-                                    None
-                                }
+                    .into_iter()
+                    .flat_map(|v| v.iter_mut())
+                    .flat_map(|(target, v)| {
+                        let target = target.get().to_owned();
+                        v.as_table_like_mut().into_iter().flat_map(move |v| {
+                            let target = target.clone();
+                            v.iter_mut().filter_map(move |(k, v)| {
+                                let section = section_of(k.get())?.set_target(target.clone());
+                                v.as_table_like_mut().map(|t| (section, t))
                             })
                         })
                     })
@@ -329,7 +542,52 @@ impl LocalManifest {
 
     /// Override the manifest's version
     pub fn set_package_version(&mut self, version: &Version) {
-        self.data["package"]["version"] = toml_edit::value(version.to_string());
+        let package = self.package_key();
+        self.data[package]["version"] = toml_edit::value(version.to_string());
+    }
+
+    /// Name of the package's table, which old manifests call `[project]`
+    pub fn package_key(&self) -> &'static str {
+        if !self.data.contains_key("package") && self.data.contains_key("project") {
+            "project"
+        } else {
+            "package"
+        }
+    }
+
+    /// Features declared in `[features]`, with the values each one activates
+    pub fn features(&self) -> BTreeMap<String, Vec<String>> {
+        self.data
+            .get("features")
+            .and_then(|f| f.as_table_like())
+            .into_iter()
+            .flat_map(|f| f.iter())
+            .map(|(name, values)| {
+                let values = values
+                    .as_array()
+                    .into_iter()
+                    .flat_map(|a| a.iter())
+                    .filter_map(|v| v.as_str())
+                    .map(|v| v.to_owned())
+                    .collect();
+                (name.to_owned(), values)
+            })
+            .collect()
+    }
+
+    /// Keys cargo still accepts in their deprecated spelling, like `dev_dependencies` or
+    /// `default_features`, as dotted paths along with their modern spelling
+    pub fn deprecated_keys(&self) -> Vec<(String, &'static str)> {
+        let mut data = self.data.clone();
+        visit_deprecated_keys(data.as_table_mut(), false)
+    }
+
+    /// Rename the keys [`LocalManifest::deprecated_keys`] reports, keeping their place among the
+    /// other keys, and return the ones renamed
+    ///
+    /// Keys whose modern spelling is also present are left for the user to merge.
+    pub fn fix_deprecated_keys(&mut self) -> Vec<(String, &'static str)> {
+        visit_deprecated_keys(self.data.as_table_mut(), true)
     }
 
     /// Remove references to `dep_key` if its no longer present
@@ -370,6 +628,114 @@ impl LocalManifest {
     }
 }
 
+/// Deprecated dependency sections, with their modern spelling
+const DEPRECATED_SECTIONS: &[(&str, &str)] = &[
+    ("dev_dependencies", "dev-dependencies"),
+    ("build_dependencies", "build-dependencies"),
+];
+
+/// Deprecated keys of a dependency, with their modern spelling
+const DEPRECATED_DEPENDENCY_KEYS: &[(&str, &str)] = &[("default_features", "default-features")];
+
+/// Deprecated keys of `[lib]`, with their modern spelling
+const DEPRECATED_LIB_KEYS: &[(&str, &str)] =
+    &[("crate_type", "crate-type"), ("proc_macro", "proc-macro")];
+
+/// Deprecated keys beneath `root`, or with `fix`, the ones renamed
+fn visit_deprecated_keys(root: &mut toml_edit::Table, fix: bool) -> Vec<(String, &'static str)> {
+    let mut found = Vec::new();
+    visit_dependency_sections(root, "", fix, &mut found);
+    if let Some(platforms) = root.get_mut("target").and_then(|t| t.as_table_like_mut()) {
+        for (platform, item) in platforms.iter_mut() {
+            if let Some(table) = item.as_table_like_mut() {
+                let prefix = format!("target.{}.", platform.get());
+                visit_dependency_sections(table, &prefix, fix, &mut found);
+            }
+        }
+    }
+    if let Some(deps) = root
+        .get_mut("workspace")
+        .and_then(|w| w.get_mut("dependencies"))
+        .and_then(|d| d.as_table_like_mut())
+    {
+        visit_dependencies(deps, "workspace.dependencies.", fix, &mut found);
+    }
+    if let Some(lib) = root.get_mut("lib").and_then(|l| l.as_table_like_mut()) {
+        visit_keys(lib, "lib.", DEPRECATED_LIB_KEYS, fix, &mut found);
+    }
+    found
+}
+
+/// The dependency sections of the manifest or one of its `[target]` tables, and their entries
+fn visit_dependency_sections(
+    table: &mut dyn toml_edit::TableLike,
+    prefix: &str,
+    fix: bool,
+    found: &mut Vec<(String, &'static str)>,
+) {
+    visit_keys(table, prefix, DEPRECATED_SECTIONS, fix, found);
+    for (key, item) in table.iter_mut() {
+        let section = DepTable::KINDS
+            .iter()
+            .map(|kind| kind.kind_table())
+            .chain(DEPRECATED_SECTIONS.iter().map(|(old, _)| *old))
+            .any(|section| section == key.get());
+        if let Some(deps) = item.as_table_like_mut().filter(|_| section) {
+            let prefix = format!("{}{}.", prefix, key.get());
+            visit_dependencies(deps, &prefix, fix, found);
+        }
+    }
+}
+
+fn visit_dependencies(
+    deps: &mut dyn toml_edit::TableLike,
+    prefix: &str,
+    fix: bool,
+    found: &mut Vec<(String, &'static str)>,
+) {
+    for (name, dep) in deps.iter_mut() {
+        if let Some(dep) = dep.as_table_like_mut() {
+            let prefix = format!("{}{}.", prefix, name.get());
+            visit_keys(dep, &prefix, DEPRECATED_DEPENDENCY_KEYS, fix, found);
+        }
+    }
+}
+
+fn visit_keys(
+    table: &mut dyn toml_edit::TableLike,
+    prefix: &str,
+    keys: &[(&str, &'static str)],
+    fix: bool,
+    found: &mut Vec<(String, &'static str)>,
+) {
+    for (old, new) in keys {
+        if !table.contains_key(old) {
+            continue;
+        }
+        if !fix {
+            found.push((format!("{}{}", prefix, old), *new));
+        } else if !table.contains_key(new) {
+            rename_key(table, old, new);
+            found.push((format!("{}{}", prefix, old), *new));
+        }
+    }
+}
+
+/// Rename `old` to `new`, moving the keys after it back behind it to keep its place
+fn rename_key(table: &mut dyn toml_edit::TableLike, old: &str, new: &str) {
+    let keys = table
+        .iter()
+        .map(|(key, _)| key.to_owned())
+        .skip_while(|key| key != old)
+        .collect::<Vec<_>>();
+    for key in keys {
+        if let Some(item) = table.remove(&key) {
+            let key = if key == old { new } else { key.as_str() };
+            table.insert(key, item);
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 enum FeatureStatus {
     None,
@@ -377,30 +743,84 @@ enum FeatureStatus {
     Feature,
 }
 
+/// A value in a `[features]` table
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FeatureValue<'a> {
+    /// `feature`, another feature or an optional dependency
+    Feature(&'a str),
+    /// `dep:name`, an optional dependency without its implicit feature
+    Dep(&'a str),
+    /// `name/feature` or `name?/feature`, a feature of a dependency
+    DepFeature {
+        /// Dependency the feature belongs to
+        dep: &'a str,
+        /// Feature to activate on the dependency
+        feature: &'a str,
+        /// `name?/feature`, which doesn't activate an optional dependency by itself
+        weak: bool,
+    },
+}
+
+impl<'a> FeatureValue<'a> {
+    /// Interpret a value from a feature's list
+    pub fn parse(value: &'a str) -> Self {
+        if let Some(dep) = value.strip_prefix("dep:") {
+            Self::Dep(dep)
+        } else if let Some((dep, feature)) = value.split_once('/') {
+            match dep.strip_suffix('?') {
+                Some(dep) => Self::DepFeature {
+                    dep,
+                    feature,
+                    weak: true,
+                },
+                None => Self::DepFeature {
+                    dep,
+                    feature,
+                    weak: false,
+                },
+            }
+        } else {
+            Self::Feature(value)
+        }
+    }
+}
+
 fn remove_feature_activation(
     feature_activations: &mut toml_edit::Array,
     dep: &str,
     status: FeatureStatus,
 ) {
-    let dep_feature: &str = &format!("{}/", dep);
-
-    let remove_list: Vec<usize> = feature_activations
-        .iter()
-        .enumerate()
-        .filter_map(|(idx, feature_activation)| {
-            if let toml_edit::Value::String(feature_activation) = feature_activation {
-                let activation = feature_activation.value();
-                match status {
-                    FeatureStatus::None => activation == dep || activation.starts_with(dep_feature),
-                    FeatureStatus::DepFeature => activation == dep,
-                    FeatureStatus::Feature => false,
-                }
-                .then(|| idx)
-            } else {
-                None
+    let mut remove_list = Vec::new();
+    for (idx, feature_activation) in feature_activations.iter_mut().enumerate() {
+        let activation = match feature_activation {
+            toml_edit::Value::String(activation) => activation.value().to_owned(),
+            _ => continue,
+        };
+        match (FeatureValue::parse(&activation), status) {
+            (_, FeatureStatus::Feature) => {}
+            (FeatureValue::Feature(name) | FeatureValue::Dep(name), _) if name == dep => {
+                remove_list.push(idx);
             }
-        })
-        .collect();
+            (FeatureValue::DepFeature { dep: name, .. }, FeatureStatus::None) if name == dep => {
+                remove_list.push(idx);
+            }
+            // Only optional dependencies can be weakly activated
+            (
+                FeatureValue::DepFeature {
+                    dep: name,
+                    feature,
+                    weak: true,
+                },
+                FeatureStatus::DepFeature,
+            ) if name == dep => {
+                let decor = feature_activation.decor().clone();
+                let mut strong = toml_edit::Value::from(format!("{}/{}", name, feature));
+                *strong.decor_mut() = decor;
+                *feature_activation = strong;
+            }
+            _ => {}
+        }
+    }
 
     // Remove found idx in revers order so we don't invalidate the idx.
     for idx in remove_list.iter().rev() {
@@ -408,6 +828,18 @@ fn remove_feature_activation(
     }
 }
 
+/// Replace `path` with a file written by [`LocalManifest::stage`]
+pub(crate) fn commit_staged(temp_path: &Path, path: &Path) -> CargoResult<()> {
+    ensure_writable(path)?;
+    fs::rename(temp_path, resolve_symlinks(path))
+        .with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+/// The file `path` links to, or `path` itself when it isn't a symlink
+fn resolve_symlinks(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
 /// If a manifest is specified, return that one, otherise perform a manifest search starting from
 /// the current directory.
 /// If a manifest is specified, return that one. If a path is specified, perform a manifest search
@@ -460,6 +892,20 @@ pub fn set_dep_version(dep_item: &mut toml_edit::Item, new_version: &str) -> Car
     Ok(())
 }
 
+/// Set the `tag` or `rev` of a git dependency, keeping its formatting
+pub fn set_dep_git_ref(
+    dep_item: &mut toml_edit::Item,
+    key: &str,
+    new_ref: &str,
+) -> CargoResult<()> {
+    let item = dep_item
+        .as_table_like_mut()
+        .and_then(|table| table.get_mut(key))
+        .ok_or_else(|| anyhow::format_err!("Missing {} field", key))?;
+    overwrite_value(item, new_ref);
+    Ok(())
+}
+
 /// Overwrite a value while preserving the original formatting
 fn overwrite_value(item: &mut toml_edit::Item, value: impl Into<toml_edit::Value>) {
     let mut value = value.into();
@@ -1,12 +1,61 @@
 use super::errors::*;
+use super::read_only::is_read_only;
+use super::selection::find_workspace_root;
+use super::{DepKind, Dependency, LocalManifest};
 use cargo_metadata::Package;
 use std::convert::TryInto;
 use std::path::Path;
 
+/// Flags forwarded to `cargo` when it is run on the user's behalf
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CargoFlags {
+    /// Run without accessing the network
+    pub offline: bool,
+    /// Require `Cargo.lock` to be up to date
+    pub locked: bool,
+    /// Require `Cargo.lock` and cache are up to date
+    pub frozen: bool,
+}
+
+impl CargoFlags {
+    /// Whether the network may be accessed
+    pub fn is_offline(&self) -> bool {
+        self.offline || self.frozen
+    }
+
+    /// Whether `Cargo.lock` may be modified
+    pub fn is_locked(&self) -> bool {
+        self.locked || self.frozen
+    }
+
+    /// Forward the flags to a `cargo metadata` invocation
+    ///
+    /// While running read-only, `cargo` isn't allowed to update `Cargo.lock` either.
+    pub fn apply(&self, cmd: &mut cargo_metadata::MetadataCommand) {
+        let mut other = Vec::new();
+        if self.frozen {
+            other.push("--frozen".to_owned());
+        } else {
+            if self.locked || is_read_only() {
+                other.push("--locked".to_owned());
+            }
+            if self.offline {
+                other.push("--offline".to_owned());
+            }
+        }
+        cmd.other_options(other);
+    }
+}
+
 /// Takes a pkgid and attempts to find the path to it's `Cargo.toml`, using `cargo`'s metadata
-pub fn manifest_from_pkgid(manifest_path: Option<&Path>, pkgid: &str) -> CargoResult<Package> {
+pub fn manifest_from_pkgid(
+    manifest_path: Option<&Path>,
+    pkgid: &str,
+    flags: CargoFlags,
+) -> CargoResult<Package> {
     let mut cmd = cargo_metadata::MetadataCommand::new();
     cmd.no_deps();
+    flags.apply(&mut cmd);
     if let Some(manifest_path) = manifest_path {
         cmd.manifest_path(manifest_path);
     }
@@ -23,9 +72,13 @@ pub fn manifest_from_pkgid(manifest_path: Option<&Path>, pkgid: &str) -> CargoRe
 }
 
 /// Lookup all members of the current workspace
-pub fn workspace_members(manifest_path: Option<&Path>) -> CargoResult<Vec<Package>> {
+pub fn workspace_members(
+    manifest_path: Option<&Path>,
+    flags: CargoFlags,
+) -> CargoResult<Vec<Package>> {
     let mut cmd = cargo_metadata::MetadataCommand::new();
     cmd.no_deps();
+    flags.apply(&mut cmd);
     if let Some(manifest_path) = manifest_path {
         cmd.manifest_path(manifest_path);
     }
@@ -35,7 +88,13 @@ pub fn workspace_members(manifest_path: Option<&Path>) -> CargoResult<Vec<Packag
     let workspace_members: Vec<_> = result
         .packages
         .into_iter()
-        .filter(|p| workspace_members.contains(&p.id))
+        .filter(|p| {
+            workspace_members.contains(&p.id)
+                && !is_generated(
+                    result.workspace_root.as_std_path(),
+                    p.manifest_path.as_std_path(),
+                )
+        })
         .map(|mut p| {
             p.manifest_path = canonicalize_path(p.manifest_path);
             for dep in p.dependencies.iter_mut() {
@@ -59,46 +118,297 @@ fn canonicalize_path(
     path
 }
 
-/// Determine packages selected by user
-pub fn resolve_manifests(
-    manifest_path: Option<&Path>,
-    workspace: bool,
-    pkgid: Vec<&str>,
-) -> CargoResult<Vec<Package>> {
-    let manifest_path = manifest_path.map(|p| Ok(p.to_owned())).unwrap_or_else(|| {
-        find_manifest_path(
-            &std::env::current_dir().with_context(|| "Failed to get current directory")?,
-        )
-    })?;
-    let manifest_path = dunce::canonicalize(manifest_path)?;
-
-    let mut cmd = cargo_metadata::MetadataCommand::new();
-    cmd.no_deps();
-    cmd.manifest_path(&manifest_path);
-    let result = cmd.exec().with_context(|| "Invalid manifest")?;
-    let pkgs = if workspace {
-        result.packages
-    } else if !pkgid.is_empty() {
-        pkgid
-            .into_iter()
-            .map(|id| {
-                result
-                    .packages
-                    .iter()
-                    .find(|pkg| pkg.name == id)
-                    .map(|p| p.to_owned())
-                    .with_context(|| format!("could not find pkgid {}", id))
-            })
-            .collect::<Result<Vec<_>, anyhow::Error>>()?
+/// Describe a package from its manifest alone, ignoring any workspace it may belong to.
+///
+/// Unlike [`resolve_manifests`](crate::resolve_manifests), this never runs `cargo`, making it
+/// suitable for read-only inspection of packages outside of a workspace, like an extracted
+/// `.crate`.  `manifest_path` may also be the package's directory.
+///
+/// The dependencies, features, metadata, and library and binary targets come from the manifest as
+/// written, so workspace inheritance isn't resolved and dependency sources are left blank.
+pub fn manifest_package(manifest_path: &Path) -> CargoResult<Package> {
+    let manifest_path = if manifest_path.is_dir() {
+        manifest_path.join("Cargo.toml")
     } else {
-        result
-            .packages
-            .iter()
-            .find(|p| p.manifest_path == manifest_path)
-            .map(|p| vec![(p.to_owned())])
-            .unwrap_or_else(|| result.packages)
+        manifest_path.to_owned()
     };
-    Ok(pkgs)
+    let manifest_path = dunce::canonicalize(&manifest_path)
+        .with_context(|| format!("Unable to find {}", manifest_path.display()))?;
+    let manifest = LocalManifest::try_new(&manifest_path)?;
+    package_from_manifest(&manifest)
+}
+
+pub(crate) fn package_from_manifest(manifest: &LocalManifest) -> CargoResult<Package> {
+    let manifest_path = manifest.path.as_path();
+    let package = manifest
+        .data
+        .get(manifest.package_key())
+        .and_then(|p| p.as_table_like())
+        .with_context(|| format!("Missing `package` in {}", manifest_path.display()))?;
+    let name = package
+        .get("name")
+        .and_then(|n| n.as_str())
+        .with_context(|| format!("Missing `package.name` in {}", manifest_path.display()))?;
+    let version = package
+        .get("version")
+        .and_then(|v| v.as_str())
+        .with_context(|| format!("Missing `package.version` in {}", manifest_path.display()))?;
+
+    let edition = package
+        .get("edition")
+        .and_then(|e| e.as_str())
+        .unwrap_or("2015");
+    let metadata = match package.get("metadata").cloned().map(|m| m.into_table()) {
+        Some(Ok(metadata)) => {
+            toml_edit::easy::from_str(&metadata.to_string()).with_context(|| {
+                format!("Invalid `package.metadata` in {}", manifest_path.display())
+            })?
+        }
+        _ => serde_json::Value::Null,
+    };
+    // Like `cargo metadata`, `publish = true` and a missing `publish` are both unrestricted
+    let publish = match package.get("publish") {
+        Some(publish) if publish.as_bool() == Some(false) => Some(Vec::new()),
+        Some(publish) => publish.as_array().map(|registries| {
+            registries
+                .iter()
+                .filter_map(|r| r.as_str())
+                .map(|r| r.to_owned())
+                .collect::<Vec<_>>()
+        }),
+        None => None,
+    };
+
+    let crate_root = manifest_path.parent().expect("at least a parent");
+    let url = url::Url::from_file_path(crate_root)
+        .map_err(|_| anyhow::format_err!("Invalid manifest path {}", manifest_path.display()))?;
+    let package = serde_json::json!({
+        "name": name,
+        "version": version,
+        "id": format!("{} {} (path+{})", name, version, url),
+        "authors": [],
+        "dependencies": manifest_dependencies(manifest, crate_root),
+        "targets": manifest_targets(manifest, name, edition, crate_root),
+        "features": manifest.features(),
+        "categories": [],
+        "keywords": [],
+        "edition": edition,
+        "metadata": metadata,
+        "publish": publish,
+        "manifest_path": manifest_path,
+    });
+    let package = serde_json::from_value(package)
+        .with_context(|| format!("Invalid manifest {}", manifest_path.display()))?;
+    Ok(package)
+}
+
+/// Dependencies as `cargo metadata` lists them, from the manifest's dependency tables
+///
+/// Sources aren't resolved, so they are left blank, and inherited dependencies allow any version.
+fn manifest_dependencies(manifest: &LocalManifest, crate_root: &Path) -> Vec<serde_json::Value> {
+    let mut dependencies = Vec::new();
+    for (table, item) in manifest.get_sections() {
+        let kind = match table.kind() {
+            DepKind::Normal => None,
+            DepKind::Development => Some("dev"),
+            DepKind::Build => Some("build"),
+        };
+        for (key, dep_item) in item.as_table_like().into_iter().flat_map(|t| t.iter()) {
+            let dependency = match Dependency::from_toml(crate_root, key, dep_item) {
+                Ok(dependency) => dependency,
+                // Cargo would reject the manifest, so there is nothing to describe
+                Err(_) => continue,
+            };
+            dependencies.push(serde_json::json!({
+                "name": dependency.name,
+                "source": null,
+                "req": dependency.version().unwrap_or("*"),
+                "kind": kind,
+                "rename": dependency.rename(),
+                "optional": dependency.optional().unwrap_or(false),
+                "uses_default_features": dependency.default_features().unwrap_or(true),
+                "features": dependency.features.clone().unwrap_or_default(),
+                "target": table.target(),
+                "registry": dependency.registry(),
+                "path": dependency.source().and_then(|s| s.as_path()).map(|s| &s.path),
+            }));
+        }
+    }
+    dependencies
+}
+
+/// The library and binary targets cargo would build, from `[lib]` and `[[bin]]` or else
+/// `src/lib.rs` and `src/main.rs`
+fn manifest_targets(
+    manifest: &LocalManifest,
+    name: &str,
+    edition: &str,
+    crate_root: &Path,
+) -> Vec<serde_json::Value> {
+    let target = |kind: Vec<&str>, name: &str, src_path: std::path::PathBuf| {
+        serde_json::json!({
+            "name": name,
+            "kind": kind,
+            "crate_types": kind,
+            "src_path": src_path,
+            "edition": edition,
+        })
+    };
+
+    let mut targets = Vec::new();
+    let lib_path = crate_root.join("src").join("lib.rs");
+    match manifest.data.get("lib").and_then(|l| l.as_table_like()) {
+        Some(lib) => {
+            let kind = lib
+                .get("crate-type")
+                .and_then(|t| t.as_array())
+                .map(|types| types.iter().filter_map(|t| t.as_str()).collect())
+                .unwrap_or_else(|| vec!["lib"]);
+            let lib_name = lib
+                .get("name")
+                .and_then(|n| n.as_str())
+                .map(|n| n.to_owned())
+                .unwrap_or_else(|| name.replace('-', "_"));
+            let src_path = lib
+                .get("path")
+                .and_then(|p| p.as_str())
+                .map(|p| crate_root.join(p))
+                .unwrap_or(lib_path);
+            targets.push(target(kind, &lib_name, src_path));
+        }
+        None if lib_path.is_file() => {
+            targets.push(target(vec!["lib"], &name.replace('-', "_"), lib_path));
+        }
+        None => {}
+    }
+    let bins = manifest
+        .data
+        .get("bin")
+        .and_then(|b| b.as_array_of_tables())
+        .into_iter()
+        .flat_map(|b| b.iter())
+        .filter_map(|bin| {
+            let bin_name = bin.get("name")?.as_str()?;
+            let src_path = bin
+                .get("path")
+                .and_then(|p| p.as_str())
+                .map(|p| crate_root.join(p))
+                .unwrap_or_else(|| {
+                    crate_root
+                        .join("src")
+                        .join("bin")
+                        .join(format!("{}.rs", bin_name))
+                });
+            Some(target(vec!["bin"], bin_name, src_path))
+        })
+        .collect::<Vec<_>>();
+    let main_path = crate_root.join("src").join("main.rs");
+    if bins.is_empty() && main_path.is_file() {
+        targets.push(target(vec!["bin"], name, main_path));
+    }
+    targets.extend(bins);
+    targets
+}
+
+/// Find the independent workspaces beneath `dir`: workspace roots, and packages outside of any
+/// workspace
+///
+/// Hidden and `target` directories are skipped, as are vendored packages.
+pub fn find_workspace_roots(dir: &Path) -> CargoResult<Vec<std::path::PathBuf>> {
+    let mut roots = Vec::new();
+    let mut pending = vec![dunce::canonicalize(dir)
+        .with_context(|| format!("Failed to find the directory {}", dir.display()))?];
+    while let Some(dir) = pending.pop() {
+        let manifest_path = dir.join("Cargo.toml");
+        if manifest_path.is_file() && !is_generated(&dir, &manifest_path) {
+            match find_workspace_root(&manifest_path) {
+                Ok(root) if root != manifest_path => {}
+                _ => roots.push(manifest_path),
+            }
+        }
+
+        let entries = std::fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read the directory {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            // Symlinks aren't followed, so links back up the tree can't loop
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let name = entry.file_name();
+            let skipped = name
+                .to_str()
+                .map(|name| name.starts_with('.') || name == "target")
+                .unwrap_or(true);
+            if !skipped {
+                pending.push(entry.path());
+            }
+        }
+    }
+    roots.sort();
+    Ok(roots)
+}
+
+/// Directories that projects commonly keep companion crates in, outside of their workspace
+const NESTED_CRATE_DIRS: &[&str] = &["fuzz", "xtask"];
+
+/// Find the companion crates, like `fuzz/` and `xtask/`, kept in the directory of the workspace
+/// of `manifest_path` or of one of its members without being members themselves
+pub fn find_nested_crates(
+    manifest_path: &Path,
+    flags: CargoFlags,
+) -> CargoResult<Vec<std::path::PathBuf>> {
+    let manifest_path = dunce::canonicalize(manifest_path)
+        .with_context(|| format!("Failed to find {}", manifest_path.display()))?;
+    let root = find_workspace_root(&manifest_path).unwrap_or_else(|_| manifest_path.clone());
+    let root_dir = root.parent().expect("manifest is always in a directory");
+    let members = workspace_members(Some(&manifest_path), flags)?;
+
+    let mut dirs = vec![root_dir];
+    dirs.extend(
+        members
+            .iter()
+            .filter_map(|member| member.manifest_path.parent())
+            .map(|dir| dir.as_std_path()),
+    );
+    let mut nested = Vec::new();
+    for dir in dirs {
+        for name in NESTED_CRATE_DIRS {
+            let candidate = dir.join(name).join("Cargo.toml");
+            let candidate = match dunce::canonicalize(&candidate) {
+                Ok(candidate) => candidate,
+                Err(_) => continue,
+            };
+            let member = members
+                .iter()
+                .any(|member| member.manifest_path.as_std_path() == candidate);
+            if !member && !is_generated(root_dir, &candidate) && !nested.contains(&candidate) {
+                nested.push(candidate);
+            }
+        }
+    }
+    nested.sort();
+    Ok(nested)
+}
+
+/// Whether the manifest is from a published `.crate`, like an extracted download
+///
+/// `cargo package` normalizes the manifest, dropping its `[workspace]` context, and always keeps
+/// the original next to it as `Cargo.toml.orig`.
+pub fn is_published_manifest(manifest_path: &Path) -> bool {
+    manifest_path
+        .parent()
+        .map(|dir| dir.join("Cargo.toml.orig").is_file())
+        .unwrap_or(false)
+}
+
+/// Vendored packages and build output, like `cargo package`'s, beneath `root_dir`
+pub(crate) fn is_generated(root_dir: &Path, manifest_path: &Path) -> bool {
+    let vendored = manifest_path
+        .parent()
+        .map(|dir| dir.join(".cargo-checksum.json").is_file())
+        .unwrap_or(false);
+    vendored || manifest_path.starts_with(root_dir.join("target"))
 }
 
 /// Search for Cargo.toml in this directory and recursively up the tree until one is found.
@@ -0,0 +1,28 @@
+//! Refuse every write once a command runs with `--read-only`.
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::errors::*;
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Make every later write fail, for the rest of the process.
+///
+/// Writes are checked where they reach the filesystem, with [`ensure_writable`], so a command
+/// can't write anything by mistake once this was called.
+pub fn enforce_read_only() {
+    READ_ONLY.store(true, Ordering::SeqCst);
+}
+
+/// Whether [`enforce_read_only`] was called
+pub fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::SeqCst)
+}
+
+/// Fail if `path` is about to be written while running read-only
+pub fn ensure_writable(path: &Path) -> CargoResult<()> {
+    if is_read_only() {
+        anyhow::bail!("Refusing to write {} with `--read-only`", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,242 @@
+//! Machine-readable reports
+//!
+//! These types are a stability contract for consumers of `--output-format json`.  Fields may be
+//! added in a release but existing ones won't change meaning without bumping
+//! [`REPORT_SCHEMA_VERSION`].
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Version of the report schema, bumped on incompatible changes
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// Report of a `cargo upgrade` run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct UpgradeReport {
+    /// Version of the schema this report follows, see [`REPORT_SCHEMA_VERSION`]
+    pub schema_version: u32,
+    /// Packages that were checked, in processing order
+    pub packages: Vec<PackageReport>,
+    /// Problems that didn't stop the run, in the order they were found
+    #[serde(default)]
+    pub warnings: Vec<WarningReport>,
+    /// Where the time went, with `--timings`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timings: Option<TimingsReport>,
+}
+
+impl UpgradeReport {
+    /// Create an empty report for the current schema
+    pub fn new() -> Self {
+        Self {
+            schema_version: REPORT_SCHEMA_VERSION,
+            packages: Vec::new(),
+            warnings: Vec::new(),
+            timings: None,
+        }
+    }
+}
+
+impl Default for UpgradeReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Dependencies checked for one package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct PackageReport {
+    /// Package name
+    pub name: String,
+    /// Path to the package's `Cargo.toml`
+    pub manifest_path: PathBuf,
+    /// Dependencies, in manifest order
+    pub dependencies: Vec<DependencyReport>,
+}
+
+impl PackageReport {
+    /// Create a report with no dependencies
+    pub fn new(name: impl Into<String>, manifest_path: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            manifest_path: manifest_path.into(),
+            dependencies: Vec::new(),
+        }
+    }
+}
+
+/// Upgrade status of one dependency
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct DependencyReport {
+    /// Dependency name, as written in the manifest
+    pub name: String,
+    /// Version requirement before the upgrade
+    pub old_req: String,
+    /// Version recorded in `Cargo.lock`
+    pub locked: Option<String>,
+    /// Latest version available in the registry
+    pub latest: Option<String>,
+    /// Version requirement after the upgrade
+    pub new_req: String,
+    /// Why `new_req` isn't the latest version
+    pub reason: Option<ReportReason>,
+}
+
+impl DependencyReport {
+    /// Create a report for a dependency
+    pub fn new(
+        name: impl Into<String>,
+        old_req: impl Into<String>,
+        new_req: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            old_req: old_req.into(),
+            locked: None,
+            latest: None,
+            new_req: new_req.into(),
+            reason: None,
+        }
+    }
+
+    /// Set the locked version
+    pub fn set_locked(mut self, locked: Option<String>) -> Self {
+        self.locked = locked;
+        self
+    }
+
+    /// Set the latest version
+    pub fn set_latest(mut self, latest: Option<String>) -> Self {
+        self.latest = latest;
+        self
+    }
+
+    /// Set why the requirement wasn't upgraded to latest
+    pub fn set_reason(mut self, reason: Option<ReportReason>) -> Self {
+        self.reason = reason;
+        self
+    }
+}
+
+/// A problem that didn't stop the run, like a dependency that had to be skipped
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct WarningReport {
+    /// Package being checked, if the warning is about one
+    pub package: Option<String>,
+    /// Dependency being checked, if the warning is about one
+    pub dependency: Option<String>,
+    /// Human-readable description
+    pub message: String,
+}
+
+impl WarningReport {
+    /// Create a warning that isn't about any package in particular
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            package: None,
+            dependency: None,
+            message: message.into(),
+        }
+    }
+
+    /// Set the package the warning is about
+    pub fn set_package(mut self, package: Option<String>) -> Self {
+        self.package = package;
+        self
+    }
+
+    /// Set the dependency the warning is about
+    pub fn set_dependency(mut self, dependency: Option<String>) -> Self {
+        self.dependency = dependency;
+        self
+    }
+}
+
+/// Local measurement of where the time of a run went, in milliseconds
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct TimingsReport {
+    /// Updating registry indexes
+    pub index_update_ms: u64,
+    /// Running `cargo metadata` and updating `Cargo.lock`
+    pub metadata_ms: u64,
+    /// Looking up dependencies in each registry, by registry name
+    pub lookups_ms: BTreeMap<String, u64>,
+    /// Reading and writing manifests
+    pub manifest_io_ms: u64,
+    /// The whole run
+    pub total_ms: u64,
+}
+
+/// Why a dependency wasn't upgraded to the latest version
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum ReportReason {
+    /// Already up-to-date
+    Unchanged,
+    /// Latest version is compatible with the existing requirement
+    Compatible,
+    /// Requirement was pinned by the user
+    Pinned,
+    /// Latest version is outside the package's `constraints`
+    Constrained,
+    /// Latest version is a new minor version, outside a tilde requirement's range
+    TildeConstrained,
+    /// Excluded by the user, so only `Cargo.lock` was updated within the requirement
+    Excluded,
+    /// Latest version needs a newer Rust than the package's `rust-version`
+    MsrvIncompatible,
+    /// Upgrade is of a kind left out by `--only`
+    Filtered,
+    /// Upgrade was turned down in `--interactive`
+    Declined,
+    /// Reason introduced by a newer release of `cargo-edit`
+    #[serde(other)]
+    Other,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut report = UpgradeReport::new();
+        let mut package = PackageReport::new("foo", "/foo/Cargo.toml");
+        package.dependencies.push(
+            DependencyReport::new("serde", "1.0", "1.0")
+                .set_locked(Some("1.0.100".to_owned()))
+                .set_latest(Some("1.0.140".to_owned()))
+                .set_reason(Some(ReportReason::Compatible)),
+        );
+        report.packages.push(package);
+        report.warnings.push(
+            WarningReport::new("ignoring bar, unsupported entry")
+                .set_package(Some("foo".to_owned()))
+                .set_dependency(Some("bar".to_owned())),
+        );
+
+        let json = serde_json::to_string(&report).unwrap();
+        let actual: UpgradeReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(actual, report);
+        assert_eq!(actual.schema_version, REPORT_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn missing_warnings() {
+        let actual: UpgradeReport =
+            serde_json::from_str(r#"{"schema_version": 1, "packages": []}"#).unwrap();
+        assert_eq!(actual, UpgradeReport::new());
+    }
+
+    #[test]
+    fn unknown_reason() {
+        let actual: ReportReason = serde_json::from_str("\"from-the-future\"").unwrap();
+        assert_eq!(actual, ReportReason::Other);
+    }
+}
@@ -0,0 +1,565 @@
+//! Select the packages a command operates on
+//!
+//! Every command resolves `--manifest-path`, `--package`, and `--workspace` through here, so they
+//! agree on which packages that means: running from a workspace root selects its
+//! `default-members` like `cargo build`, members are found through `package.workspace` or the
+//! nearest enclosing `[workspace]`, and `workspace.exclude`d, vendored, and generated packages are
+//! left alone.
+
+use std::path::{Path, PathBuf};
+
+use cargo_metadata::Package;
+
+use super::errors::*;
+use super::metadata::{
+    find_manifest_path, is_generated, is_published_manifest, package_from_manifest, CargoFlags,
+};
+use super::util::{glob_match, shell_warn};
+use super::LocalManifest;
+
+/// Determine packages selected by user
+pub fn resolve_manifests(
+    manifest_path: Option<&Path>,
+    workspace: bool,
+    pkgid: Vec<&str>,
+    flags: CargoFlags,
+) -> CargoResult<Vec<Package>> {
+    let explicit_manifest = manifest_path.is_some();
+    let manifest_path = manifest_path.map(|p| Ok(p.to_owned())).unwrap_or_else(|| {
+        find_manifest_path(
+            &std::env::current_dir().with_context(|| "Failed to get current directory")?,
+        )
+    })?;
+    let manifest_path = dunce::canonicalize(manifest_path)?;
+
+    if is_published_manifest(&manifest_path) {
+        // The workspace was stripped when publishing, so an enclosing one would claim the package
+        // and fail on it not being a member
+        let package = package_from_manifest(&LocalManifest::try_new(&manifest_path)?)?;
+        if let Some(id) = pkgid.iter().find(|id| **id != package.name) {
+            anyhow::bail!("could not find pkgid {}", id);
+        }
+        return Ok(vec![package]);
+    }
+
+    if explicit_manifest && !workspace && pkgid.is_empty() {
+        // `cargo metadata` dominates the runtime for simple edits, so skip it when there is
+        // nothing a workspace could change about the answer
+        if let Some(package) = standalone_package(&manifest_path)? {
+            return Ok(vec![package]);
+        }
+    }
+
+    let mut cmd = cargo_metadata::MetadataCommand::new();
+    cmd.no_deps();
+    cmd.manifest_path(&manifest_path);
+    flags.apply(&mut cmd);
+    let result = match cmd.exec() {
+        Ok(result) => result,
+        Err(err) if !workspace && pkgid.is_empty() => {
+            // Cargo refuses `cargo-features` it doesn't know, but the sections we edit still are
+            // as we know them
+            let manifest = LocalManifest::try_new(&manifest_path)?;
+            if manifest.cargo_features().is_empty() {
+                return Err(err).with_context(|| "Invalid manifest");
+            }
+            shell_warn(&format!(
+                "cargo can't read {}, editing it on its own: {}",
+                manifest_path.display(),
+                err
+            ))?;
+            return Ok(vec![package_from_manifest(&manifest)?]);
+        }
+        Err(err) => return Err(err).with_context(|| "Invalid manifest"),
+    };
+    let pkgs = if workspace {
+        // Globbed members can sweep up vendored packages
+        let workspace_root = result.workspace_root.as_std_path();
+        result
+            .packages
+            .into_iter()
+            .filter(|p| !is_generated(workspace_root, p.manifest_path.as_std_path()))
+            .collect()
+    } else if !pkgid.is_empty() {
+        pkgid
+            .into_iter()
+            .map(|id| {
+                result
+                    .packages
+                    .iter()
+                    .find(|pkg| pkg.name == id)
+                    .map(|p| p.to_owned())
+                    .with_context(|| format!("could not find pkgid {}", id))
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?
+    } else if let Some(default_members) = default_members(
+        &manifest_path,
+        result.workspace_root.as_std_path(),
+        &result.packages,
+    )? {
+        default_members
+    } else {
+        result
+            .packages
+            .iter()
+            .find(|p| p.manifest_path == manifest_path)
+            .map(|p| vec![(p.to_owned())])
+            .unwrap_or_else(|| result.packages)
+    };
+    Ok(pkgs)
+}
+
+/// Packages from `workspace.default-members`, when run from the workspace root like `cargo build`
+fn default_members(
+    manifest_path: &Path,
+    workspace_root: &Path,
+    packages: &[Package],
+) -> CargoResult<Option<Vec<Package>>> {
+    let root_manifest = dunce::canonicalize(workspace_root.join("Cargo.toml"))?;
+    if manifest_path != root_manifest {
+        return Ok(None);
+    }
+    let manifest = LocalManifest::try_new(&root_manifest)?;
+    let default_members = match manifest
+        .data
+        .get("workspace")
+        .and_then(|w| w.get("default-members"))
+        .and_then(|m| m.as_array())
+    {
+        Some(default_members) => default_members,
+        None => return Ok(None),
+    };
+
+    default_members
+        .iter()
+        .map(|member| {
+            let member = member
+                .as_str()
+                .with_context(|| "`workspace.default-members` must only contain paths")?;
+            let member_manifest = workspace_root.join(member).join("Cargo.toml");
+            let member_manifest = dunce::canonicalize(&member_manifest)
+                .with_context(|| format!("Unable to find default member {}", member))?;
+            packages
+                .iter()
+                .find(|p| {
+                    dunce::canonicalize(&p.manifest_path).ok().as_ref() == Some(&member_manifest)
+                })
+                .cloned()
+                .with_context(|| format!("default member {} is not a workspace member", member))
+        })
+        .collect::<CargoResult<Vec<_>>>()
+        .map(Some)
+}
+
+/// Describe a package without `cargo metadata`, if it can't be part of a workspace.
+fn standalone_package(manifest_path: &Path) -> CargoResult<Option<Package>> {
+    let manifest = match LocalManifest::try_new(manifest_path) {
+        Ok(manifest) => manifest,
+        // Let cargo report on the broken manifest
+        Err(_) => return Ok(None),
+    };
+    if manifest.data.contains_key("workspace") {
+        return Ok(None);
+    }
+    match manifest
+        .data
+        .get(manifest.package_key())
+        .and_then(|p| p.as_table_like())
+    {
+        Some(package) if !package.contains_key("workspace") => {}
+        _ => return Ok(None),
+    }
+    let in_workspace = manifest_path
+        .parent()
+        .into_iter()
+        .flat_map(Path::ancestors)
+        .skip(1)
+        .map(|dir| dir.join("Cargo.toml"))
+        .filter(|path| path.is_file())
+        .any(|path| {
+            LocalManifest::try_new(&path)
+                .map(|m| {
+                    m.data.contains_key("workspace") && !excluded_from_workspace(&m, manifest_path)
+                })
+                // Let cargo report on the broken manifest
+                .unwrap_or(true)
+        });
+    if in_workspace {
+        return Ok(None);
+    }
+
+    Ok(package_from_manifest(&manifest).ok())
+}
+
+/// Find the manifest with the `[workspace]` that `manifest_path` belongs to
+///
+/// Like cargo, this follows `package.workspace` when the package names its root, and otherwise
+/// takes the nearest ancestor with a `[workspace]` that doesn't exclude the package.
+pub fn find_workspace_root(manifest_path: &Path) -> CargoResult<PathBuf> {
+    if is_published_manifest(manifest_path) {
+        anyhow::bail!(
+            "{} is from a published `.crate`, which has no workspace",
+            manifest_path.display()
+        );
+    }
+    if let Some(root) = workspace_pointer(manifest_path)? {
+        return Ok(root);
+    }
+    for dir in manifest_path.parent().into_iter().flat_map(Path::ancestors) {
+        let candidate = dir.join("Cargo.toml");
+        if !candidate.is_file() {
+            continue;
+        }
+        let manifest = LocalManifest::try_new(&candidate)?;
+        if manifest.data.contains_key("workspace")
+            && (candidate == manifest_path || !excluded_from_workspace(&manifest, manifest_path))
+        {
+            return Ok(candidate);
+        }
+    }
+    anyhow::bail!(
+        "Unable to find the workspace of {}",
+        manifest_path.display()
+    );
+}
+
+/// The root that the package's `package.workspace` points to, if it has one
+fn workspace_pointer(manifest_path: &Path) -> CargoResult<Option<PathBuf>> {
+    if !manifest_path.is_file() {
+        return Ok(None);
+    }
+    let manifest = LocalManifest::try_new(manifest_path)?;
+    let pointer = manifest
+        .data
+        .get(manifest.package_key())
+        .and_then(|p| p.get("workspace"))
+        .and_then(|w| w.as_str());
+    let pointer = match pointer {
+        Some(pointer) => pointer,
+        None => return Ok(None),
+    };
+    let dir = manifest_path
+        .parent()
+        .expect("manifest is always in a directory");
+    let root = dunce::canonicalize(dir.join(pointer).join("Cargo.toml")).with_context(|| {
+        format!(
+            "Unable to find the workspace `{}` of {}",
+            pointer,
+            manifest_path.display()
+        )
+    })?;
+    Ok(Some(root))
+}
+
+/// Keep the packages whose directory, relative to the workspace root, matches one of `patterns`
+///
+/// Patterns are globs like `crates/net-*`, see [`glob_match`].
+pub fn filter_members(packages: Vec<Package>, patterns: &[String]) -> CargoResult<Vec<Package>> {
+    if patterns.is_empty() {
+        return Ok(packages);
+    }
+    let root_dir = match packages.first() {
+        Some(package) => {
+            let manifest_path = package.manifest_path.as_std_path();
+            let root = find_workspace_root(manifest_path).unwrap_or_else(|_| manifest_path.into());
+            root.parent()
+                .expect("manifest is always in a directory")
+                .to_owned()
+        }
+        None => return Ok(packages),
+    };
+    let patterns = patterns
+        .iter()
+        .map(|pattern| pattern.trim_end_matches('/'))
+        .collect::<Vec<_>>();
+    let members = packages
+        .into_iter()
+        .filter(|package| {
+            let dir = package
+                .manifest_path
+                .as_std_path()
+                .parent()
+                .expect("manifest is always in a directory");
+            let dir = dir.strip_prefix(&root_dir).unwrap_or(dir);
+            let dir = dir.to_string_lossy().replace('\\', "/");
+            patterns.iter().any(|pattern| glob_match(pattern, &dir))
+        })
+        .collect::<Vec<_>>();
+    if members.is_empty() {
+        anyhow::bail!(
+            "no workspace members are in a directory matching `{}`",
+            patterns.join("`, `")
+        );
+    }
+    Ok(members)
+}
+
+/// Whether the workspace in `root` leaves out `manifest_path` even though it is beneath it
+///
+/// This follows cargo's `workspace.exclude` rules, and also leaves out vendored packages and
+/// anything in the workspace's `target` directory, which are never meant to be edited.
+fn excluded_from_workspace(root: &LocalManifest, manifest_path: &Path) -> bool {
+    let root_dir = root.path.parent().expect("at least a parent");
+    if is_generated(root_dir, manifest_path) {
+        return true;
+    }
+
+    let paths = |key: &str| {
+        root.data
+            .get("workspace")
+            .and_then(|w| w.get(key))
+            .and_then(|p| p.as_array())
+            .into_iter()
+            .flat_map(|p| p.iter())
+            .filter_map(|p| p.as_str())
+            .map(|p| root_dir.join(p))
+            .collect::<Vec<_>>()
+    };
+    let excluded = paths("exclude")
+        .iter()
+        .any(|path| manifest_path.starts_with(path));
+    let member = paths("members")
+        .iter()
+        .any(|path| manifest_path.starts_with(path));
+    excluded && !member
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use assert_fs::prelude::*;
+
+    /// Write `files` beneath a new temporary directory, giving each package a `src/lib.rs`
+    fn workspace(files: &[(&str, &str)]) -> (assert_fs::TempDir, PathBuf) {
+        let root = assert_fs::TempDir::new().unwrap();
+        for (path, content) in files {
+            root.child(path).write_str(content).unwrap();
+            if content.contains("[package]") {
+                let dir = Path::new(path).parent().unwrap();
+                root.child(dir.join("src/lib.rs")).touch().unwrap();
+            }
+        }
+        let dir = dunce::canonicalize(root.path()).unwrap();
+        (root, dir)
+    }
+
+    fn package(name: &str) -> String {
+        format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\n", name)
+    }
+
+    fn names(packages: Vec<Package>) -> Vec<String> {
+        let mut names = packages.into_iter().map(|p| p.name).collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn virtual_root_claims_members() {
+        let (_root, dir) = workspace(&[
+            ("Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\n"),
+            ("crates/a/Cargo.toml", &package("a")),
+        ]);
+        let root = find_workspace_root(&dir.join("crates/a/Cargo.toml")).unwrap();
+        assert_eq!(root, dir.join("Cargo.toml"));
+    }
+
+    #[test]
+    fn nested_workspace_claims_its_own_members() {
+        let (_root, dir) = workspace(&[
+            ("Cargo.toml", "[workspace]\nmembers = [\"a\"]\n"),
+            ("a/Cargo.toml", &package("a")),
+            ("inner/Cargo.toml", "[workspace]\nmembers = [\"b\"]\n"),
+            ("inner/b/Cargo.toml", &package("b")),
+        ]);
+        let root = find_workspace_root(&dir.join("inner/b/Cargo.toml")).unwrap();
+        assert_eq!(root, dir.join("inner/Cargo.toml"));
+        let root = find_workspace_root(&dir.join("inner/Cargo.toml")).unwrap();
+        assert_eq!(root, dir.join("inner/Cargo.toml"));
+    }
+
+    #[test]
+    fn package_workspace_points_at_root() {
+        let (_root, dir) = workspace(&[
+            ("ws/Cargo.toml", "[workspace]\nmembers = [\"../pkg\"]\n"),
+            (
+                "pkg/Cargo.toml",
+                &format!("{}workspace = \"../ws\"\n", package("pkg")),
+            ),
+        ]);
+        let root = find_workspace_root(&dir.join("pkg/Cargo.toml")).unwrap();
+        assert_eq!(root, dir.join("ws/Cargo.toml"));
+
+        let packages = resolve_manifests(
+            Some(&dir.join("pkg/Cargo.toml")),
+            false,
+            Vec::new(),
+            CargoFlags::default(),
+        )
+        .unwrap();
+        assert_eq!(names(packages), ["pkg"]);
+    }
+
+    #[test]
+    fn published_package_is_detached() {
+        let (_root, dir) = workspace(&[
+            ("Cargo.toml", "[workspace]\nmembers = [\"a\"]\n"),
+            ("a/Cargo.toml", &package("a")),
+            ("b-0.1.0/Cargo.toml", &package("b")),
+            ("b-0.1.0/Cargo.toml.orig", &package("b")),
+        ]);
+        let manifest_path = dir.join("b-0.1.0/Cargo.toml");
+        assert!(find_workspace_root(&manifest_path).is_err());
+
+        let packages = resolve_manifests(
+            Some(&manifest_path),
+            true,
+            Vec::new(),
+            CargoFlags::default(),
+        )
+        .unwrap();
+        assert_eq!(names(packages), ["b"]);
+    }
+
+    #[test]
+    fn unknown_cargo_features_are_tolerated() {
+        let manifest = format!(
+            "cargo-features = [\"some-future-feature\"]\n\n{}",
+            package("a")
+        );
+        let (_root, dir) = workspace(&[
+            ("Cargo.toml", "[workspace]\nmembers = [\"a\"]\n"),
+            ("a/Cargo.toml", &manifest),
+        ]);
+
+        let packages = resolve_manifests(
+            Some(&dir.join("a/Cargo.toml")),
+            false,
+            Vec::new(),
+            CargoFlags::default(),
+        )
+        .unwrap();
+        assert_eq!(names(packages), ["a"]);
+    }
+
+    #[test]
+    fn excluded_package_has_no_root() {
+        let (_root, dir) = workspace(&[
+            (
+                "Cargo.toml",
+                "[workspace]\nmembers = [\"a\"]\nexclude = [\"standalone\"]\n",
+            ),
+            ("a/Cargo.toml", &package("a")),
+            ("standalone/Cargo.toml", &package("standalone")),
+        ]);
+        assert!(find_workspace_root(&dir.join("standalone/Cargo.toml")).is_err());
+
+        let packages = resolve_manifests(
+            Some(&dir.join("standalone/Cargo.toml")),
+            false,
+            Vec::new(),
+            CargoFlags::default(),
+        )
+        .unwrap();
+        assert_eq!(names(packages), ["standalone"]);
+    }
+
+    #[test]
+    fn root_selects_default_members() {
+        let (_root, dir) = workspace(&[
+            (
+                "Cargo.toml",
+                "[workspace]\nmembers = [\"a\", \"b\"]\ndefault-members = [\"a\"]\n",
+            ),
+            ("a/Cargo.toml", &package("a")),
+            ("b/Cargo.toml", &package("b")),
+        ]);
+        let manifest_path = dir.join("Cargo.toml");
+        let resolve = |workspace, pkgid| {
+            resolve_manifests(
+                Some(&manifest_path),
+                workspace,
+                pkgid,
+                CargoFlags::default(),
+            )
+            .map(names)
+            .unwrap()
+        };
+        assert_eq!(resolve(false, Vec::new()), ["a"]);
+        assert_eq!(resolve(true, Vec::new()), ["a", "b"]);
+        assert_eq!(resolve(false, vec!["b"]), ["b"]);
+    }
+
+    #[test]
+    fn virtual_root_without_default_members_selects_all() {
+        let (_root, dir) = workspace(&[
+            ("Cargo.toml", "[workspace]\nmembers = [\"a\", \"b\"]\n"),
+            ("a/Cargo.toml", &package("a")),
+            ("b/Cargo.toml", &package("b")),
+        ]);
+        let packages = resolve_manifests(
+            Some(&dir.join("Cargo.toml")),
+            false,
+            Vec::new(),
+            CargoFlags::default(),
+        )
+        .unwrap();
+        assert_eq!(names(packages), ["a", "b"]);
+
+        let member = resolve_manifests(
+            Some(&dir.join("b/Cargo.toml")),
+            false,
+            Vec::new(),
+            CargoFlags::default(),
+        )
+        .unwrap();
+        assert_eq!(names(member), ["b"]);
+    }
+
+    #[test]
+    fn members_are_filtered_by_directory() {
+        let (_root, dir) = workspace(&[
+            (
+                "Cargo.toml",
+                "[workspace]\nmembers = [\"crates/*\", \"tools/*\"]\n",
+            ),
+            ("crates/net/Cargo.toml", &package("net")),
+            ("crates/db/Cargo.toml", &package("db")),
+            ("tools/gen/Cargo.toml", &package("gen")),
+        ]);
+        let packages = resolve_manifests(
+            Some(&dir.join("Cargo.toml")),
+            true,
+            Vec::new(),
+            CargoFlags::default(),
+        )
+        .unwrap();
+        let filtered = filter_members(packages.clone(), &["crates/*".to_owned()]).unwrap();
+        assert_eq!(names(filtered), ["db", "net"]);
+        assert!(filter_members(packages, &["examples/*".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn excluded_members_are_not_claimed() {
+        let root = assert_fs::TempDir::new().unwrap();
+        root.child("Cargo.toml")
+            .write_str(
+                r#"[workspace]
+members = ["crates/*", "vendor/patched"]
+exclude = ["vendor", "examples/standalone"]
+"#,
+            )
+            .unwrap();
+        root.child("vendor/serde/.cargo-checksum.json")
+            .write_str("{}")
+            .unwrap();
+        let manifest = LocalManifest::try_new(&root.path().join("Cargo.toml")).unwrap();
+
+        let excluded = |path: &str| excluded_from_workspace(&manifest, &root.path().join(path));
+        assert!(!excluded("crates/a/Cargo.toml"));
+        assert!(excluded("examples/standalone/Cargo.toml"));
+        assert!(excluded("vendor/serde/Cargo.toml"));
+        assert!(!excluded("vendor/patched/Cargo.toml"));
+        assert!(excluded("target/package/a-0.1.0/Cargo.toml"));
+    }
+}
@@ -0,0 +1,265 @@
+//! Pick which registry version a dependency moves to.
+use std::fmt::Debug;
+use std::path::Path;
+
+use semver::VersionReq;
+use url::Url;
+
+use super::errors::*;
+use super::fetch::{
+    get_compatible_dependency, get_dependency_published_before, get_latest_dependency,
+    get_minimal_dependency,
+};
+use super::Dependency;
+
+/// A dependency whose version is being picked
+#[derive(Copy, Clone, Debug)]
+pub struct VersionQuery<'a> {
+    /// Name of the crate in its registry
+    pub crate_name: &'a str,
+    /// The dependency's current requirement
+    pub version_req: &'a VersionReq,
+    /// Whether prerelease versions may be picked
+    pub allow_prerelease: bool,
+    /// Manifest declaring the dependency, for finding its registry
+    pub manifest_path: &'a Path,
+    /// Index of an alternative registry, or `None` for crates.io
+    pub registry: Option<&'a Url>,
+}
+
+/// How to pick the version a dependency moves to, out of those in its registry
+///
+/// `cargo upgrade --strategy` picks one of the strategies in this crate; library consumers can
+/// implement their own.
+pub trait VersionStrategy: Debug {
+    /// The version to move the dependency to
+    fn select(&self, query: &VersionQuery<'_>) -> CargoResult<Dependency>;
+
+    /// Like [`VersionStrategy::select`], but only among the versions `bound` allows, for when a
+    /// policy rules out the version that would be picked otherwise
+    ///
+    /// Picks the newest version `bound` allows by default.
+    fn select_within(
+        &self,
+        query: &VersionQuery<'_>,
+        bound: &VersionReq,
+    ) -> CargoResult<Dependency> {
+        get_compatible_dependency(query.crate_name, bound, query.manifest_path, query.registry)
+    }
+}
+
+impl<S: VersionStrategy + ?Sized> VersionStrategy for Box<S> {
+    fn select(&self, query: &VersionQuery<'_>) -> CargoResult<Dependency> {
+        (**self).select(query)
+    }
+
+    fn select_within(
+        &self,
+        query: &VersionQuery<'_>,
+        bound: &VersionReq,
+    ) -> CargoResult<Dependency> {
+        (**self).select_within(query, bound)
+    }
+}
+
+/// The newest version, even if the current requirement doesn't allow it
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LatestVersion;
+
+impl VersionStrategy for LatestVersion {
+    fn select(&self, query: &VersionQuery<'_>) -> CargoResult<Dependency> {
+        get_latest_dependency(
+            query.crate_name,
+            query.allow_prerelease,
+            query.manifest_path,
+            query.registry,
+        )
+    }
+}
+
+/// The newest version the current requirement allows
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LatestCompatibleVersion;
+
+impl VersionStrategy for LatestCompatibleVersion {
+    fn select(&self, query: &VersionQuery<'_>) -> CargoResult<Dependency> {
+        self.select_within(query, query.version_req)
+    }
+}
+
+/// The oldest version the current requirement allows, like `-Z minimal-versions`
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MinimalVersion;
+
+impl VersionStrategy for MinimalVersion {
+    fn select(&self, query: &VersionQuery<'_>) -> CargoResult<Dependency> {
+        self.select_within(query, query.version_req)
+    }
+
+    fn select_within(
+        &self,
+        query: &VersionQuery<'_>,
+        bound: &VersionReq,
+    ) -> CargoResult<Dependency> {
+        get_minimal_dependency(query.crate_name, bound, query.manifest_path, query.registry)
+    }
+}
+
+/// The newest version published before a day, see [`get_dependency_published_before`]
+#[derive(Clone, Debug)]
+pub struct PublishedBefore {
+    max_date: String,
+    compatible: bool,
+}
+
+impl PublishedBefore {
+    /// Versions published before `max_date`, a `YYYY-MM-DD` day in UTC
+    ///
+    /// With `compatible`, only versions the current requirement allows are picked.
+    pub fn new(max_date: impl Into<String>, compatible: bool) -> Self {
+        Self {
+            max_date: max_date.into(),
+            compatible,
+        }
+    }
+}
+
+impl VersionStrategy for PublishedBefore {
+    fn select(&self, query: &VersionQuery<'_>) -> CargoResult<Dependency> {
+        get_dependency_published_before(
+            query.crate_name,
+            self.compatible.then(|| query.version_req),
+            query.allow_prerelease,
+            &self.max_date,
+            query.manifest_path,
+            query.registry,
+        )
+    }
+
+    fn select_within(
+        &self,
+        query: &VersionQuery<'_>,
+        bound: &VersionReq,
+    ) -> CargoResult<Dependency> {
+        get_dependency_published_before(
+            query.crate_name,
+            Some(bound),
+            query.allow_prerelease,
+            &self.max_date,
+            query.manifest_path,
+            query.registry,
+        )
+    }
+}
+
+/// Another strategy, held to a policy's requirement
+///
+/// When the other strategy picks a version `constraint` rules out, the version it picks within
+/// `constraint` is used instead, like for `[package.metadata.cargo-edit.constraints]`.
+#[derive(Clone, Debug)]
+pub struct ConstrainedVersion<S> {
+    constraint: VersionReq,
+    strategy: S,
+}
+
+impl<S: VersionStrategy> ConstrainedVersion<S> {
+    /// Hold `strategy` to `constraint`
+    pub fn new(constraint: VersionReq, strategy: S) -> Self {
+        Self {
+            constraint,
+            strategy,
+        }
+    }
+}
+
+impl<S: VersionStrategy> VersionStrategy for ConstrainedVersion<S> {
+    fn select(&self, query: &VersionQuery<'_>) -> CargoResult<Dependency> {
+        let selected = self.strategy.select(query)?;
+        let version: semver::Version = selected
+            .version()
+            .ok_or_else(|| {
+                anyhow::format_err!(
+                    "{} was picked without a version to hold to `{}`",
+                    query.crate_name,
+                    self.constraint
+                )
+            })?
+            .parse()?;
+        if self.constraint.matches(&version) {
+            Ok(selected)
+        } else {
+            self.strategy.select_within(query, &self.constraint)
+        }
+    }
+
+    fn select_within(
+        &self,
+        query: &VersionQuery<'_>,
+        bound: &VersionReq,
+    ) -> CargoResult<Dependency> {
+        self.strategy
+            .select_within(query, &intersect(bound, &self.constraint))
+    }
+}
+
+/// The requirement allowing only the versions both `a` and `b` allow
+fn intersect(a: &VersionReq, b: &VersionReq) -> VersionReq {
+    VersionReq {
+        comparators: a
+            .comparators
+            .iter()
+            .chain(&b.comparators)
+            .cloned()
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::RefCell;
+
+    /// Records the bounds it is asked to pick within
+    #[derive(Debug, Default)]
+    struct Recording {
+        bounds: RefCell<Vec<VersionReq>>,
+    }
+
+    impl VersionStrategy for Recording {
+        fn select(&self, _query: &VersionQuery<'_>) -> CargoResult<Dependency> {
+            anyhow::bail!("only `select_within` is expected")
+        }
+
+        fn select_within(
+            &self,
+            _query: &VersionQuery<'_>,
+            bound: &VersionReq,
+        ) -> CargoResult<Dependency> {
+            self.bounds.borrow_mut().push(bound.clone());
+            Ok(Dependency::new("my-package"))
+        }
+    }
+
+    #[test]
+    fn constrained_bound_keeps_constraint() {
+        let strategy =
+            ConstrainedVersion::new(VersionReq::parse("<20").unwrap(), Recording::default());
+        let version_req = VersionReq::parse("0.1.1").unwrap();
+        let query = VersionQuery {
+            crate_name: "my-package",
+            version_req: &version_req,
+            allow_prerelease: false,
+            manifest_path: Path::new("Cargo.toml"),
+            registry: None,
+        };
+        strategy
+            .select_within(&query, &VersionReq::parse(">=0.4").unwrap())
+            .unwrap();
+
+        let bound = strategy.strategy.bounds.borrow()[0].clone();
+        assert!(bound.matches(&semver::Version::new(0, 4, 1)));
+        assert!(!bound.matches(&semver::Version::new(0, 2, 3)));
+        assert!(!bound.matches(&semver::Version::new(20, 0, 0)));
+    }
+}
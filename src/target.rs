@@ -0,0 +1,57 @@
+//! Evaluate `[target.'cfg(..)'.dependencies]` tables against a target triple.
+
+use super::errors::*;
+
+pub use cargo_platform::{Cfg, Platform};
+
+/// Configuration of the `target` triple, like `unix` or `target_os = "linux"`, as reported by
+/// `rustc --print cfg`
+pub fn target_cfgs(target: &str) -> CargoResult<Vec<Cfg>> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = std::process::Command::new(rustc)
+        .args(["--print", "cfg", "--target", target])
+        .output()
+        .with_context(|| "Failed to run `rustc`")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to read the configuration of target `{}`: {}",
+            target,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| {
+            line.parse()
+                .with_context(|| format!("Invalid cfg `{}` from `rustc`", line))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::DepTable;
+
+    #[test]
+    fn cfg_tables_match_semantically() {
+        let target = "x86_64-unknown-linux-gnu";
+        let cfgs = ["unix", "target_os=\"linux\""]
+            .iter()
+            .map(|cfg| cfg.parse().unwrap())
+            .collect::<Vec<Cfg>>();
+        let table = |platform: &str| DepTable::new().set_target(platform);
+
+        assert!(DepTable::new().matches_target(target, &cfgs).unwrap());
+        assert!(table("cfg(unix)").matches_target(target, &cfgs).unwrap());
+        assert!(table("cfg(target_os = \"linux\")")
+            .matches_target(target, &cfgs)
+            .unwrap());
+        assert!(!table("cfg(windows)").matches_target(target, &cfgs).unwrap());
+        assert!(table(target).matches_target(target, &cfgs).unwrap());
+        assert!(!table("aarch64-apple-darwin")
+            .matches_target(target, &cfgs)
+            .unwrap());
+        assert!(table("cfg(").matches_target(target, &cfgs).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+//! Stage edits to several manifests and write them all at once.
+use std::path::{Path, PathBuf};
+
+use indexmap::IndexMap;
+
+use super::errors::*;
+use super::hooks::WriteHooks;
+use super::interrupt::{check_interrupted, defer_interrupts};
+use super::manifest::{commit_staged, LocalManifest};
+
+/// Manifests edited in memory, to be written together by [`ManifestTransaction::commit`]
+///
+/// Commands plan every change before touching the filesystem, so a failure or interrupt part way
+/// through leaves all manifests as they were.
+#[derive(Debug, Default)]
+pub struct ManifestTransaction {
+    manifests: IndexMap<PathBuf, Staged>,
+}
+
+#[derive(Debug)]
+struct Staged {
+    manifest: LocalManifest,
+    original: String,
+}
+
+impl ManifestTransaction {
+    /// Start a transaction with no manifests
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the manifest at `path` for editing.
+    ///
+    /// Loading the same path again returns the copy with all prior edits.
+    pub fn load(&mut self, path: &Path) -> CargoResult<&mut LocalManifest> {
+        // Different spellings of a path must share edits
+        let path = dunce::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+        if !self.manifests.contains_key(&path) {
+            let manifest = LocalManifest::try_new(&path)?;
+            let original = manifest.to_string();
+            self.manifests
+                .insert(path.clone(), Staged { manifest, original });
+        }
+        Ok(&mut self.manifests[&path].manifest)
+    }
+
+    /// Paths of the manifests with edits
+    pub fn modified(&self) -> impl Iterator<Item = &Path> {
+        self.manifests
+            .values()
+            .filter(|s| s.is_modified())
+            .map(|s| s.manifest.path.as_path())
+    }
+
+    /// Whether any manifest has edits
+    pub fn is_modified(&self) -> bool {
+        self.modified().next().is_some()
+    }
+
+    /// Warnings for the edited manifests using keys or `cargo-features` this version doesn't know
+    pub fn future_keys_warnings(&self) -> Vec<String> {
+        self.manifests
+            .values()
+            .filter(|s| s.is_modified())
+            .filter_map(|s| s.manifest.future_keys_warning())
+            .collect()
+    }
+
+    /// Path, original contents and edited contents of each manifest with edits
+    pub(crate) fn changes(&self) -> impl Iterator<Item = (&Path, &str, String)> {
+        self.manifests.values().filter_map(|s| {
+            let modified = s.manifest.to_string();
+            (modified != s.original)
+                .then(|| (s.manifest.path.as_path(), s.original.as_str(), modified))
+        })
+    }
+
+    /// Write every edited manifest.
+    ///
+    /// All new contents are written to temporary files before any manifest is replaced; if that
+    /// fails, the manifests are left untouched.  Should replacing one fail, the error lists the
+    /// manifests already replaced, and the remaining ones are left as they were.  Configured `pre-write` hooks run on the staged
+    /// files and can abort the commit, `post-write` hooks run once the manifests are replaced.
+    pub fn commit(self) -> CargoResult<()> {
+        let modified = self
+            .manifests
+            .into_values()
+            .filter(|s| s.is_modified())
+            .map(|s| s.manifest)
+            .collect::<Vec<_>>();
+        let mut hooks = Vec::with_capacity(modified.len());
+        for manifest in &modified {
+            // Workspace roots are edited for their `[workspace]`, virtual or not
+            if !manifest.data.contains_key("workspace") {
+                manifest.check_writable()?;
+            }
+            hooks.push(WriteHooks::for_manifest(&manifest.path)?);
+        }
+        check_interrupted()?;
+
+        let _guard = defer_interrupts();
+        let mut staged = Vec::with_capacity(modified.len());
+        for (manifest, hooks) in modified.iter().zip(&mut hooks) {
+            let result = manifest.stage().and_then(|temp_path| {
+                let result = hooks.pre_write(&manifest.path, &temp_path);
+                staged.push((temp_path, manifest.path.as_path()));
+                result
+            });
+            if let Err(err) = result {
+                for (temp_path, _) in staged {
+                    let _ = std::fs::remove_file(temp_path);
+                }
+                return Err(err);
+            }
+        }
+        for (i, (temp_path, path)) in staged.iter().enumerate() {
+            if let Err(err) = commit_staged(temp_path, path) {
+                for (temp_path, _) in &staged[i..] {
+                    let _ = std::fs::remove_file(temp_path);
+                }
+                let replaced = staged[..i]
+                    .iter()
+                    .map(|(_, path)| path.display().to_string())
+                    .collect::<Vec<_>>();
+                if replaced.is_empty() {
+                    return Err(err);
+                }
+                return Err(err.context(format!(
+                    "Only some manifests were written: {}",
+                    replaced.join(", ")
+                )));
+            }
+        }
+        for ((_, path), hooks) in staged.iter().zip(&hooks) {
+            hooks.post_write(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Staged {
+    fn is_modified(&self) -> bool {
+        self.manifest.to_string() != self.original
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MANIFEST: &str = "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n";
+
+    #[test]
+    fn commit_only_modified() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let foo = temp.path().join("foo.toml");
+        let bar = temp.path().join("bar.toml");
+        std::fs::write(&foo, MANIFEST).unwrap();
+        std::fs::write(&bar, MANIFEST).unwrap();
+        let foo = dunce::canonicalize(foo).unwrap();
+        let bar = dunce::canonicalize(bar).unwrap();
+
+        let mut transaction = ManifestTransaction::new();
+        transaction.load(&foo).unwrap();
+        transaction
+            .load(&bar)
+            .unwrap()
+            .set_package_version(&semver::Version::new(0, 2, 0));
+        // Edits are kept across loads
+        assert!(transaction
+            .load(&bar)
+            .unwrap()
+            .to_string()
+            .contains("0.2.0"));
+        assert_eq!(
+            transaction.modified().collect::<Vec<_>>(),
+            vec![bar.as_path()]
+        );
+        assert_eq!(std::fs::read_to_string(&bar).unwrap(), MANIFEST);
+
+        transaction.commit().unwrap();
+        assert_eq!(std::fs::read_to_string(&foo).unwrap(), MANIFEST);
+        assert!(std::fs::read_to_string(&bar).unwrap().contains("0.2.0"));
+    }
+
+    #[test]
+    fn commit_virtual_root() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let root = temp.path().join("Cargo.toml");
+        std::fs::write(&root, "[workspace]\nmembers = []\n").unwrap();
+        let root = dunce::canonicalize(root).unwrap();
+
+        let mut transaction = ManifestTransaction::new();
+        transaction.load(&root).unwrap().data["workspace"]["members"] =
+            toml_edit::value(toml_edit::Array::from_iter(["foo"]));
+        transaction.commit().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&root).unwrap(),
+            "[workspace]\nmembers = [\"foo\"]\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commit_keeps_symlinks_and_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = assert_fs::TempDir::new().unwrap();
+        let target = temp.path().join("shared.toml");
+        let link = temp.path().join("Cargo.toml");
+        std::fs::write(&target, MANIFEST).unwrap();
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o640)).unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let mut transaction = ManifestTransaction::new();
+        transaction
+            .load(&link)
+            .unwrap()
+            .set_package_version(&semver::Version::new(0, 2, 0));
+        transaction.commit().unwrap();
+
+        assert!(std::fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(std::fs::read_to_string(&target).unwrap().contains("0.2.0"));
+        let mode = std::fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+}
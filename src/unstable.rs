@@ -0,0 +1,175 @@
+//! Gate experimental behavior behind `-Z` flags.
+use std::str::FromStr;
+
+use super::errors::*;
+
+/// A `-Z <name>` or `-Z <name>=<value>` flag, as given on the command line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnstableFlag {
+    name: String,
+    value: Option<String>,
+}
+
+impl UnstableFlag {
+    /// Name of the flag, without the value
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Value after the `=`, if any
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+}
+
+impl FromStr for UnstableFlag {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_owned())),
+            None => (s, None),
+        };
+        if name.is_empty() {
+            anyhow::bail!("`-Z` needs a flag name, see `-Z help` for the available ones");
+        }
+        Ok(Self {
+            name: name.to_owned(),
+            value,
+        })
+    }
+}
+
+/// An unstable flag that a command accepts
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UnstableOption {
+    /// Name, as in `-Z <name>`
+    pub name: &'static str,
+    /// Placeholder for the value, when the flag takes one
+    pub value_name: Option<&'static str>,
+    /// One-line description for `-Z help`
+    pub help: &'static str,
+}
+
+/// The `-Z` flags a command was run with, checked against the options it knows about
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnstableFlags {
+    flags: Vec<UnstableFlag>,
+}
+
+impl UnstableFlags {
+    /// Check `flags` against the command's `known` options
+    ///
+    /// Like `--help`, `-Z help` lists the options and exits.
+    pub fn new(flags: &[UnstableFlag], known: &[UnstableOption]) -> CargoResult<Self> {
+        if flags.iter().any(|flag| flag.name == "help") {
+            print_help(known);
+            std::process::exit(0);
+        }
+        for flag in flags {
+            let option = known
+                .iter()
+                .find(|option| option.name == flag.name)
+                .with_context(|| {
+                    format!(
+                        "unknown unstable flag `-Z {}`, see `-Z help` for the available ones",
+                        flag.name
+                    )
+                })?;
+            match (option.value_name, &flag.value) {
+                (Some(value_name), None) => anyhow::bail!(
+                    "`-Z {}` needs a value, like `-Z {}=<{}>`",
+                    flag.name,
+                    flag.name,
+                    value_name
+                ),
+                (None, Some(_)) => anyhow::bail!("`-Z {}` doesn't take a value", flag.name),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            flags: flags.to_vec(),
+        })
+    }
+
+    /// Whether `-Z <name>` was given
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag.name == name)
+    }
+
+    /// Value of the last `-Z <name>=<value>`
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.flags
+            .iter()
+            .rev()
+            .find(|flag| flag.name == name)
+            .and_then(|flag| flag.value())
+    }
+}
+
+fn print_help(known: &[UnstableOption]) {
+    if known.is_empty() {
+        println!("No unstable (nightly-only) flags are available");
+        return;
+    }
+    let usage = known
+        .iter()
+        .map(|option| match option.value_name {
+            Some(value_name) => format!("-Z {}=<{}>", option.name, value_name),
+            None => format!("-Z {}", option.name),
+        })
+        .collect::<Vec<_>>();
+    let width = usage.iter().map(|usage| usage.len()).max().unwrap_or(0);
+    println!("Available unstable (nightly-only) flags:");
+    println!();
+    for (usage, option) in usage.iter().zip(known) {
+        println!("    {:width$}    {}", usage, option.help, width = width);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KNOWN: &[UnstableOption] = &[
+        UnstableOption {
+            name: "bindeps",
+            value_name: None,
+            help: "Allow artifact dependencies",
+        },
+        UnstableOption {
+            name: "policy",
+            value_name: Some("FILE"),
+            help: "Check upgrades against a policy",
+        },
+    ];
+
+    #[test]
+    fn parse() {
+        let flag: UnstableFlag = "policy=deny.toml".parse().unwrap();
+        assert_eq!(flag.name(), "policy");
+        assert_eq!(flag.value(), Some("deny.toml"));
+        let flag: UnstableFlag = "bindeps".parse().unwrap();
+        assert_eq!(flag.value(), None);
+        assert!("=deny.toml".parse::<UnstableFlag>().is_err());
+    }
+
+    #[test]
+    fn check() {
+        let flags = ["bindeps", "policy=deny.toml"]
+            .iter()
+            .map(|flag| flag.parse().unwrap())
+            .collect::<Vec<UnstableFlag>>();
+        let flags = UnstableFlags::new(&flags, KNOWN).unwrap();
+        assert!(flags.is_enabled("bindeps"));
+        assert_eq!(flags.value("policy"), Some("deny.toml"));
+        assert!(!flags.is_enabled("sparse"));
+
+        let unknown = ["sparse".parse().unwrap()];
+        assert!(UnstableFlags::new(&unknown, KNOWN).is_err());
+        let missing_value = ["policy".parse().unwrap()];
+        assert!(UnstableFlags::new(&missing_value, KNOWN).is_err());
+        let extra_value = ["bindeps=yes".parse().unwrap()];
+        assert!(UnstableFlags::new(&extra_value, KNOWN).is_err());
+    }
+}
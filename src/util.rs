@@ -14,6 +14,15 @@ pub fn colorize_stderr() -> ColorChoice {
     }
 }
 
+/// Whether to color output meant for stdout
+pub fn colorize_stdout() -> ColorChoice {
+    if concolor_control::get(concolor_control::Stream::Stdout).color() {
+        ColorChoice::Always
+    } else {
+        ColorChoice::Never
+    }
+}
+
 /// Print a message with a colored title in the style of Cargo shell messages.
 pub fn shell_print(status: &str, message: &str, color: Color, justified: bool) -> CargoResult<()> {
     let color_choice = colorize_stderr();
@@ -59,3 +68,65 @@ pub fn shell_write_stderr(fragment: impl std::fmt::Display, spec: &ColorSpec) ->
     output.reset()?;
     Ok(())
 }
+
+/// Match a name against a pattern, where `*` matches any run of characters and `?` matches one
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let name = name.as_bytes();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == b'?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// Spell a crate name the way the registry compares it, where `-` and `_` are the same
+pub fn normalize_crate_name(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+/// [`glob_match`] for crate names, treating `-` and `_` as the same
+pub fn crate_glob_match(pattern: &str, name: &str) -> bool {
+    glob_match(&normalize_crate_name(pattern), &normalize_crate_name(name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn glob_matches_names() {
+        assert!(glob_match("serde", "serde"));
+        assert!(!glob_match("serde", "serde_json"));
+        assert!(glob_match("serde*", "serde_json"));
+        assert!(glob_match("*-sys", "openssl-sys"));
+        assert!(glob_match("tokio-?til", "tokio-util"));
+        assert!(glob_match("*", "anything"));
+        assert!(!glob_match("*-sys", "openssl"));
+    }
+
+    #[test]
+    fn crate_names_ignore_separators() {
+        assert!(crate_glob_match("serde-json", "serde_json"));
+        assert!(crate_glob_match("*_sys", "openssl-sys"));
+        assert!(!crate_glob_match("serde-json", "serde"));
+    }
+}
@@ -1,6 +1,10 @@
+use std::path::Path;
 use std::str::FromStr;
 
 use super::errors::*;
+use super::manifest::LocalManifest;
+use super::metadata::find_workspace_root;
+use super::util::shell_warn;
 
 /// Additional version functionality
 pub trait VersionExt {
@@ -178,6 +182,149 @@ pub fn upgrade_requirement(req: &str, version: &semver::Version) -> CargoResult<
     }
 }
 
+/// Canonical style for version requirements, see [`normalize_requirement`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReqStyle {
+    /// Write caret requirements as `^1.2.3` rather than `1.2.3`
+    pub explicit_caret: bool,
+    /// Drop `.0` components that don't change what a caret requirement matches, like `1.0.0` to `1`
+    pub trim_zeros: bool,
+}
+
+impl ReqStyle {
+    /// Style from `normalize-reqs` under `[workspace.metadata.cargo-upgrade]`, or
+    /// `[package.metadata.cargo-upgrade]` outside of a workspace
+    pub fn for_manifest(manifest_path: &Path) -> CargoResult<Self> {
+        let mut style = Self::default();
+        let root = find_workspace_root(manifest_path).unwrap_or_else(|_| manifest_path.to_owned());
+        let root = LocalManifest::try_new(&root)?;
+        let config = ["workspace", "package"].iter().find_map(|table| {
+            root.data
+                .get(table)?
+                .get("metadata")?
+                .get("cargo-upgrade")?
+                .get("normalize-reqs")
+        });
+        let config = match config {
+            Some(config) => config
+                .as_table_like()
+                .with_context(|| "`normalize-reqs` must be a table")?,
+            None => return Ok(style),
+        };
+        for (key, value) in config.iter() {
+            let value = value
+                .as_bool()
+                .with_context(|| format!("`normalize-reqs.{}` must be `true` or `false`", key))?;
+            match key {
+                "explicit-caret" => style.explicit_caret = value,
+                "trim-zeros" => style.trim_zeros = value,
+                _ => shell_warn(&format!("ignoring unknown `normalize-reqs.{}`", key))?,
+            }
+        }
+        Ok(style)
+    }
+}
+
+/// Rewrite a requirement in `style`
+///
+/// Build metadata is always dropped since cargo ignores it.  Only single caret requirements are
+/// restyled, anything else is left as written.  Returns `None` when `req` is already canonical.
+pub fn normalize_requirement(req: &str, style: ReqStyle) -> CargoResult<Option<String>> {
+    let parsed = semver::VersionReq::parse(req)
+        .with_context(|| format!("Invalid version requirement `{}`", req))?;
+    let normalized = match parsed.comparators.as_slice() {
+        [comparator] if comparator.op == semver::Op::Caret => {
+            let mut comparator = comparator.clone();
+            if style.trim_zeros && comparator.pre.is_empty() {
+                // `^1.0.0` matches the same versions as `^1` but `^0.0.0` is narrower than `^0`
+                if comparator.patch == Some(0)
+                    && (comparator.major != 0 || comparator.minor.unwrap_or(0) != 0)
+                {
+                    comparator.patch = None;
+                }
+                if comparator.patch.is_none()
+                    && comparator.minor == Some(0)
+                    && comparator.major != 0
+                {
+                    comparator.minor = None;
+                }
+            }
+            let normalized = comparator.to_string();
+            if style.explicit_caret {
+                normalized
+            } else {
+                normalized.trim_start_matches('^').to_owned()
+            }
+        }
+        _ if req.contains('+') => parsed.to_string(),
+        _ => return Ok(None),
+    };
+    if normalized == req {
+        Ok(None)
+    } else {
+        Ok(Some(normalized))
+    }
+}
+
+/// Parse a `rust-version`, which may leave off its minor and patch versions like `1.60`
+pub fn parse_rust_version(rust_version: &str) -> CargoResult<semver::Version> {
+    let invalid = || format!("Invalid `rust-version` `{}`", rust_version);
+    let mut parts = rust_version.trim().split('.');
+    let mut next = || -> CargoResult<u64> {
+        parts
+            .next()
+            .map_or(Ok(0), |part| part.parse())
+            .with_context(invalid)
+    };
+    let version = semver::Version::new(next()?, next()?, next()?);
+    if parts.next().is_some() {
+        anyhow::bail!(invalid());
+    }
+    Ok(version)
+}
+
+/// The `rust-version` of a package, following `rust-version.workspace = true` to its workspace
+///
+/// Returns `None` when the package doesn't declare one.
+pub fn package_rust_version(manifest: &LocalManifest) -> CargoResult<Option<semver::Version>> {
+    let rust_version = match manifest
+        .data
+        .get(manifest.package_key())
+        .and_then(|package| package.get("rust-version"))
+    {
+        Some(rust_version) => rust_version,
+        None => return Ok(None),
+    };
+    if let Some(rust_version) = rust_version.as_str() {
+        return parse_rust_version(rust_version).map(Some);
+    }
+    let inherited = rust_version
+        .get("workspace")
+        .and_then(|w| w.as_bool())
+        .unwrap_or(false);
+    if !inherited {
+        anyhow::bail!(
+            "`rust-version` in {} must be a string",
+            manifest.path.display()
+        );
+    }
+    let root = LocalManifest::try_new(&find_workspace_root(&manifest.path)?)?;
+    root.data
+        .get("workspace")
+        .and_then(|workspace| workspace.get("package"))
+        .and_then(|package| package.get("rust-version"))
+        .and_then(|rust_version| rust_version.as_str())
+        .with_context(|| {
+            format!(
+                "{} inherits `rust-version`, but {} doesn't set `workspace.package.rust-version`",
+                manifest.path.display(),
+                root.path.display()
+            )
+        })
+        .and_then(parse_rust_version)
+        .map(Some)
+}
+
 fn set_comparator(
     mut pred: semver::Comparator,
     version: &semver::Version,
@@ -226,6 +373,20 @@ fn assign_partial_req(
 mod test {
     use super::*;
 
+    #[test]
+    fn rust_version() {
+        assert_eq!(
+            parse_rust_version("1.60").unwrap(),
+            semver::Version::new(1, 60, 0)
+        );
+        assert_eq!(
+            parse_rust_version("1.56.1").unwrap(),
+            semver::Version::new(1, 56, 1)
+        );
+        assert!(parse_rust_version("1.60.0.1").is_err());
+        assert!(parse_rust_version("stable").is_err());
+    }
+
     mod increment {
         use super::*;
 
@@ -411,4 +572,56 @@ mod test {
             assert_req_bump("2.0.0", "=1.0.0", "=2.0.0");
         }
     }
+
+    mod normalize_requirement {
+        use super::*;
+
+        #[track_caller]
+        fn assert_normalized<'a, O: Into<Option<&'a str>>>(
+            req: &str,
+            style: ReqStyle,
+            expected: O,
+        ) {
+            let actual = normalize_requirement(req, style).unwrap();
+            assert_eq!(actual.as_deref(), expected.into());
+        }
+
+        #[test]
+        fn caret() {
+            let style = ReqStyle::default();
+            assert_normalized("1.2.3", style, None);
+            assert_normalized("^1.2.3", style, "1.2.3");
+            assert_normalized("~1.2.3", style, None);
+            assert_normalized(">=1.2, <2", style, None);
+
+            let style = ReqStyle {
+                explicit_caret: true,
+                ..Default::default()
+            };
+            assert_normalized("1.2.3", style, "^1.2.3");
+            assert_normalized("^1.2.3", style, None);
+        }
+
+        #[test]
+        fn trim_zeros() {
+            let style = ReqStyle {
+                trim_zeros: true,
+                ..Default::default()
+            };
+            assert_normalized("1.0.0", style, "1");
+            assert_normalized("1.2.0", style, "1.2");
+            assert_normalized("0.1.0", style, "0.1");
+            assert_normalized("0.0.0", style, None);
+            assert_normalized("0.0", style, None);
+            assert_normalized("1.0.0-alpha.1", style, None);
+            assert_normalized("~1.0.0", style, None);
+        }
+
+        #[test]
+        fn build_metadata() {
+            let style = ReqStyle::default();
+            assert_normalized("1.2.3+zstd.1.5", style, "1.2.3");
+            assert_normalized("=1.2.3+zstd.1.5", style, "=1.2.3");
+        }
+    }
 }
@@ -6,6 +6,7 @@
 extern crate cargo_test_macro;
 
 mod dry_run;
+mod dry_run_diff;
 mod invalid_arg;
 mod invalid_dep;
 mod invalid_rm_target;
@@ -15,15 +16,28 @@ mod invalid_section_dep;
 mod no_arg;
 mod rm_avoid_empty_tables;
 mod rm_build;
+mod rm_build_script;
 mod rm_dev;
 mod rm_existing;
+mod rm_future_keys;
+mod rm_include_nested;
+mod rm_keep_empty_tables;
 mod rm_multiple_deps;
 mod rm_multiple_dev;
 mod rm_optional_dep_feature;
 mod rm_optional_feature;
+mod rm_optional_feature_usage;
+mod rm_recursive;
+mod rm_recursive_package_root;
+mod rm_separator_spelling;
+mod rm_stdin;
+mod rm_stdin_optional;
 mod rm_target;
 mod rm_target_build;
 mod rm_target_dev;
+mod rm_target_prune_empty;
+mod rm_weak_dep_feature;
+mod rm_workspace_dep;
 
 fn init_registry() {
     cargo_test_support::registry::init();
@@ -0,0 +1,6 @@
+use semver::Version;
+
+fn main() {
+    let version = Version::parse(env!("CARGO_PKG_VERSION")).unwrap();
+    println!("cargo:rustc-env=MAJOR={}", version.major);
+}
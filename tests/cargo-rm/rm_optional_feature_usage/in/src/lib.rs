@@ -0,0 +1,5 @@
+#[cfg(feature = "semver")]
+pub use semver::Version;
+
+#[cfg(feature = "versioning")]
+pub fn version() {}
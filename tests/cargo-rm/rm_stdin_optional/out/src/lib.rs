@@ -0,0 +1,2 @@
+#[cfg(feature = "semver")]
+pub use semver::Version;
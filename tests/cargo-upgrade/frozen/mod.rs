@@ -0,0 +1,33 @@
+use cargo_test_support::compare::assert_ui;
+use cargo_test_support::Project;
+
+use crate::init_registry;
+use crate::CargoCommand;
+use cargo_test_support::curr_dir;
+
+#[cargo_test]
+fn case() {
+    init_registry();
+    let project = Project::from_template(curr_dir!().join("in"));
+    let project_root = project.root();
+    let cwd = &project_root;
+
+    // `--frozen` runs offline, so the index has to be cached first
+    snapbox::cmd::Command::cargo_ui()
+        .arg("upgrade")
+        .args(["--dry-run"])
+        .current_dir(cwd)
+        .assert()
+        .success();
+
+    snapbox::cmd::Command::cargo_ui()
+        .arg("upgrade")
+        .args(["--frozen"])
+        .current_dir(cwd)
+        .assert()
+        .failure()
+        .stdout_matches_path(curr_dir!().join("stdout.log"))
+        .stderr_matches_path(curr_dir!().join("stderr.log"));
+
+    assert_ui().subset_matches(curr_dir!().join("out"), &project_root);
+}
@@ -0,0 +1,46 @@
+use cargo_test_support::compare::assert_ui;
+use cargo_test_support::Project;
+
+use crate::init_registry;
+use crate::CargoCommand;
+use cargo_test_support::curr_dir;
+
+#[cargo_test]
+fn case() {
+    init_registry();
+    let git_dep = cargo_test_support::git::new("git-dep", |project| {
+        project
+            .file(
+                "Cargo.toml",
+                &cargo_test_support::basic_manifest("git-dep", "0.1.0"),
+            )
+            .file("src/lib.rs", "")
+    });
+    let repo = git2::Repository::open(git_dep.root()).unwrap();
+    cargo_test_support::git::tag(&repo, "v0.1.0");
+    git_dep.change_file("src/lib.rs", "pub fn hello() {}");
+    cargo_test_support::git::add(&repo);
+    cargo_test_support::git::commit(&repo);
+    cargo_test_support::git::tag(&repo, "v0.2.0");
+
+    let project = Project::from_template(curr_dir!().join("in"));
+    let project_root = project.root();
+    let cwd = &project_root;
+    // The repository's URL is only known once it exists
+    let manifest = std::fs::read_to_string(project_root.join("Cargo.toml")).unwrap();
+    project.change_file(
+        "Cargo.toml",
+        &manifest.replace("GIT_URL", git_dep.url().as_str()),
+    );
+
+    snapbox::cmd::Command::cargo_ui()
+        .arg("upgrade")
+        .args(["--git-deps", "tag"])
+        .current_dir(cwd)
+        .assert()
+        .success()
+        .stdout_matches_path(curr_dir!().join("stdout.log"))
+        .stderr_matches_path(curr_dir!().join("stderr.log"));
+
+    assert_ui().subset_matches(curr_dir!().join("out"), &project_root);
+}
@@ -0,0 +1,29 @@
+use cargo_test_support::compare::assert_ui;
+use cargo_test_support::Project;
+
+use crate::init_registry;
+use crate::CargoCommand;
+use cargo_test_support::curr_dir;
+
+#[cargo_test]
+fn case() {
+    init_registry();
+    let project = Project::from_template(curr_dir!().join("in"));
+    let project_root = project.root();
+    let cwd = &project_root;
+    // Edit the manifest after `Cargo.lock` was written
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let manifest_path = project_root.join("Cargo.toml");
+    std::fs::write(&manifest_path, std::fs::read(&manifest_path).unwrap()).unwrap();
+
+    snapbox::cmd::Command::cargo_ui()
+        .arg("upgrade")
+        .args(["--dry-run"])
+        .current_dir(cwd)
+        .assert()
+        .success()
+        .stdout_matches_path(curr_dir!().join("stdout.log"))
+        .stderr_matches_path(curr_dir!().join("stderr.log"));
+
+    assert_ui().subset_matches(curr_dir!().join("out"), &project_root);
+}
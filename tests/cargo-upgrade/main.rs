@@ -6,26 +6,83 @@
 extern crate cargo_test_macro;
 
 mod alt_registry;
+mod check_duplicates;
+mod check_majors;
+mod compatible;
+mod compatible_only;
+mod compatible_only_default_members;
+mod constraints;
+mod default_members;
 mod dry_run;
+mod dry_run_env;
+mod env_flags;
 mod exclude_dep;
+mod exclude_expired;
+mod exclude_from;
+mod exclude_lock_update;
+mod exclude_remember;
 mod exclude_renamed;
+mod exclude_renamed_package;
+mod exclude_scoped;
+mod explicit_default_features;
+mod explicit_default_features_declined;
+mod fix_deprecations;
+mod fix_wildcards;
+mod frozen;
+mod git_deps;
+mod git_deps_only;
+mod group_by_dep;
+mod html;
+mod ignore_errors;
 mod implicit_prerelease;
+mod include_nested;
+mod interactive;
 mod invalid_dep;
 mod invalid_flag;
 mod invalid_manifest;
 mod invalid_workspace_root_manifest;
+mod json;
+mod kind;
+mod legacy_manifest;
 mod locked;
+mod locked_stale;
+mod lockfile_stale;
+mod manifest_list;
+mod manifest_policy;
+mod max_date;
+mod narrow_terminal;
+mod normalize_reqs;
+mod notify_command;
+mod notify_untrusted;
+mod only;
 mod optional_dep;
 mod pinned;
+mod pinned_exact;
 mod preserve_op;
 mod preserve_precision_major;
 mod preserve_precision_minor;
 mod preserve_precision_patch;
 mod preserves_inline_table;
 mod preserves_std_table;
+mod public_dep_hazard;
+mod published_crate;
+mod published_manifest;
+mod read_only;
+mod recursive_dirs;
+mod registry_isolation;
+mod renovate;
+mod respect_msrv;
+mod separator_spelling;
 mod single_dep;
 mod skip_compatible;
 mod specified;
+mod specified_renamed;
+mod standalone_manifest_path;
+mod strategy_minimal;
+mod summary;
+mod target;
+mod test_support;
+mod timings;
 mod to_lockfile;
 mod to_version;
 mod upgrade_all;